@@ -17,6 +17,8 @@ pub struct BasicBoard {
     pub current: Color,
     pub castling_rights: [bool; 4],
     pub en_passant: i8,
+    /// Plies since the last capture or pawn move
+    pub halfmove_clock: u64,
 
     pub material_score: i32,
 
@@ -46,6 +48,7 @@ impl BasicBoard {
             castling_rights: [true; 4],
 
             en_passant: 8,
+            halfmove_clock: 0,
 
             material_score: 0,
 
@@ -63,6 +66,7 @@ impl BasicBoard {
             current: White,
             castling_rights: [true; 4],
             en_passant: 8,
+            halfmove_clock: 0,
             material_score: 0,
             clock: [initial_time_limit; 2],
             move_count: 0,
@@ -124,6 +128,12 @@ impl Board for BasicBoard {
         let movable = self.piece_at(m.from);
         let replaces = self.piece_at(m.to);
 
+        if movable.is_pawn() || !replaces.is_empty() {
+            new_board.halfmove_clock = 0;
+        } else {
+            new_board.halfmove_clock += 1;
+        }
+
         new_board.material_score += self.piece_at(m.to).material_worth();
 
         if movable == BlackKing {
@@ -263,6 +273,15 @@ impl Board for BasicBoard {
         new_board
     }
 
+    fn null_move(&self) -> Self {
+        let mut new_board = self.clone();
+
+        new_board.en_passant = 8;
+        new_board.current = self.current.other();
+
+        new_board
+    }
+
 
     fn all_pieces(&self) -> Vec<(Piece, Location)> {
         (0..8).map(move |i| (0..8).map(move |j| {
@@ -290,6 +309,11 @@ impl Board for BasicBoard {
             }
         }
 
+        // the fifty-move rule
+        if self.halfmove_clock >= 100 {
+            return Some(Color::EmptyColor);
+        }
+
         None
     }
 
@@ -319,6 +343,14 @@ impl Board for BasicBoard {
         self.material_score
     }
 
+    fn halfmove_clock(&self) -> u64 {
+        self.halfmove_clock
+    }
+
+    fn move_count(&self) -> u64 {
+        self.move_count as u64
+    }
+
     fn get_clock(&self) -> [Duration; 2] {
         self.clock
     }
@@ -336,6 +368,10 @@ impl Board for BasicBoard {
         self.clock = [time;2];
     }
 
+    fn set_clocks(&mut self, clock: [Duration; 2]) {
+        self.clock = clock;
+    }
+
     fn heuristic(&self) -> f64 {
         self.get_material_score() as f64
     }
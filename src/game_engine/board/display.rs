@@ -12,8 +12,7 @@ use std::time::Duration;
 pub struct DisplayableBoard<B> {
     pub inner: B,
     highlighted: Vec<Location>,
-    last_from: Option<Location>,
-    last_to: Option<Location>,
+    last_move: Option<Move>,
 }
 
 
@@ -42,7 +41,7 @@ impl<B: Board> fmt::Display for DisplayableBoard<B> {
                     write!(f, "\x1b[103m")?;
                 }
 
-                if let Some(lc) = self.last_from {
+                if let Some(lc) = self.last_move.map(|m| m.from) {
                     if x == lc.x && y == lc.y {
                         stdout().queue(SetBackgroundColor(Rgb {
                             r: 153,
@@ -52,7 +51,7 @@ impl<B: Board> fmt::Display for DisplayableBoard<B> {
                     }
                 }
 
-                if let Some(lc) = self.last_to {
+                if let Some(lc) = self.last_move.map(|m| m.to) {
                     if x == lc.x && y == lc.y {
                         stdout().queue(SetBackgroundColor(Rgb {
                             r: 153,
@@ -85,11 +84,15 @@ impl<B: Board> DisplayableBoard<B> {
         Self {
             inner,
             highlighted: Vec::new(),
-            last_from: None,
-            last_to: None,
+            last_move: None,
         }
     }
 
+    /// The move that led to this board, if any.
+    pub fn last_move(&self) -> Option<Move> {
+        self.last_move
+    }
+
 
     pub fn highlight(&mut self, locations: Vec<Location>) {
         self.highlighted = locations;
@@ -114,8 +117,16 @@ impl<B> Board for DisplayableBoard<B> where B: Board {
         Self {
             inner: res,
             highlighted: self.highlighted.clone(),
-            last_from: Some(m.from),
-            last_to: Some(m.to),
+            last_move: Some(m),
+        }
+    }
+
+    #[inline]
+    fn null_move(&self) -> Self {
+        Self {
+            inner: self.inner.null_move(),
+            highlighted: self.highlighted.clone(),
+            last_move: self.last_move,
         }
     }
 
@@ -149,6 +160,16 @@ impl<B> Board for DisplayableBoard<B> where B: Board {
         self.inner.get_material_score()
     }
 
+    #[inline]
+    fn halfmove_clock(&self) -> u64 {
+        self.inner.halfmove_clock()
+    }
+
+    #[inline]
+    fn move_count(&self) -> u64 {
+        self.inner.move_count()
+    }
+
     #[inline]
     fn piece_at(&self, l: impl Into<Location>) -> Piece {
         self.inner.piece_at(l)
@@ -167,6 +188,10 @@ impl<B> Board for DisplayableBoard<B> where B: Board {
         self.inner.set_clock(time);
     }
 
+    fn set_clocks(&mut self, clock: [Duration; 2]) {
+        self.inner.set_clocks(clock);
+    }
+
     fn hash(&self) -> u64 {
         self.inner.hash()
     }
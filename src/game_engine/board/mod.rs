@@ -3,14 +3,16 @@ pub use basic::BasicBoard;
 use crate::game_engine::chess_move::{Location, Move};
 use crate::game_engine::color::Color;
 use crate::game_engine::piece::Piece;
+use crate::tablebase::{self, Probe};
 use std::time::Duration;
 
 pub mod basic;
 pub mod zobrist;
 pub mod pst;
 pub mod display;
+pub mod polyglot;
 
-pub trait Board: Sized + Clone {
+pub trait Board: Sized + Clone + Send + Sync {
     fn moves(&self, location: impl Into<Location>) -> Vec<Move>;
     fn all_moves(&self) -> Vec<Move>;
 
@@ -25,6 +27,10 @@ pub trait Board: Sized + Clone {
         add_piece: impl FnMut(Piece, Location),
     ) -> Self;
 
+    /// Passes the turn to the other player without moving a piece (a "null move").
+    /// Any en passant opportunity is lost, the castling rights and clocks are kept.
+    fn null_move(&self) -> Self;
+
     fn all_pieces(&self) -> Vec<(Piece, Location)>;
 
     fn is_terminal(&self) -> Option<Color>;
@@ -37,10 +43,23 @@ pub trait Board: Sized + Clone {
     fn piece_at_mut(&mut self, l: impl Into<Location>) -> &mut Piece;
     fn get_clock(&self) -> [Duration; 2];
     fn set_clock(&mut self, time : Duration);
+    /// Sets the time left of white and black, like `get_clock` returns it.
+    fn set_clocks(&mut self, clock: [Duration; 2]);
 
     fn get_material_score(&self) -> i32;
+    /// Plies since the last capture or pawn move. At 100 the game is a draw by the fifty-move rule,
+    /// and no position before the last capture or pawn move can be repeated.
+    fn halfmove_clock(&self) -> u64;
+    /// Plies played since the board was set up.
+    fn move_count(&self) -> u64;
     fn heuristic(&self) -> f64;
 
+    /// The result of the position with perfect play, from the endgame tablebases that are installed.
+    /// `None` when the position isn't in them.
+    fn probe_tablebase(&self) -> Option<Probe> {
+        tablebase::probe(self)
+    }
+
     // TODO: use built in hash trait
     fn hash(&self) -> u64;
 }
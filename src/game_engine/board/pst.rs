@@ -2,6 +2,8 @@ use crate::game_engine::board::Board;
 use crate::game_engine::chess_move::{Move, Location};
 use crate::game_engine::piece::Piece;
 use crate::game_engine::color::Color;
+use crate::game_engine::endgame::{self, PieceCounts};
+use std::cell::Cell;
 use std::fmt;
 use std::time::Duration;
 
@@ -117,6 +119,8 @@ pub fn pos_score(p: Piece, l: Location) -> i32 {
 pub struct PSTBoard<B> {
     pub(crate) inner: B,
     pub heuristic_value: i32,
+    /// The pieces on the board, to recognise endgames
+    pub material: PieceCounts,
 }
 
 impl<B: fmt::Display> fmt::Display for PSTBoard<B> {
@@ -137,6 +141,7 @@ impl<B: Board> PSTBoard<B> {
         }
 
         Self {
+            material: PieceCounts::of(&inner),
             inner,
             heuristic_value: heuristic,
         }
@@ -157,6 +162,8 @@ impl<B> Board for PSTBoard<B> where B: Board {
     fn transition_with_move_func(&self, m: Move, mut remove_piece: impl FnMut(Piece, Location), mut add_piece: impl FnMut(Piece, Location)) -> Self {
         let mut hv1 = 0;
         let mut hv2 = 0;
+        // both closures count the pieces, which they can't both borrow mutably
+        let material = Cell::new(self.material);
 
 
         let inner = self.inner.transition_with_move_func(m, |p, l| {
@@ -170,12 +177,18 @@ impl<B> Board for PSTBoard<B> where B: Board {
 
             hv1 += p.material_worth();
             hv1 -= pos_score(p,l);
+            let mut counts = material.get();
+            counts.remove(p);
+            material.set(counts);
 
             remove_piece(p, l);
         }, |p, l| {
 
             hv2 -= p.material_worth();
             hv2 += pos_score(p,l);
+            let mut counts = material.get();
+            counts.add(p);
+            material.set(counts);
 
             add_piece(p, l);
         });
@@ -184,6 +197,16 @@ impl<B> Board for PSTBoard<B> where B: Board {
         Self {
             inner,
             heuristic_value: self.heuristic_value+hv1+hv2,
+            material: material.get(),
+        }
+    }
+
+    #[inline]
+    fn null_move(&self) -> Self {
+        Self {
+            inner: self.inner.null_move(),
+            heuristic_value: self.heuristic_value,
+            material: self.material,
         }
     }
 
@@ -216,6 +239,14 @@ impl<B> Board for PSTBoard<B> where B: Board {
         self.inner.get_material_score()
     }
 
+    fn halfmove_clock(&self) -> u64 {
+        self.inner.halfmove_clock()
+    }
+
+    fn move_count(&self) -> u64 {
+        self.inner.move_count()
+    }
+
     #[inline]
     fn piece_at(&self, l: impl Into<Location>) -> Piece {
         self.inner.piece_at(l)
@@ -234,12 +265,16 @@ impl<B> Board for PSTBoard<B> where B: Board {
         self.inner.set_clock(time);
     }
 
+    fn set_clocks(&mut self, clock: [Duration; 2]) {
+        self.inner.set_clocks(clock);
+    }
+
     fn hash(&self) -> u64 {
         self.inner.hash()
     }
 
     fn heuristic(&self) -> f64 {
-        self.heuristic_value as f64
+        endgame::evaluate(self, &self.material, self.heuristic_value as f64)
     }
 }
 
@@ -251,15 +286,16 @@ mod tests {
     use crate::game_engine::board::display::DisplayableBoard;
     use crate::solver::Solver;
     use crate::stats::Stats;
+    use std::time::Duration;
 
 
     #[test]
     fn test_heuristic_fuzzer() {
         for _ in 0..1000 {
-            let board = BasicBoard::DEFAULT_BOARD;
-            let mut pst_board = PSTBoard::new(DisplayableBoard::new(board));
+            let board = BasicBoard::default_board(Duration::from_secs(60));
+            let mut pst_board = DisplayableBoard::new(PSTBoard::new(board));
             let mut random_player = RandomPlay::new();
-            let s = Stats::new();
+            let s = Stats::new("Random Search", None, None, String::new(), false);
 
             for _ in 0..50 {
                 pst_board = match random_player.make_move(pst_board.clone(), s.clone()) {
@@ -268,11 +304,12 @@ mod tests {
                 }
             }
 
-            let pst_board_2 = PSTBoard::new(pst_board.inner.clone());
+            let pst_board_2 = PSTBoard::new(pst_board.inner.inner.clone());
 
             // println!("{}", pst_board_2);
             // println!("{}", pst_board);
-            assert_eq!(pst_board.heuristic_value, pst_board_2.heuristic_value);
+            assert_eq!(pst_board.inner.heuristic_value, pst_board_2.heuristic_value);
+            assert_eq!(pst_board.inner.material, pst_board_2.material);
         }
     }
 }
\ No newline at end of file
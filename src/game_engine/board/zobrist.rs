@@ -205,6 +205,18 @@ impl<B> Board for ZobristBoard<B> where B: Board {
         }
     }
 
+    fn null_move(&self) -> Self {
+        let inner = self.inner.null_move();
+
+        let mut hash = ZOBRIST_KEYS.switch_color(self.hash);
+        hash = ZOBRIST_KEYS.update_en_passant(hash, self.get_en_passant(), inner.get_en_passant());
+
+        Self {
+            inner,
+            hash,
+        }
+    }
+
     #[inline]
     fn all_pieces(&self) -> Vec<(Piece, Location)> {
         self.inner.all_pieces()
@@ -234,6 +246,14 @@ impl<B> Board for ZobristBoard<B> where B: Board {
         self.inner.get_material_score()
     }
 
+    fn halfmove_clock(&self) -> u64 {
+        self.inner.halfmove_clock()
+    }
+
+    fn move_count(&self) -> u64 {
+        self.inner.move_count()
+    }
+
     #[inline]
     fn piece_at(&self, l: impl Into<Location>) -> Piece {
         self.inner.piece_at(l)
@@ -252,6 +272,10 @@ impl<B> Board for ZobristBoard<B> where B: Board {
         self.inner.set_clock(time);
     }
 
+    fn set_clocks(&mut self, clock: [Duration; 2]) {
+        self.inner.set_clocks(clock);
+    }
+
     fn hash(&self) -> u64 {
         self.hash
     }
@@ -270,6 +294,9 @@ mod tests {
     use crate::solver::Solver;
     use crate::game_engine::board::display::DisplayableBoard;
     use crate::stats::Stats;
+    use crate::game_engine::chess_move::Extra;
+    use crate::game_engine::color::Color;
+    use std::time::Duration;
 
     #[test]
     fn test_switch_color_twice() {
@@ -282,7 +309,7 @@ mod tests {
 
     #[test]
     fn test_hash_move_back() {
-        let board = BasicBoard::DEFAULT_BOARD;
+        let board = BasicBoard::default_board(Duration::from_secs(60));
         let mut zboard = ZobristBoard::new(board);
 
         let initial_hash = zboard.hash;
@@ -300,10 +327,30 @@ mod tests {
         assert_eq!(initial_hash, zboard.hash);
     }
 
+    #[test]
+    fn test_null_move_hash() {
+        let board = BasicBoard::default_board(Duration::from_secs(60));
+        let zboard = ZobristBoard::new(board);
+
+        let passed = zboard.null_move();
+        assert_ne!(zboard.hash, passed.hash);
+        assert_eq!(ZobristBoard::new(passed.inner.clone()).hash, passed.hash);
+        assert_eq!(zboard.hash, passed.null_move().hash);
+
+        // a double pawn push allows en passant, which the null move takes away again
+        let pushed = zboard.transition(((4, 6), (4, 4), Extra::DoublePawn).into());
+        assert_eq!(pushed.get_en_passant(), 4);
+
+        let passed = pushed.null_move();
+        assert_eq!(passed.get_en_passant(), 8);
+        assert_eq!(passed.current_player(), Color::White);
+        assert_eq!(ZobristBoard::new(passed.inner.clone()).hash, passed.hash);
+    }
+
     #[test]
     fn test_hash_two_boards() {
-        let board1 = BasicBoard::DEFAULT_BOARD;
-        let board2 = BasicBoard::DEFAULT_BOARD;
+        let board1 = BasicBoard::default_board(Duration::from_secs(60));
+        let board2 = BasicBoard::default_board(Duration::from_secs(60));
         let zboard1 = ZobristBoard::new(board1);
         let zboard2 = ZobristBoard::new(board2);
 
@@ -314,11 +361,11 @@ mod tests {
     #[test]
     fn test_hash_fuzzer() {
         for _ in 0..10 {
-            let board = BasicBoard::DEFAULT_BOARD;
-            let mut zboard = ZobristBoard::new(DisplayableBoard::new(board));
+            let board = BasicBoard::default_board(Duration::from_secs(60));
+            let mut zboard = DisplayableBoard::new(ZobristBoard::new(board));
             let mut random_player = RandomPlay::new();
 
-            let s = Stats::new();
+            let s = Stats::new("Random Search", None, None, String::new(), false);
 
             for _ in 0..100 {
                 zboard = match random_player.make_move(zboard.clone(), s.clone()) {
@@ -327,13 +374,12 @@ mod tests {
                 }
             }
 
-            let zboard2 = ZobristBoard::new(zboard.inner.clone());
+            let zboard2 = ZobristBoard::new(zboard.inner.inner.clone());
 
-            println!("{}", zboard2);
             println!("{}", zboard);
 
 
-            assert_eq!(zboard.hash, zboard2.hash);
+            assert_eq!(zboard.inner.hash, zboard2.hash);
         }
     }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use crate::game_engine::piece::{Piece, knight_of_color, bishop_of_color, rook_of_color, queen_of_color};
 use crate::game_engine::color::Color;
+use std::fmt;
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
 pub struct Location {
@@ -16,6 +17,25 @@ impl Location {
             x, y
         }
     }
+
+    /// Parses the name of a square, like "e4".
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        let file = chars.next()?;
+        let rank = chars.next()?;
+        if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return None;
+        }
+
+        Some(Self::new(file as i8 - 'a' as i8, '8' as i8 - rank as i8))
+    }
+}
+
+/// The name of the square, like "e4"
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.x as u8) as char, 8 - self.y)
+    }
 }
 
 impl From<(i8, i8)> for Location {
@@ -47,13 +67,33 @@ pub enum Extra {
 }
 
 impl Extra {
+    /// The inverse of `extra as u8`
+    pub fn from_number(n: u8) -> Option<Extra> {
+        Some(match n {
+            0 => Extra::Quiet,
+            1 => Extra::DoublePawn,
+            2 => Extra::KingCastle,
+            3 => Extra::QueenCastle,
+            4 => Extra::Captures,
+            5 => Extra::EnPassantCaptures,
+            8 => Extra::KnightPromotion,
+            9 => Extra::BishopPromotion,
+            10 => Extra::RookPromotion,
+            11 => Extra::QueenPromotion,
+            12 => Extra::KnightPromotionCapture,
+            13 => Extra::BishopPromotionCapture,
+            14 => Extra::RookPromotionCapture,
+            15 => Extra::QueenPromotionCapture,
+            _ => return None,
+        })
+    }
 
     pub fn is_capturing(&self) -> bool {
         match self {
             Extra::Captures => true,
             Extra::EnPassantCaptures => true,
             Extra::KnightPromotionCapture => true,
-            Extra::BishopPromotion => true,
+            Extra::BishopPromotionCapture => true,
             Extra::RookPromotionCapture => true,
             Extra::QueenPromotionCapture => true,
             _ => false,
@@ -0,0 +1,328 @@
+//! Knowledge of endgames that the piece-square tables get wrong: they can't tell the winning
+//! side how to drive the other king into a corner to mate it, and they see an extra piece as
+//! winning even when it can't mate. Without tablebases, the evaluation recognises these
+//! endgames by their material, and adds a mop-up term or scales the evaluation towards a draw.
+
+use crate::game_engine::board::Board;
+use crate::game_engine::board::pst::pos_score;
+use crate::game_engine::chess_move::Location;
+use crate::game_engine::color::Color;
+use crate::game_engine::piece::Piece;
+
+/// Endgames with more pieces than this (besides the kings) are left to the piece-square tables.
+const MAX_PIECES: u8 = 6;
+
+/// The weights of the mop-up term, in centipawns: for the losing king's distance from the
+/// centre (or the right corner), and for how close the winning king is to it.
+const MOP_UP_EDGE: f64 = 20.0;
+const MOP_UP_KINGS: f64 = 10.0;
+
+/// The number of pieces of every kind on the board, indexed by `Piece as usize`. Empty squares aren't counted.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Default)]
+pub struct PieceCounts([u8; 13]);
+
+impl PieceCounts {
+    pub fn of(board: &impl Board) -> Self {
+        let mut counts = Self::default();
+        for x in 0..8 {
+            for y in 0..8 {
+                counts.add(board.piece_at((x, y)));
+            }
+        }
+        counts
+    }
+
+    pub fn add(&mut self, piece: Piece) {
+        if !piece.is_empty() {
+            self.0[piece as usize] += 1;
+        }
+    }
+
+    pub fn remove(&mut self, piece: Piece) {
+        if !piece.is_empty() {
+            self.0[piece as usize] -= 1;
+        }
+    }
+
+    pub fn count(&self, piece: Piece) -> u8 {
+        self.0[piece as usize]
+    }
+
+    fn side(&self, color: Color) -> Side {
+        let (pawn, knight, bishop, rook, queen) = if color == Color::White {
+            (Piece::WhitePawn, Piece::WhiteKnight, Piece::WhiteBishop, Piece::WhiteRook, Piece::WhiteQueen)
+        } else {
+            (Piece::BlackPawn, Piece::BlackKnight, Piece::BlackBishop, Piece::BlackRook, Piece::BlackQueen)
+        };
+
+        Side {
+            pawns: self.count(pawn),
+            knights: self.count(knight),
+            bishops: self.count(bishop),
+            rooks: self.count(rook),
+            queens: self.count(queen),
+        }
+    }
+}
+
+/// The pieces of one side, besides its king.
+#[derive(Clone, Copy, Debug)]
+struct Side {
+    pawns: u8,
+    knights: u8,
+    bishops: u8,
+    rooks: u8,
+    queens: u8,
+}
+
+impl Side {
+    fn pieces(&self) -> u8 {
+        self.pawns + self.minors() + self.rooks + self.queens
+    }
+
+    fn minors(&self) -> u8 {
+        self.knights + self.bishops
+    }
+
+    fn is_bare(&self) -> bool {
+        self.pieces() == 0
+    }
+
+    /// Whether the pieces can mate at all. Two knights can't force it, and are counted as not.
+    fn can_mate(&self) -> bool {
+        self.pawns > 0 || self.rooks > 0 || self.queens > 0 || (self.bishops > 0 && self.minors() >= 2)
+    }
+
+    /// Whether the pieces can mate a bare king without help from pawns.
+    fn can_force_mate(&self) -> bool {
+        self.rooks > 0 || self.queens > 0 || (self.bishops > 0 && self.minors() >= 2)
+    }
+}
+
+/// Where the pieces the rules look at are, found in one pass over the board. Everything is
+/// indexed by `side_index`.
+#[derive(Default)]
+struct Squares {
+    kings: [Option<Location>; 2],
+    /// A bishop of each side. The rules only look at sides with a single bishop.
+    bishops: [Option<Location>; 2],
+    /// The file of the pawns of each side, if there are any
+    pawn_files: [Option<i8>; 2],
+    /// Whether the pawns of each side are on more than one file
+    pawns_spread: [bool; 2],
+}
+
+impl Squares {
+    fn of(board: &impl Board) -> Self {
+        let mut squares = Self::default();
+        for x in 0..8 {
+            for y in 0..8 {
+                let piece = board.piece_at((x, y));
+                if piece.is_empty() {
+                    continue;
+                }
+
+                let side = side_index(piece.color());
+                let l = Location { x, y };
+                if piece.is_king() {
+                    squares.kings[side] = Some(l);
+                } else if piece.is_bishop() {
+                    squares.bishops[side] = Some(l);
+                } else if piece.is_pawn() {
+                    match squares.pawn_files[side] {
+                        Some(file) if file != x => squares.pawns_spread[side] = true,
+                        _ => squares.pawn_files[side] = Some(x),
+                    }
+                }
+            }
+        }
+        squares
+    }
+}
+
+fn side_index(color: Color) -> usize {
+    if color == Color::White { 0 } else { 1 }
+}
+
+fn king_of(color: Color) -> Piece {
+    if color == Color::White { Piece::WhiteKing } else { Piece::BlackKing }
+}
+
+fn distance(a: Location, b: Location) -> i8 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+/// The number of files and ranks to the nearest of the four centre squares, from 0 to 6.
+fn centre_distance(l: Location) -> i8 {
+    let axis = |c: i8| if c < 4 { 3 - c } else { c - 4 };
+    axis(l.x) + axis(l.y)
+}
+
+fn is_light(l: Location) -> bool {
+    // a8 (x = 0, y = 0) is a light square
+    (l.x + l.y) % 2 == 0
+}
+
+/// The bonus for the side with mating material against a bare king: the losing king belongs
+/// on the edge (in a corner of the bishop's color with a bishop and a knight), with the kings close.
+/// It replaces the piece-square tables of the kings, which want the losing king on its back rank.
+fn mop_up(squares: &Squares, counts: &PieceCounts, winner: Color) -> f64 {
+    let (king, loser) = match (squares.kings[side_index(winner)], squares.kings[side_index(winner.other())]) {
+        (Some(king), Some(loser)) => (king, loser),
+        _ => return 0.0,
+    };
+    let side = counts.side(winner);
+
+    let edge = if side.bishops == 1 && side.knights == 1 && side.rooks == 0 && side.queens == 0 {
+        let light = squares.bishops[side_index(winner)].is_some_and(is_light);
+        // the corners that can be mated in, 7 moves away from the other two
+        let corners = if light { [(0, 0), (7, 7)] } else { [(7, 0), (0, 7)] };
+        let corner = corners.iter().map(|&(x, y)| distance(loser, Location { x, y })).min().unwrap();
+        2 * (7 - corner)
+    } else {
+        centre_distance(loser)
+    };
+
+    let sign = if winner == Color::White { 1.0 } else { -1.0 };
+    let tables = pos_score(king_of(winner), king) + pos_score(king_of(winner.other()), loser);
+
+    sign * (MOP_UP_EDGE * edge as f64 + MOP_UP_KINGS * (7 - distance(king, loser)) as f64) - tables as f64
+}
+
+/// Whether `strong` only has pawns on one rook file, maybe with a bishop that doesn't cover the
+/// promotion square, and the bare king of the other side is in the corner in front of them.
+fn is_wrong_rook_pawn(squares: &Squares, counts: &PieceCounts, strong: Color) -> bool {
+    let side = counts.side(strong);
+    if side.pawns == 0 || side.knights > 0 || side.bishops > 1 || side.rooks > 0 || side.queens > 0 {
+        return false;
+    }
+
+    let file = match squares.pawn_files[side_index(strong)] {
+        Some(file) if !squares.pawns_spread[side_index(strong)] && (file == 0 || file == 7) => file,
+        _ => return false,
+    };
+
+    let promotion = Location { x: file, y: if strong == Color::White { 0 } else { 7 } };
+    if side.bishops == 1 && squares.bishops[side_index(strong)].map(is_light) == Some(is_light(promotion)) {
+        return false;
+    }
+
+    squares.kings[side_index(strong.other())].is_some_and(|defender| distance(defender, promotion) <= 1)
+}
+
+/// Corrects the evaluation `eval` (positive when white is better) of `board` for the endgames
+/// this module knows about.
+pub fn evaluate(board: &impl Board, counts: &PieceCounts, eval: f64) -> f64 {
+    let white = counts.side(Color::White);
+    let black = counts.side(Color::Black);
+    if white.pieces() + black.pieces() > MAX_PIECES {
+        return eval;
+    }
+
+    // neither side can win
+    if !white.can_mate() && !black.can_mate() {
+        return 0.0;
+    }
+
+    // a side that can't mate can't be better than a draw
+    let mut eval = eval;
+    if !white.can_mate() {
+        eval = eval.min(0.0);
+    }
+    if !black.can_mate() {
+        eval = eval.max(0.0);
+    }
+
+    let squares = Squares::of(board);
+    for &(color, strong, weak) in &[(Color::White, white, black), (Color::Black, black, white)] {
+        if weak.is_bare() {
+            if is_wrong_rook_pawn(&squares, counts, color) {
+                return 0.0;
+            }
+            if strong.can_force_mate() {
+                return eval + mop_up(&squares, counts, color);
+            }
+        }
+    }
+
+    if white.pawns == 0 && black.pawns == 0 {
+        let only_minors = |side: Side| side.rooks == 0 && side.queens == 0 && side.minors() <= 2;
+        // minor pieces against a minor piece, like a bishop against a knight
+        if only_minors(white) && only_minors(black) && white.minors() > 0 && black.minors() > 0 {
+            return eval / 16.0;
+        }
+        // a rook against a minor piece, or a rook and a minor piece against a rook
+        let rook_vs_minor = |a: Side, b: Side| {
+            a.queens == 0 && b.queens == 0 && a.rooks == 1 && a.minors() <= 1
+                && ((b.rooks == 0 && b.minors() == 1) || (b.rooks == 1 && b.minors() == 0 && a.minors() == 1))
+        };
+        if rook_vs_minor(white, black) || rook_vs_minor(black, white) {
+            return eval / 4.0;
+        }
+    }
+
+    // bishops of opposite colors, with only pawns besides them
+    let only_bishop = |side: Side| side.bishops == 1 && side.knights == 0 && side.rooks == 0 && side.queens == 0;
+    if only_bishop(white) && only_bishop(black) {
+        if let [Some(white), Some(black)] = squares.bishops {
+            if is_light(white) != is_light(black) {
+                return eval / 2.0;
+            }
+        }
+    }
+
+    eval
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::pst::PSTBoard;
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::chess_move::Location;
+    use crate::game_engine::color::Color;
+    use crate::game_engine::piece::Piece;
+    use std::time::Duration;
+
+    fn board(pieces: &[(Piece, &str)]) -> PSTBoard<BasicBoard> {
+        let mut board = BasicBoard::new(Duration::from_secs(60));
+        for &(piece, name) in pieces {
+            *board.piece_at_mut(Location::from_name(name).unwrap()) = piece;
+        }
+        board.current = Color::White;
+        PSTBoard::new(board)
+    }
+
+    #[test]
+    fn test_draws() {
+        let b = board(&[(Piece::WhiteKing, "e1"), (Piece::WhiteBishop, "c4"), (Piece::BlackKing, "e8")]);
+        assert_eq!(b.heuristic(), 0.0);
+
+        // the bishop doesn't cover h8, and the king is there
+        let b = board(&[(Piece::WhiteKing, "e1"), (Piece::WhiteBishop, "c4"), (Piece::WhitePawn, "h5"), (Piece::BlackKing, "g8")]);
+        assert_eq!(b.heuristic(), 0.0);
+
+        // with the other bishop it wins
+        let b = board(&[(Piece::WhiteKing, "e1"), (Piece::WhiteBishop, "d4"), (Piece::WhitePawn, "h5"), (Piece::BlackKing, "g8")]);
+        assert!(b.heuristic() > 300.0);
+
+        let b = board(&[(Piece::WhiteKing, "e1"), (Piece::WhiteBishop, "d4"), (Piece::BlackKing, "e8"), (Piece::BlackKnight, "b8")]);
+        assert!(b.heuristic().abs() < 30.0);
+    }
+
+    #[test]
+    fn test_mop_up() {
+        let centre = board(&[(Piece::WhiteKing, "e1"), (Piece::WhiteRook, "a1"), (Piece::BlackKing, "d5")]);
+        let edge = board(&[(Piece::WhiteKing, "e1"), (Piece::WhiteRook, "a1"), (Piece::BlackKing, "d8")]);
+        assert!(edge.heuristic() > centre.heuristic());
+
+        // the same for black
+        let centre = board(&[(Piece::BlackKing, "e8"), (Piece::BlackQueen, "a8"), (Piece::WhiteKing, "d4")]);
+        let edge = board(&[(Piece::BlackKing, "e8"), (Piece::BlackQueen, "a8"), (Piece::WhiteKing, "h4")]);
+        assert!(edge.heuristic() < centre.heuristic());
+
+        // with a light-squared bishop and a knight, a8 is the corner to mate in, not a1
+        let right = board(&[(Piece::WhiteKing, "c6"), (Piece::WhiteBishop, "d5"), (Piece::WhiteKnight, "e4"), (Piece::BlackKing, "a8")]);
+        let wrong = board(&[(Piece::WhiteKing, "c3"), (Piece::WhiteBishop, "d5"), (Piece::WhiteKnight, "e4"), (Piece::BlackKing, "a1")]);
+        assert!(right.heuristic() > wrong.heuristic());
+    }
+}
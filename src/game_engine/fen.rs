@@ -0,0 +1,193 @@
+use crate::game_engine::board::{BasicBoard, Board};
+use crate::game_engine::chess_move::Location;
+use crate::game_engine::color::Color;
+use crate::game_engine::piece::Piece;
+use std::time::{Duration, SystemTime};
+
+/// The initial position in Forsyth-Edwards Notation.
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The castling rights in FEN, in the order of `Board::get_castling_rights` (a8, h8, a1, h1).
+const CASTLING: [char; 4] = ['q', 'k', 'Q', 'K'];
+
+fn piece_of_letter(letter: char) -> Option<Piece> {
+    Some(match letter {
+        'P' => Piece::WhitePawn,
+        'N' => Piece::WhiteKnight,
+        'B' => Piece::WhiteBishop,
+        'R' => Piece::WhiteRook,
+        'Q' => Piece::WhiteQueen,
+        'K' => Piece::WhiteKing,
+        'p' => Piece::BlackPawn,
+        'n' => Piece::BlackKnight,
+        'b' => Piece::BlackBishop,
+        'r' => Piece::BlackRook,
+        'q' => Piece::BlackQueen,
+        'k' => Piece::BlackKing,
+        _ => return None,
+    })
+}
+
+fn letter_of_piece(piece: Piece) -> char {
+    let letter = if piece.is_pawn() {
+        'p'
+    } else if piece.is_knight() {
+        'n'
+    } else if piece.is_bishop() {
+        'b'
+    } else if piece.is_rook() {
+        'r'
+    } else if piece.is_queen() {
+        'q'
+    } else {
+        'k'
+    };
+
+    if piece.color() == Color::White { letter.to_ascii_uppercase() } else { letter }
+}
+
+/// Sets up the position of a FEN string, with `initial_time_limit` on both clocks. The move
+/// counters may be left out. `None` when the FEN is malformed, or either side has no king.
+pub fn parse_fen(fen: &str, initial_time_limit: Duration) -> Option<BasicBoard> {
+    let mut fields = fen.split_whitespace();
+    let mut board = BasicBoard::new(initial_time_limit);
+
+    let ranks: Vec<&str> = fields.next()?.split('/').collect();
+    if ranks.len() != 8 {
+        return None;
+    }
+    for (y, rank) in ranks.iter().enumerate() {
+        let mut x = 0;
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                x += empty as usize;
+            } else {
+                if x >= 8 {
+                    return None;
+                }
+                board.board[y][x] = piece_of_letter(c)?;
+                x += 1;
+            }
+        }
+        if x != 8 {
+            return None;
+        }
+    }
+
+    board.current = match fields.next()? {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return None,
+    };
+
+    let castling = fields.next()?;
+    if castling != "-" && !castling.chars().all(|c| CASTLING.contains(&c)) {
+        return None;
+    }
+    for (right, letter) in board.castling_rights.iter_mut().zip(CASTLING.iter()) {
+        *right = castling.contains(*letter);
+    }
+
+    board.en_passant = match fields.next()? {
+        "-" => 8,
+        square => Location::from_name(square)?.x,
+    };
+
+    board.halfmove_clock = fields.next().map_or(Some(0), |n| n.parse().ok())?;
+    let fullmoves: i32 = fields.next().map_or(Some(1), |n| n.parse().ok())?;
+    board.move_count = 2 * (fullmoves.max(1) - 1) + if board.current == Color::Black { 1 } else { 0 };
+    // the clock of the side to move runs from now on
+    board.last_move_time = SystemTime::now();
+
+    let pieces: Vec<Piece> = board.board.iter().flatten().copied().collect();
+    board.material_score = -pieces.iter().map(Piece::material_worth).sum::<i32>();
+    if !pieces.contains(&Piece::WhiteKing) || !pieces.contains(&Piece::BlackKing) {
+        return None;
+    }
+
+    Some(board)
+}
+
+/// Writes the position of `board` in Forsyth-Edwards Notation.
+pub fn to_fen(board: &impl Board) -> String {
+    let mut fen = String::new();
+
+    for y in 0..8 {
+        let mut empty = 0;
+        for x in 0..8 {
+            let piece = board.piece_at((x, y));
+            if piece.is_empty() {
+                empty += 1;
+                continue;
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+                empty = 0;
+            }
+            fen.push(letter_of_piece(piece));
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if y < 7 {
+            fen.push('/');
+        }
+    }
+
+    fen.push_str(if board.current_player() == Color::White { " w " } else { " b " });
+
+    // FEN lists white's rights first
+    let castling: String = [3, 2, 1, 0].iter()
+        .filter(|&&i| board.get_castling_rights()[i])
+        .map(|&i| CASTLING[i])
+        .collect();
+    fen.push_str(if castling.is_empty() { "-" } else { &castling });
+
+    match board.get_en_passant() {
+        8 => fen.push_str(" -"),
+        file => {
+            // the square the pawn passed over
+            let y = if board.current_player() == Color::White { 2 } else { 5 };
+            fen.push_str(&format!(" {}", Location::new(file, y)));
+        }
+    }
+
+    fen.push_str(&format!(" {} {}", board.halfmove_clock(), board.move_count() / 2 + 1));
+    fen
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::fen::{parse_fen, to_fen, START_FEN};
+    use crate::game_engine::san::parse_san;
+    use std::time::Duration;
+
+    #[test]
+    fn test_fen() {
+        let start = BasicBoard::default_board(Duration::from_secs(60));
+        let parsed = parse_fen(START_FEN, Duration::from_secs(60)).unwrap();
+        assert_eq!(parsed.board, start.board);
+        assert_eq!(parsed.castling_rights, start.castling_rights);
+        assert_eq!(parsed.hash(), start.hash());
+        assert_eq!(parsed.material_score, 0);
+        assert_eq!(to_fen(&start), START_FEN);
+
+        let mut board = start;
+        for san in &["e4", "c5", "e5", "d5"] {
+            board = board.transition(parse_san(&board, san).unwrap());
+        }
+        let fen = "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(to_fen(&board), fen);
+        assert_eq!(parse_fen(fen, Duration::from_secs(60)).unwrap().hash(), board.hash());
+
+        let fen = "4k3/8/8/8/8/8/1q6/R3K2R b K - 12 40";
+        let board = parse_fen(fen, Duration::from_secs(60)).unwrap();
+        assert_eq!(board.castling_rights, [false, false, false, true]);
+        assert_eq!(board.material_score, 100);
+        assert_eq!(to_fen(&board), fen);
+
+        assert!(parse_fen("8/8/8/8/8/8/8/8 w - - 0 1", Duration::from_secs(60)).is_none());
+        assert!(parse_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", Duration::from_secs(60)).is_none());
+    }
+}
@@ -0,0 +1,48 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::Move;
+use crate::game_engine::color::Color;
+use crate::game_engine::piece::Piece;
+
+/// Writes `m` in the long algebraic notation of UCI and xboard: the origin and destination
+/// squares, and the piece a pawn promotes to, like "e2e4", "e1g1" or "e7e8q".
+pub fn to_lan(m: Move) -> String {
+    let mut lan = format!("{}{}", m.from, m.to);
+
+    // the color doesn't matter for the letter
+    match m.extra.promotion_of_color(Color::White) {
+        Some(Piece::WhiteQueen) => lan.push('q'),
+        Some(Piece::WhiteRook) => lan.push('r'),
+        Some(Piece::WhiteBishop) => lan.push('b'),
+        Some(Piece::WhiteKnight) => lan.push('n'),
+        _ => {}
+    }
+
+    lan
+}
+
+/// Finds the legal move of `board` that `lan` describes.
+pub fn parse_lan(board: &impl Board, lan: &str) -> Option<Move> {
+    let lan = lan.trim().to_ascii_lowercase();
+    board.all_moves().into_iter().find(|&m| to_lan(m) == lan)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::Board;
+    use crate::game_engine::fen::parse_fen;
+    use crate::game_engine::lan::{parse_lan, to_lan};
+    use std::time::Duration;
+
+    #[test]
+    fn test_lan() {
+        let board = parse_fen("r3k3/1P6/8/8/8/8/8/4K2R w Kq - 0 1", Duration::from_secs(60)).unwrap();
+        for m in board.all_moves() {
+            assert_eq!(parse_lan(&board, &to_lan(m)), Some(m));
+        }
+
+        assert_eq!(parse_lan(&board, "e1g1").map(to_lan), Some("e1g1".to_string()));
+        assert_eq!(parse_lan(&board, "b7a8N").map(to_lan), Some("b7a8n".to_string()));
+        assert_eq!(parse_lan(&board, "b7b8"), None);
+        assert_eq!(parse_lan(&board, "e1e3"), None);
+    }
+}
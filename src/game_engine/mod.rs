@@ -4,3 +4,9 @@ pub mod chess_move;
 pub mod color;
 pub mod king_check;
 pub mod piece_moves;
+pub mod see;
+pub mod endgame;
+pub mod san;
+pub mod lan;
+pub mod fen;
+pub mod pgn;
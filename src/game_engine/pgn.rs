@@ -0,0 +1,272 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::Move;
+use crate::game_engine::color::Color;
+use crate::game_engine::san::parse_san;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// The game didn't finish, or the result isn't known
+    Unknown,
+}
+
+impl GameResult {
+    /// The result of a game won by `winner`, in which `EmptyColor` is a draw.
+    pub fn from_winner(winner: Color) -> Self {
+        match winner {
+            Color::White => GameResult::WhiteWins,
+            Color::Black => GameResult::BlackWins,
+            Color::EmptyColor => GameResult::Draw,
+        }
+    }
+
+    pub fn parse(result: &str) -> Option<Self> {
+        Some(match result {
+            "1-0" => GameResult::WhiteWins,
+            "0-1" => GameResult::BlackWins,
+            "1/2-1/2" => GameResult::Draw,
+            "*" => GameResult::Unknown,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+}
+
+/// A game in Portable Game Notation (PGN).
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    /// The tag pairs, like `Event` and `White`, in the order they appear
+    pub tags: Vec<(String, String)>,
+    /// The moves of the main line, in standard algebraic notation
+    pub moves: Vec<String>,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    pub fn new() -> Self {
+        Self {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the game starts from another position than the initial one, which is given in FEN.
+    pub fn has_setup(&self) -> bool {
+        self.tag("FEN").is_some() || self.tag("SetUp") == Some("1")
+    }
+
+    /// Plays the moves of the game from `board`, and returns the positions after every move
+    /// with the move that led there. Stops at the first move that isn't legal.
+    pub fn replay<B: Board>(&self, board: B) -> Vec<(Move, B)> {
+        let mut positions = Vec::new();
+        let mut board = board;
+
+        for san in &self.moves {
+            let m = match parse_san(&board, san) {
+                Some(m) => m,
+                None => break,
+            };
+            board = board.transition(m);
+            positions.push((m, board.clone()));
+        }
+
+        positions
+    }
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (tag, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", tag, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        if self.tag("Result").is_none() {
+            writeln!(f, "[Result \"{}\"]", self.result.as_str())?;
+        }
+        writeln!(f)?;
+
+        // keep lines short, like most programs do
+        let mut line = String::new();
+        let tokens = self.moves.iter()
+            .enumerate()
+            .map(|(i, san)| if i.is_multiple_of(2) { format!("{}. {}", i / 2 + 1, san) } else { san.clone() })
+            .chain(std::iter::once(self.result.as_str().to_string()));
+
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+/// Reads all games of a PGN file. Comments, variations, move numbers and numeric
+/// annotations are skipped, so only the main line is kept.
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::new();
+    let mut movetext = MoveText::default();
+    let mut in_movetext = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if !movetext.is_open() {
+            // escaped lines
+            if line.starts_with('%') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                // a tag after moves starts the next game, even if the result was missing
+                if in_movetext {
+                    games.push(std::mem::replace(&mut game, PgnGame::new()));
+                    in_movetext = false;
+                }
+                if let Some(tag) = parse_tag(line) {
+                    game.tags.push(tag);
+                }
+                continue;
+            }
+        }
+
+        for token in movetext.tokenize(line) {
+            if let Some(result) = GameResult::parse(&token) {
+                game.result = result;
+                games.push(std::mem::replace(&mut game, PgnGame::new()));
+                in_movetext = false;
+            } else {
+                game.moves.push(token);
+                in_movetext = true;
+            }
+        }
+    }
+
+    if in_movetext || !game.tags.is_empty() {
+        games.push(game);
+    }
+
+    games
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.trim_end().strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    Some((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+/// The comments and variations that are open in the movetext, which can span several lines.
+#[derive(Default)]
+struct MoveText {
+    comment: bool,
+    variations: usize,
+}
+
+impl MoveText {
+    fn is_open(&self) -> bool {
+        self.comment || self.variations > 0
+    }
+
+    /// Splits a line of movetext into the moves and results of the main line.
+    fn tokenize(&mut self, line: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut token = String::new();
+
+        for c in line.chars() {
+            match c {
+                '}' if self.comment => self.comment = false,
+                _ if self.comment => {}
+                // the rest of the line is a comment
+                ';' => break,
+                '{' => {
+                    push_token(&mut token, &mut tokens);
+                    self.comment = true;
+                }
+                '(' => {
+                    push_token(&mut token, &mut tokens);
+                    self.variations += 1;
+                }
+                ')' => self.variations = self.variations.saturating_sub(1),
+                _ if self.variations > 0 => {}
+                c if c.is_whitespace() => push_token(&mut token, &mut tokens),
+                c => token.push(c),
+            }
+        }
+        push_token(&mut token, &mut tokens);
+
+        tokens
+    }
+}
+
+/// Adds `token` to `tokens` without its move number, unless it's only a move number or an annotation like "$1".
+fn push_token(token: &mut String, tokens: &mut Vec<String>) {
+    // move numbers like "12." and "12..." can be written without a space before the move
+    let san = match token.rfind('.') {
+        Some(i) => &token[i + 1..],
+        None => token.as_str(),
+    };
+
+    if !san.is_empty() && !san.starts_with('$') {
+        tokens.push(san.to_string());
+    }
+    token.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::BasicBoard;
+    use crate::game_engine::pgn::{parse_pgn, GameResult};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_pgn() {
+        let text = r#"[Event "Test"]
+[White "A \"quoted\" name"]
+
+1. e4 {best by test
+(probably)} e5 2.Nf3 (2. f4 exf4 (2... d5)) Nc6 $1 3. Bb5 a6; the Ruy Lopez
+4. O-O 1-0
+
+[Event "Second"]
+1. d4 d5 *
+"#;
+
+        let games = parse_pgn(text);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("White"), Some("A \"quoted\" name"));
+        assert_eq!(games[0].moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "O-O"]);
+        assert_eq!(games[0].result, GameResult::WhiteWins);
+        assert_eq!(games[0].replay(BasicBoard::default_board(Duration::from_secs(60))).len(), 7);
+        assert_eq!(games[1].result, GameResult::Unknown);
+
+        let again = parse_pgn(&games[0].to_string());
+        assert_eq!(again[0].moves, games[0].moves);
+        assert_eq!(again[0].tag("White"), games[0].tag("White"));
+        assert_eq!(again[0].result, games[0].result);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::{Move, Location, Extra};
+use crate::game_engine::king_check::king_check;
+use crate::game_engine::piece::Piece;
+
+/// The letter of a piece in standard algebraic notation (SAN), `None` for pawns.
+fn piece_letter(piece: Piece) -> Option<char> {
+    if piece.is_king() {
+        Some('K')
+    } else if piece.is_queen() {
+        Some('Q')
+    } else if piece.is_rook() {
+        Some('R')
+    } else if piece.is_bishop() {
+        Some('B')
+    } else if piece.is_knight() {
+        Some('N')
+    } else {
+        None
+    }
+}
+
+fn promotion_letter(board: &impl Board, m: &Move) -> Option<char> {
+    m.extra.promotion_of_color(board.current_player()).and_then(piece_letter)
+}
+
+/// Writes `m`, a legal move in `board`, in standard algebraic notation, like "Nbd7", "exd5" or "e8=Q+".
+pub fn to_san(board: &impl Board, m: Move) -> String {
+    let mut san = match m.extra {
+        Extra::KingCastle => "O-O".to_string(),
+        Extra::QueenCastle => "O-O-O".to_string(),
+        _ => {
+            let piece = board.piece_at(m.from);
+            let mut san = String::new();
+
+            match piece_letter(piece) {
+                Some(letter) => {
+                    san.push(letter);
+
+                    // name the origin when another piece of the same kind can go to the same square
+                    let others: Vec<Location> = board.all_moves().into_iter()
+                        .filter(|other| other.to == m.to && other.from != m.from && board.piece_at(other.from) == piece)
+                        .map(|other| other.from)
+                        .collect();
+                    let origin = m.from.to_string();
+
+                    if !others.is_empty() {
+                        if others.iter().all(|other| other.x != m.from.x) {
+                            san.push_str(&origin[..1]);
+                        } else if others.iter().all(|other| other.y != m.from.y) {
+                            san.push_str(&origin[1..]);
+                        } else {
+                            san.push_str(&origin);
+                        }
+                    }
+                }
+                None if m.extra.is_capturing() => san.push_str(&m.from.to_string()[..1]),
+                None => {}
+            }
+
+            if m.extra.is_capturing() {
+                san.push('x');
+            }
+            san.push_str(&m.to.to_string());
+
+            if let Some(letter) = promotion_letter(board, &m) {
+                san.push('=');
+                san.push(letter);
+            }
+
+            san
+        }
+    };
+
+    let after = board.transition(m);
+    if king_check(&after, after.current_player()) {
+        san.push(if after.all_moves().is_empty() { '#' } else { '+' });
+    }
+
+    san
+}
+
+/// Finds the legal move of `board` that `san` describes. Check marks and annotations like "!?"
+/// are ignored, and castling may be written with zeroes. Ambiguous moves are `None`.
+pub fn parse_san(board: &impl Board, san: &str) -> Option<Move> {
+    let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+
+    match san {
+        "O-O" | "0-0" => return board.all_moves().into_iter().find(|m| m.extra == Extra::KingCastle),
+        "O-O-O" | "0-0-0" => return board.all_moves().into_iter().find(|m| m.extra == Extra::QueenCastle),
+        _ => {}
+    }
+
+    let (san, promotion) = match san.char_indices().last()? {
+        (i, letter @ ('Q' | 'R' | 'B' | 'N')) if san.starts_with(|c: char| c.is_ascii_lowercase()) => {
+            (san[..i].trim_end_matches('='), Some(letter))
+        }
+        _ => (san, None),
+    };
+
+    let (piece, rest) = match san.chars().next()? {
+        letter @ ('K' | 'Q' | 'R' | 'B' | 'N') => (Some(letter), &san[1..]),
+        _ => (None, san),
+    };
+
+    let rest: String = rest.chars().filter(|&c| c != 'x' && c != '-').collect();
+    if rest.len() < 2 || !rest.is_ascii() {
+        return None;
+    }
+    let to = Location::from_name(&rest[rest.len() - 2..])?;
+    let origin = &rest[..rest.len() - 2];
+
+    let mut candidates = board.all_moves().into_iter()
+        .filter(|m| m.to == to)
+        .filter(|m| piece_letter(board.piece_at(m.from)) == piece)
+        .filter(|m| promotion_letter(board, m) == promotion)
+        .filter(|m| {
+            let from = m.from.to_string();
+            origin.chars().all(|c| from.contains(c))
+        });
+
+    let m = candidates.next()?;
+    if candidates.next().is_some() {
+        return None;
+    }
+    Some(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::san::{to_san, parse_san};
+    use rand::seq::SliceRandom;
+    use rand::thread_rng;
+    use std::time::Duration;
+
+    #[test]
+    fn test_san_round_trip() {
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut board = BasicBoard::default_board(Duration::from_secs(60));
+
+            for _ in 0..100 {
+                let moves = board.all_moves();
+                for &m in &moves {
+                    assert_eq!(parse_san(&board, &to_san(&board, m)), Some(m));
+                }
+
+                match moves.choose(&mut rng) {
+                    Some(&m) if board.is_terminal().is_none() => board = board.transition(m),
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_san() {
+        let board = BasicBoard::default_board(Duration::from_secs(60));
+        let e4 = parse_san(&board, "e4").unwrap();
+        assert_eq!(to_san(&board, e4), "e4");
+        assert_eq!(to_san(&board, parse_san(&board, "Nf3").unwrap()), "Nf3");
+        assert_eq!(parse_san(&board, "Ke2"), None);
+
+        let board = board.transition(e4);
+        let board = board.transition(parse_san(&board, "f5").unwrap());
+        assert_eq!(to_san(&board, parse_san(&board, "exf5").unwrap()), "exf5");
+        assert_eq!(to_san(&board, parse_san(&board, "Qh5+").unwrap()), "Qh5+");
+    }
+}
@@ -0,0 +1,160 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::{Location, Move, Extra};
+use crate::game_engine::color::Color;
+use crate::game_engine::piece::Piece;
+
+/// The value of a piece in centipawns, regardless of its color.
+pub fn piece_value(piece: Piece) -> i32 {
+    piece.material_worth().abs()
+}
+
+/// Finds the least valuable piece of `color` that attacks `target` on the given squares.
+/// Pins are ignored, as is usual in static exchange evaluation.
+fn least_valuable_attacker(squares: &[[Piece; 8]; 8], target: Location, color: Color) -> Option<Location> {
+    let piece_at = |x: i8, y: i8| -> Option<Piece> {
+        if (0..8).contains(&x) && (0..8).contains(&y) {
+            Some(squares[y as usize][x as usize])
+        } else {
+            None
+        }
+    };
+
+    let mut best: Option<(Location, i32)> = None;
+    let mut consider = |x: i8, y: i8, piece: Piece| {
+        let value = piece_value(piece);
+        if best.is_none_or(|(_, best_value)| value < best_value) {
+            best = Some(((x, y).into(), value));
+        }
+    };
+
+    // white pawns attack towards y = 0, black pawns towards y = 7
+    let pawn_y = if color == Color::White { target.y + 1 } else { target.y - 1 };
+    for &x in &[target.x - 1, target.x + 1] {
+        if let Some(p) = piece_at(x, pawn_y) {
+            if p.is_pawn() && p.color() == color {
+                consider(x, pawn_y, p);
+            }
+        }
+    }
+
+    for &(dx, dy) in &[(2, 1), (2, -1), (-2, 1), (-2, -1), (1, 2), (-1, 2), (1, -2), (-1, -2)] {
+        if let Some(p) = piece_at(target.x + dx, target.y + dy) {
+            if p.is_knight() && p.color() == color {
+                consider(target.x + dx, target.y + dy, p);
+            }
+        }
+    }
+
+    for &(dx, dy) in &[(1, 1), (1, -1), (-1, 1), (-1, -1), (1, 0), (-1, 0), (0, 1), (0, -1)] {
+        let diagonal = dx != 0 && dy != 0;
+
+        let (mut x, mut y) = (target.x + dx, target.y + dy);
+        let mut distance = 1;
+        while let Some(p) = piece_at(x, y) {
+            if !p.is_empty() {
+                if p.color() == color {
+                    let slides = if diagonal {
+                        p.is_bishop() || p.is_queen()
+                    } else {
+                        p.is_rook() || p.is_queen()
+                    };
+
+                    if slides || (p.is_king() && distance == 1) {
+                        consider(x, y, p);
+                    }
+                }
+                break;
+            }
+
+            x += dx;
+            y += dy;
+            distance += 1;
+        }
+    }
+
+    best.map(|(l, _)| l)
+}
+
+/// Static exchange evaluation: the material the side to move gains by playing the capture `m`,
+/// assuming both sides keep recapturing on the destination square with their least valuable
+/// piece for as long as that is profitable. Negative for captures that lose material.
+pub fn static_exchange_evaluation(board: &impl Board, m: Move) -> i32 {
+    let mut squares = [[Piece::Empty; 8]; 8];
+    for (y, row) in squares.iter_mut().enumerate() {
+        for (x, square) in row.iter_mut().enumerate() {
+            *square = board.piece_at((x as i8, y as i8));
+        }
+    }
+
+    let mover = squares[m.from.y as usize][m.from.x as usize];
+
+    let victim = if m.extra == Extra::EnPassantCaptures {
+        let captured = &mut squares[m.from.y as usize][m.to.x as usize];
+        let victim = *captured;
+        *captured = Piece::Empty;
+        victim
+    } else {
+        squares[m.to.y as usize][m.to.x as usize]
+    };
+
+    let mut gain = vec![piece_value(victim)];
+    let mut on_target = mover;
+    let mut side = mover.color().other();
+
+    squares[m.from.y as usize][m.from.x as usize] = Piece::Empty;
+    squares[m.to.y as usize][m.to.x as usize] = mover;
+
+    while let Some(attacker) = least_valuable_attacker(&squares, m.to, side) {
+        let previous = *gain.last().unwrap();
+        gain.push(piece_value(on_target) - previous);
+
+        on_target = squares[attacker.y as usize][attacker.x as usize];
+        squares[attacker.y as usize][attacker.x as usize] = Piece::Empty;
+        squares[m.to.y as usize][m.to.x as usize] = on_target;
+        side = side.other();
+    }
+
+    // every side may also choose not to recapture
+    for d in (1..gain.len()).rev() {
+        gain[d - 1] = -(-gain[d - 1]).max(gain[d]);
+    }
+
+    gain[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::piece::Piece;
+    use crate::game_engine::chess_move::Extra;
+    use crate::game_engine::see::static_exchange_evaluation;
+    use std::time::Duration;
+
+    #[test]
+    fn test_see() {
+        let mut board = BasicBoard::new(Duration::from_secs(60));
+        *board.piece_at_mut((4, 7)) = Piece::WhiteKing;
+        *board.piece_at_mut((4, 0)) = Piece::BlackKing;
+
+        *board.piece_at_mut((3, 3)) = Piece::BlackKnight;
+        *board.piece_at_mut((4, 4)) = Piece::WhitePawn;
+        *board.piece_at_mut((3, 6)) = Piece::WhiteQueen;
+
+        // an undefended knight
+        assert_eq!(static_exchange_evaluation(&board, ((4, 4), (3, 3), Extra::Captures).into()), 320);
+        assert_eq!(static_exchange_evaluation(&board, ((3, 6), (3, 3), Extra::Captures).into()), 320);
+
+        // defended by a pawn, but the pawn is lost too when it recaptures, so it shouldn't
+        *board.piece_at_mut((2, 2)) = Piece::BlackPawn;
+        assert_eq!(static_exchange_evaluation(&board, ((4, 4), (3, 3), Extra::Captures).into()), 320);
+        // taking with the queen loses it for a knight and a pawn
+        assert_eq!(static_exchange_evaluation(&board, ((3, 6), (3, 3), Extra::Captures).into()), 320 - 900 + 100);
+
+        // the rook behind the black queen joins in once the queen has recaptured
+        *board.piece_at_mut((2, 2)) = Piece::Empty;
+        *board.piece_at_mut((3, 0)) = Piece::BlackRook;
+        *board.piece_at_mut((3, 1)) = Piece::BlackQueen;
+        assert_eq!(static_exchange_evaluation(&board, ((3, 6), (3, 3), Extra::Captures).into()), 320 - 900 + 900 - 100);
+        assert_eq!(static_exchange_evaluation(&board, ((4, 4), (3, 3), Extra::Captures).into()), 320 - 100);
+    }
+}
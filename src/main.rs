@@ -8,27 +8,70 @@ use crate::solver::iterative_deepening::IterativeDeepening;
 use std::time::Duration;
 use crate::solver::id_quiescence::IDQuiescence;
 use crate::solver::player::Player;
+use crate::solver::book_builder::{BookBuilder, BookBuilderConfig};
+use crate::protocol::EngineOptions;
+use crate::solver::contempt::ContemptMode;
+use crate::tablebase::Tablebases;
+use crate::tablebase::generator::generate_all;
+use crate::tablebase::syzygy::{self, Syzygy};
+use crate::tablebase::position::Material;
+use std::fs;
+use std::path::Path;
 
 mod game_engine;
 mod solver;
 mod runner;
 mod transposition_table;
 mod stats;
+mod tablebase;
+mod protocol;
+
+/// The tablebases in this directory are used by the solvers
+const TABLEBASE_DIRECTORY: &str = "tablebases";
+/// And the Syzygy tables (`.rtbw` and `.rtbz` files) in this one
+const SYZYGY_DIRECTORY: &str = "syzygy";
+/// The opening book that is played from when no other book is given with `--book`
+const BOOK_FILE: &str = "book.bin";
 
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("book") {
+        build_book(&args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("tablebase") {
+        build_tablebases(&args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("uci") {
+        // stdout belongs to the GUI
+        install_tablebases(|message| eprintln!("{}", message));
+        protocol::uci::run(engine_options(&args[2..], |message| eprintln!("{}", message)));
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("xboard") {
+        install_tablebases(|message| eprintln!("{}", message));
+        protocol::xboard::run(engine_options(&args[2..], |message| eprintln!("{}", message)));
+        return;
+    }
+
+    install_tablebases(|message| println!("{}", message));
+    let options = engine_options(&args[1..], |message| println!("{}", message));
+
     let mut b = ZobristBoard::new(PSTBoard::new(BasicBoard::default_board(Duration::from_secs(2 * 60))));
 
-    // let p1 = AlphaBetaTransp::new(4, 16 * 1024 * 1024);
+    // let p1 = AlphaBetaTransp::new(4, 16);
     // let p2 = Quiescence::new(4);
     // let p2 = Player::new();
-    // let p1 = AlphaBetaTransp::new(4, 16 * 1024 * 1024);
+    // let p1 = AlphaBetaTransp::new(4, 16);
     // let p2 = Quiescence::new(4);
 
     let p1 = IterativeDeepening::new();
-    let p2 = IDQuiescence::new();
+    let p2 = IDQuiescence::with_config(options.search_config());
 
-    let mut r = Runner::new(p1, p2, "stats".to_string());
+    let mut r = Runner::new(p1, p2, "stats".to_string())
+        .with_pgn_file("stats/games.pgn");
     r.run(b);
 
     // let mut b = BasicBoard::new();
@@ -56,3 +99,168 @@ fn main() {
     //     println!("{}", b);
     // }
 }
+
+/// Installs the tablebases and Syzygy tables in their directories, if there are any, and passes
+/// what it found to `log`.
+fn install_tablebases(log: impl Fn(String)) {
+    if Path::new(TABLEBASE_DIRECTORY).is_dir() {
+        match Tablebases::open(TABLEBASE_DIRECTORY) {
+            Ok(tablebases) => {
+                log(format!("using {} tablebases from {}", tablebases.len(), TABLEBASE_DIRECTORY));
+                tablebase::install(tablebases);
+            }
+            Err(e) => log(format!("couldn't open the tablebases in {}: {}", TABLEBASE_DIRECTORY, e)),
+        }
+    }
+    if Path::new(SYZYGY_DIRECTORY).is_dir() {
+        match Syzygy::open(SYZYGY_DIRECTORY) {
+            Ok(syzygy) => {
+                log(format!("using {} syzygy tables of up to {} pieces from {}", syzygy.len(), syzygy.max_pieces(), SYZYGY_DIRECTORY));
+                syzygy::install(syzygy);
+            }
+            Err(e) => log(format!("couldn't open the syzygy tables in {}: {}", SYZYGY_DIRECTORY, e)),
+        }
+    }
+}
+
+/// The options of the engine from the command line: `--book <file.bin>` plays from a Polyglot book,
+/// like the ones `venom-chess book` builds. Without it, the book in `BOOK_FILE` is played from if
+/// there is one. `--contempt <centipawns>`, `--contempt-mode <fixed|material|opponent>` and
+/// `--elo-difference <elo>` set how much the engine dislikes draws. `--threads <n>` searches with more
+/// threads, and `--measure-speedup` reports how much faster they are in the stats. What was loaded,
+/// and problems, are passed to `log`.
+fn engine_options(args: &[String], log: impl Fn(String)) -> EngineOptions {
+    let mut options = EngineOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--book" => match args.next() {
+                Some(path) => match options.open_book(path) {
+                    Ok(()) => {
+                        log(format!("using the opening book {}", path));
+                        options.own_book = true;
+                    }
+                    Err(e) => log(format!("couldn't open the opening book {}: {}", path, e)),
+                },
+                None => log("--book needs a file".to_string()),
+            },
+            "--contempt" => match args.next().and_then(|c| c.parse().ok()) {
+                Some(contempt) => options.contempt = contempt,
+                None => log("--contempt needs a number of centipawns".to_string()),
+            },
+            "--contempt-mode" => match args.next().and_then(|mode| ContemptMode::from_name(mode)) {
+                Some(mode) => options.contempt_mode = mode,
+                None => log("--contempt-mode needs fixed, material or opponent".to_string()),
+            },
+            "--threads" => match args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0) {
+                Some(threads) => options.config.threads = threads,
+                None => log("--threads needs a number of threads".to_string()),
+            },
+            "--measure-speedup" => options.config.measure_speedup = true,
+            "--elo-difference" => match args.next().and_then(|elo| elo.parse().ok()) {
+                Some(elo) => options.elo_difference = elo,
+                None => log("--elo-difference needs a number".to_string()),
+            },
+            _ => log(format!("unknown argument {}", arg)),
+        }
+    }
+
+    if options.book_file.is_none() && Path::new(BOOK_FILE).is_file() {
+        match options.open_book(BOOK_FILE) {
+            Ok(()) => {
+                log(format!("using the opening book {}", BOOK_FILE));
+                options.own_book = true;
+            }
+            Err(e) => log(format!("couldn't open the opening book {}: {}", BOOK_FILE, e)),
+        }
+    }
+
+    options
+}
+
+/// `venom-chess book <output.bin> <games.pgn>... [--plies N] [--min-games N]` builds an opening book
+/// from PGN files, like the games the runner records. The engine plays from it with `--book <output.bin>`,
+/// or by itself when it's written to `BOOK_FILE`.
+fn build_book(args: &[String]) {
+    let mut config = BookBuilderConfig::default();
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut number = || args.next().and_then(|n| n.parse().ok()).expect("expected a number");
+        match arg.as_str() {
+            "--plies" => config.max_plies = number(),
+            "--min-games" => config.min_games = number(),
+            file => files.push(file.to_string()),
+        }
+    }
+
+    if files.len() < 2 {
+        println!("usage: venom-chess book <output.bin> <games.pgn>... [--plies N] [--min-games N]");
+        return;
+    }
+
+    let mut builder = BookBuilder::with_config(config);
+    for file in &files[1..] {
+        match fs::read_to_string(file) {
+            Ok(text) => builder.add_pgn(&text),
+            Err(e) => println!("couldn't read {}: {}", file, e),
+        }
+    }
+
+    let book = builder.build();
+    match book.save(&files[0]) {
+        Ok(()) => {
+            println!("wrote {} moves from {} games to {} ({} games skipped)", book.len(), builder.games(), files[0], builder.skipped());
+            if Path::new(&files[0]) != Path::new(BOOK_FILE) {
+                println!("play from it with --book {}", files[0]);
+            }
+        }
+        Err(e) => println!("couldn't write {}: {}", files[0], e),
+    }
+}
+
+/// `venom-chess tablebase <directory> <material>...` makes the tablebases of sets of pieces like KQvK
+/// or KRvKP, and all smaller ones they need, and writes them to a directory. Tables that are already
+/// in the directory aren't made again.
+fn build_tablebases(args: &[String]) {
+    if args.len() < 2 {
+        println!("usage: venom-chess tablebase <directory> <material>...");
+        return;
+    }
+
+    let directory = Path::new(&args[0]);
+    let mut tablebases = if directory.is_dir() {
+        match Tablebases::open(directory) {
+            Ok(tablebases) => tablebases,
+            Err(e) => {
+                println!("couldn't open the tablebases in {}: {}", directory.display(), e);
+                return;
+            }
+        }
+    } else {
+        if let Err(e) = fs::create_dir_all(directory) {
+            println!("couldn't create {}: {}", directory.display(), e);
+            return;
+        }
+        Tablebases::new()
+    };
+
+    for name in &args[1..] {
+        let material = match Material::parse(name) {
+            Some(material) => material,
+            None => {
+                println!("{} isn't a set of at most {} pieces like KQvK", name, tablebase::position::MAX_MEN);
+                continue;
+            }
+        };
+
+        generate_all(&material, &mut tablebases, &mut |table| {
+            match table.save(directory) {
+                Ok(()) => println!("wrote {}", table.material().name()),
+                Err(e) => println!("couldn't write {}: {}", table.material().name(), e),
+            }
+        });
+    }
+}
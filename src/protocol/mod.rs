@@ -0,0 +1,359 @@
+//! Front ends that let other programs, like chess GUIs and tournament managers,
+//! play against the solvers over stdin and stdout.
+
+use crate::game_engine::board::display::DisplayableBoard;
+use crate::game_engine::board::pst::PSTBoard;
+use crate::game_engine::board::zobrist::ZobristBoard;
+use crate::game_engine::board::{BasicBoard, Board};
+use crate::game_engine::chess_move::Move;
+use crate::game_engine::fen::parse_fen;
+use crate::solver::Solver;
+use crate::solver::book::{BookConfig, BookSelection, OpeningBook};
+use crate::solver::config::SearchConfig;
+use crate::solver::contempt::{Contempt, ContemptMode};
+use crate::solver::control::SearchInfo;
+use crate::solver::id_quiescence::IDQuiescence;
+use crate::solver::iterative_deepening::IterativeDeepening;
+use crate::solver::limits::SearchLimits;
+use crate::solver::mcts::{Mcts, MctsConfig};
+use crate::solver::proof_number::{ProofNumberConfig, ProofNumberSearch};
+use crate::solver::time_manager::TimeControl;
+use crate::stats::Stats;
+use std::io;
+use std::time::Duration;
+
+pub mod uci;
+pub mod xboard;
+
+pub type GameBoard = DisplayableBoard<ZobristBoard<PSTBoard<BasicBoard>>>;
+
+/// The clocks of positions before the GUI told us the time. They can't be zero,
+/// because a flag that fell ends the game.
+pub const UNTIMED: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The depth limit of the solvers for a search of `plies` plies deep. They don't count the root move,
+/// so a search of one ply, or none, has a depth limit of 0 and only looks at the root moves.
+pub fn depth_limit(plies: u64) -> u64 {
+    plies.saturating_sub(1)
+}
+
+/// A score for the side to move, like the protocols report it.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Score {
+    Centipawns(i64),
+    /// Mate in this many moves, negative when the side to move is mated
+    Mate(i64),
+}
+
+impl Score {
+    /// The score of `info` for white when `white`, or for black otherwise.
+    /// The distance to a mate is only known from the principal variation.
+    pub fn of(info: &SearchInfo, white: bool) -> Self {
+        let score = if white { info.score } else { -info.score };
+
+        if score.is_infinite() {
+            let moves = info.pv.len().div_ceil(2).max(1) as i64;
+            Score::Mate(if score > 0.0 { moves } else { -moves })
+        } else {
+            Score::Centipawns(score.round() as i64)
+        }
+    }
+}
+
+/// The solvers that the front ends can play with.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SolverKind {
+    IDQuiescence,
+    IterativeDeepening,
+    Mcts,
+    ProofNumber,
+}
+
+impl SolverKind {
+    pub const ALL: [SolverKind; 4] = [SolverKind::IDQuiescence, SolverKind::IterativeDeepening, SolverKind::Mcts, SolverKind::ProofNumber];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolverKind::IDQuiescence => "IDQuiescence",
+            SolverKind::IterativeDeepening => "IterativeDeepening",
+            SolverKind::Mcts => "MCTS",
+            SolverKind::ProofNumber => "ProofNumber",
+        }
+    }
+
+    /// The solver with this name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// What the GUI can change about the engine. Only the `IDQuiescence` solver uses all of the search
+/// configuration, the other solvers only use its contempt and MCTS its book. The book and contempt
+/// of `config` are replaced by the ones these options choose, see `search_config`.
+#[derive(Clone, Debug)]
+pub struct EngineOptions {
+    pub solver: SolverKind,
+    pub config: SearchConfig,
+    /// Play from `config.book` at all. GUIs that have a book of their own turn it off.
+    pub own_book: bool,
+    /// The file `config.book` was loaded from
+    pub book_file: Option<String>,
+    /// How the move is picked from the book, whichever book is loaded
+    pub book_selection: BookSelection,
+    pub contempt_mode: ContemptMode,
+    /// The contempt in centipawns, or its base in the material mode
+    pub contempt: i64,
+    /// How much stronger the engine is than its opponent, for the opponent mode
+    pub elo_difference: i32,
+}
+
+impl EngineOptions {
+    /// Loads the Polyglot book at `path`. It's only played from with `own_book`.
+    pub fn open_book(&mut self, path: &str) -> io::Result<()> {
+        self.config.book = Some(BookConfig::new(OpeningBook::open(path)?));
+        self.book_file = Some(path.to_string());
+        Ok(())
+    }
+
+    /// The book the solver plays from, if any.
+    fn book(&self) -> Option<BookConfig> {
+        let book = self.config.book.clone().filter(|_| self.own_book)?;
+        Some(BookConfig {
+            selection: self.book_selection,
+            ..book
+        })
+    }
+
+    /// The configuration the solvers search with: `config` with the book and contempt of these options.
+    pub fn search_config(&self) -> SearchConfig {
+        SearchConfig {
+            book: self.book(),
+            contempt: Contempt::new(self.contempt_mode, self.contempt as f64, self.elo_difference),
+            ..self.config.clone()
+        }
+    }
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            solver: SolverKind::IDQuiescence,
+            config: SearchConfig::default(),
+            own_book: false,
+            book_file: None,
+            book_selection: BookSelection::WeightedRandom,
+            contempt_mode: ContemptMode::Fixed,
+            contempt: 0,
+            elo_difference: 0,
+        }
+    }
+}
+
+/// A game as the GUI sees it: the position it started from, and the moves played since.
+#[derive(Clone)]
+pub struct Game {
+    start: GameBoard,
+    moves: Vec<Move>,
+    board: GameBoard,
+}
+
+impl Game {
+    pub fn new(start: BasicBoard) -> Self {
+        let start = DisplayableBoard::new(ZobristBoard::new(PSTBoard::new(start)));
+        Self {
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+        }
+    }
+
+    /// A game from the initial position.
+    pub fn standard() -> Self {
+        Self::new(BasicBoard::default_board(UNTIMED))
+    }
+
+    pub fn from_fen(fen: &str) -> Option<Self> {
+        parse_fen(fen, UNTIMED).map(Self::new)
+    }
+
+    /// The current position.
+    pub fn board(&self) -> &GameBoard {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Plays `m`, which must be legal.
+    pub fn play(&mut self, m: Move) {
+        self.board = self.board.transition(m);
+        self.moves.push(m);
+    }
+
+    /// Takes back the last move, if there is one.
+    pub fn undo(&mut self) -> Option<Move> {
+        let m = self.moves.pop()?;
+        self.board = self.moves.iter().fold(self.start.clone(), |board, &m| board.transition(m));
+        Some(m)
+    }
+
+    /// Sets the time left of white and black.
+    pub fn set_clocks(&mut self, clock: [Duration; 2]) {
+        self.board.set_clocks(clock);
+    }
+}
+
+/// One of the solvers. Solvers can't be trait objects, so the front ends pick one through this.
+enum AnySolver {
+    IDQuiescence(Box<IDQuiescence>),
+    IterativeDeepening(IterativeDeepening),
+    Mcts(Box<Mcts>),
+    ProofNumber(ProofNumberSearch),
+}
+
+macro_rules! with_solver {
+    ($solver:expr, $s:ident => $body:expr) => {
+        match $solver {
+            AnySolver::IDQuiescence($s) => $body,
+            AnySolver::IterativeDeepening($s) => $body,
+            AnySolver::Mcts($s) => $body,
+            AnySolver::ProofNumber($s) => $body,
+        }
+    };
+}
+
+/// The solver that plays for a front end, and what it knows about the game.
+pub struct Engine {
+    options: EngineOptions,
+    solver: AnySolver,
+    stats: Stats,
+    /// The start of the game the solver has seen, and the moves of it it was told about.
+    /// Only moves after these are passed to the solver.
+    known: Option<(u64, Vec<Move>)>,
+}
+
+impl Engine {
+    pub fn new(options: EngineOptions) -> Self {
+        let config = options.search_config();
+        let solver = match options.solver {
+            SolverKind::IDQuiescence => AnySolver::IDQuiescence(Box::new(IDQuiescence::with_config(config))),
+            SolverKind::IterativeDeepening => AnySolver::IterativeDeepening(IterativeDeepening::with_config(TimeControl::default(), config.contempt)),
+            SolverKind::Mcts => AnySolver::Mcts(Box::new(Mcts::with_config(MctsConfig {
+                contempt: config.contempt,
+                book: config.book,
+                ..MctsConfig::default()
+            }))),
+            SolverKind::ProofNumber => AnySolver::ProofNumber(ProofNumberSearch::with_config(ProofNumberConfig {
+                contempt: config.contempt,
+                ..ProofNumberConfig::default()
+            })),
+        };
+
+        Self {
+            // statistics are only kept in memory, the GUI doesn't want files next to the engine
+            stats: Stats::new(options.solver.name(), None, None, String::new(), false),
+            options,
+            solver,
+            known: None,
+        }
+    }
+
+    pub fn options(&self) -> &EngineOptions {
+        &self.options
+    }
+
+    /// Forgets everything about the previous game, like the transposition table.
+    pub fn new_game(&mut self) {
+        *self = Self::new(self.options.clone());
+    }
+
+    /// Tells the solver about the moves of `game` it hasn't seen yet. When `game` isn't a
+    /// continuation of the game it knows, it starts over.
+    fn follow(&mut self, game: &Game) {
+        let start = game.start.hash();
+        let seen = match &self.known {
+            Some((hash, moves)) if *hash == start && game.moves.starts_with(moves) => moves.len(),
+            Some(_) => {
+                self.new_game();
+                0
+            }
+            None => 0,
+        };
+
+        let mut board = game.start.clone();
+        for (i, &m) in game.moves.iter().enumerate() {
+            board = board.transition(m);
+            if i >= seen {
+                with_solver!(&mut self.solver, s => s.opponent_played(&board, m));
+            }
+        }
+
+        self.known = Some((start, game.moves.clone()));
+    }
+
+    /// Searches the current position of `game` within `limits`, and returns the move to play.
+    /// `None` when there are no legal moves.
+    pub fn think(&mut self, game: &Game, control: TimeControl, limits: &SearchLimits) -> Option<Move> {
+        self.follow(game);
+
+        let board = game.board().clone();
+        let stats = self.stats.clone();
+        let played = with_solver!(&mut self.solver, s => {
+            s.set_time_control(control);
+            s.make_move_with_limits(board, limits, stats)
+        });
+
+        let m = played?.last_move()?;
+        if let Some((_, moves)) = &mut self.known {
+            moves.push(m);
+        }
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::lan::parse_lan;
+    use crate::protocol::{depth_limit, Engine, EngineOptions, Game};
+    use crate::solver::book_builder::BookBuilder;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::time_manager::TimeControl;
+
+    #[test]
+    fn test_depth_limit() {
+        assert_eq!(depth_limit(0), 0);
+        // one ply is just the root move
+        assert_eq!(depth_limit(1), 0);
+        assert_eq!(depth_limit(2), 1);
+        assert_eq!(depth_limit(5), 4);
+    }
+
+    #[test]
+    fn test_built_book() {
+        // an opening nobody would search for, drawn so both sides keep their moves
+        let mut builder = BookBuilder::new();
+        builder.add_pgn(&"1. a3 h6 2. h3 a6 1/2-1/2\n\n".repeat(3));
+
+        let path = std::env::temp_dir().join(format!("venom-chess-built-{}.bin", std::process::id()));
+        builder.build().save(&path).unwrap();
+
+        let mut options = EngineOptions::default();
+        options.open_book(path.to_str().unwrap()).unwrap();
+        options.own_book = true;
+        std::fs::remove_file(&path).unwrap();
+
+        let mut engine = Engine::new(options);
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..SearchLimits::default()
+        };
+
+        let mut game = Game::standard();
+        for lan in &["a2a3", "h7h6", "h2h3", "a7a6"] {
+            let m = engine.think(&game, TimeControl::default(), &limits);
+            assert_eq!(m, parse_lan(game.board(), lan));
+            game.play(m.unwrap());
+        }
+    }
+}
@@ -0,0 +1,469 @@
+//! The Universal Chess Interface (UCI), which most chess GUIs and tools like cutechess-cli speak.
+//!
+//! Searches run on their own thread, so that `stop`, `ponderhit` and `isready` are answered while
+//! the engine is thinking. The solver is moved to that thread, and comes back when the search is done.
+
+use crate::game_engine::board::Board;
+use crate::game_engine::color::Color;
+use crate::game_engine::fen::START_FEN;
+use crate::game_engine::lan::{parse_lan, to_lan};
+use crate::protocol::{depth_limit, Engine, EngineOptions, Game, Score, SolverKind};
+use crate::solver::book::BookSelection;
+use crate::solver::contempt::ContemptMode;
+use crate::solver::control::{InfoCallback, SearchInfo, StopToken};
+use crate::solver::limits::SearchLimits;
+use crate::solver::skill::{Skill, MAX_LEVEL};
+use crate::solver::time_manager::{TimeControl, TimeManager};
+use parking_lot::Mutex;
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The range of the Hash option, in megabytes
+const MAX_HASH: u64 = 4096;
+const MAX_THREADS: usize = 256;
+const MAX_MULTI_PV: usize = 64;
+/// The range of the Contempt option, in centipawns
+const MAX_CONTEMPT: i64 = 500;
+const MAX_ELO_DIFFERENCE: i32 = 3000;
+
+/// A search that is running on its own thread.
+struct Search {
+    stop: StopToken,
+    /// While set, the search thread keeps its move to itself until it's stopped, even when it's done.
+    /// The GUI expects this for infinite searches, and for pondering until the opponent moved.
+    hold: Arc<AtomicBool>,
+    infinite: bool,
+    /// How long to keep searching after a ponder hit, `None` to stop at once
+    ponder_time: Option<Duration>,
+    handle: JoinHandle<Engine>,
+}
+
+struct Uci {
+    /// `None` while a search has it
+    engine: Option<Engine>,
+    game: Game,
+    search: Option<Search>,
+}
+
+/// Plays with the solvers over UCI on stdin and stdout, until the GUI says `quit`.
+pub fn run(options: EngineOptions) {
+    let mut uci = Uci {
+        engine: Some(Engine::new(options)),
+        game: Game::standard(),
+        search: None,
+    };
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !uci.handle(&line) {
+            break;
+        }
+    }
+
+    uci.finish_search();
+}
+
+impl Uci {
+    /// Handles a command from the GUI. Returns false when the engine should quit.
+    fn handle(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return true,
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "uci" => self.identify(),
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.finish_search();
+                self.engine_mut().new_game();
+                self.game = Game::standard();
+            }
+            "position" => {
+                self.finish_search();
+                match parse_position(&args) {
+                    Some(game) => self.game = game,
+                    None => println!("info string invalid position: {}", line),
+                }
+            }
+            "go" => {
+                self.finish_search();
+                self.go(&args);
+            }
+            "stop" => self.finish_search(),
+            "ponderhit" => self.ponder_hit(),
+            "setoption" => {
+                self.finish_search();
+                self.set_option(&args);
+            }
+            "quit" => return false,
+            // debug and register, and anything we don't know, are ignored like the protocol asks
+            _ => {}
+        }
+
+        true
+    }
+
+    fn engine_mut(&mut self) -> &mut Engine {
+        self.engine.as_mut().expect("the engine is only away during a search")
+    }
+
+    fn identify(&self) {
+        let options = self.engine.as_ref().expect("the engine is only away during a search").options();
+
+        println!("id name venom-chess");
+        println!("id author the venom-chess developers");
+        println!("option name Hash type spin default {} min 1 max {}", options.config.transposition_table_size, MAX_HASH);
+        println!("option name Threads type spin default {} min 1 max {}", options.config.threads, MAX_THREADS);
+        println!("option name Ponder type check default false");
+        println!("option name MultiPV type spin default {} min 1 max {}", options.config.multi_pv, MAX_MULTI_PV);
+        println!("option name Skill Level type spin default {} min 0 max {}", MAX_LEVEL, MAX_LEVEL);
+        println!("option name OwnBook type check default {}", options.own_book);
+        println!("option name BookFile type string default {}", options.book_file.as_deref().unwrap_or("<empty>"));
+        println!("option name Best Book Move type check default {}", options.book_selection == BookSelection::Best);
+        println!("option name Contempt type spin default {} min {} max {}", options.contempt, -MAX_CONTEMPT, MAX_CONTEMPT);
+        let modes: String = ContemptMode::ALL.iter().map(|mode| format!(" var {}", mode.name())).collect();
+        println!("option name Contempt Mode type combo default {}{}", options.contempt_mode.name(), modes);
+        println!("option name Elo Difference type spin default {} min {} max {}", options.elo_difference, -MAX_ELO_DIFFERENCE, MAX_ELO_DIFFERENCE);
+
+        let solvers: String = SolverKind::ALL.iter().map(|kind| format!(" var {}", kind.name())).collect();
+        println!("option name Solver type combo default {}{}", options.solver.name(), solvers);
+        println!("uciok");
+    }
+
+    /// `setoption name <name> value <value>`. Changing the solver or its configuration starts a new game.
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args.iter().position(|&arg| arg == "value").unwrap_or(args.len());
+        let name_at = args.iter().position(|&arg| arg == "name").filter(|&i| i < value_at).map_or(0, |i| i + 1);
+        let name = args[name_at..value_at].join(" ");
+        let value = args.get(value_at + 1..).unwrap_or_default().join(" ");
+
+        let mut options = self.engine_mut().options().clone();
+        match name.to_ascii_lowercase().as_str() {
+            "hash" => match value.parse::<u64>() {
+                Ok(size) => options.config.transposition_table_size = size.clamp(1, MAX_HASH),
+                Err(_) => return println!("info string invalid hash size: {}", value),
+            },
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) => options.config.threads = threads.clamp(1, MAX_THREADS),
+                Err(_) => return println!("info string invalid number of threads: {}", value),
+            },
+            "multipv" => match value.parse::<usize>() {
+                Ok(lines) => options.config.multi_pv = lines.clamp(1, MAX_MULTI_PV),
+                Err(_) => return println!("info string invalid number of lines: {}", value),
+            },
+            "skill level" => match value.parse::<u8>() {
+                Ok(level) if level >= MAX_LEVEL => options.config.skill = None,
+                Ok(level) => options.config.skill = Some(Skill::level(level)),
+                Err(_) => return println!("info string invalid skill level: {}", value),
+            },
+            "ownbook" => options.own_book = value.eq_ignore_ascii_case("true"),
+            "bookfile" => if value.is_empty() || value == "<empty>" {
+                options.config.book = None;
+                options.book_file = None;
+            } else if let Err(e) = options.open_book(&value) {
+                return println!("info string couldn't open the book {}: {}", value, e);
+            },
+            "best book move" => options.book_selection = if value.eq_ignore_ascii_case("true") {
+                BookSelection::Best
+            } else {
+                BookSelection::WeightedRandom
+            },
+            "contempt" => match value.parse::<i64>() {
+                Ok(contempt) => options.contempt = contempt.clamp(-MAX_CONTEMPT, MAX_CONTEMPT),
+                Err(_) => return println!("info string invalid contempt: {}", value),
+            },
+            "contempt mode" => match ContemptMode::from_name(&value) {
+                Some(mode) => options.contempt_mode = mode,
+                None => return println!("info string unknown contempt mode: {}", value),
+            },
+            "elo difference" => match value.parse::<i32>() {
+                Ok(elo) => options.elo_difference = elo.clamp(-MAX_ELO_DIFFERENCE, MAX_ELO_DIFFERENCE),
+                Err(_) => return println!("info string invalid elo difference: {}", value),
+            },
+            "solver" => match SolverKind::from_name(&value) {
+                Some(kind) => options.solver = kind,
+                None => return println!("info string unknown solver: {}", value),
+            },
+            // the GUI decides when to ponder, there is nothing to set up for it
+            "ponder" => return,
+            _ => return println!("info string unknown option: {}", name),
+        }
+
+        *self.engine_mut() = Engine::new(options);
+    }
+
+    /// `go` with the limits of the search, and the clocks. The move is printed by the search thread.
+    fn go(&mut self, args: &[&str]) {
+        let white = self.game.board().current_player() == Color::White;
+        let mut limits = SearchLimits::default();
+        let mut control = TimeControl::default();
+        let mut clock = [None, None];
+        let mut ponder = false;
+
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let mut number = || args.next().and_then(|n| n.parse::<u64>().ok());
+            match arg {
+                "wtime" => clock[0] = number().map(Duration::from_millis),
+                "btime" => clock[1] = number().map(Duration::from_millis),
+                "winc" => if let Some(ms) = number().filter(|_| white) {
+                    control.increment = Duration::from_millis(ms);
+                },
+                "binc" => if let Some(ms) = number().filter(|_| !white) {
+                    control.increment = Duration::from_millis(ms);
+                },
+                "movestogo" => control.moves_to_go = number(),
+                "depth" => limits.depth = number().map(depth_limit),
+                "nodes" => limits.nodes = number(),
+                "mate" => limits.mate = number(),
+                "movetime" => limits.move_time = number().map(Duration::from_millis),
+                "infinite" => limits.infinite = true,
+                "ponder" => ponder = true,
+                _ => {}
+            }
+        }
+
+        if let [Some(white), Some(black)] = clock {
+            self.game.set_clocks([white, black]);
+        } else if let Some(time) = clock[0].or(clock[1]) {
+            let mut clocks = self.game.board().get_clock();
+            clocks[if clock[0].is_some() { 0 } else { 1 }] = time;
+            self.game.set_clocks(clocks);
+        }
+        let remaining = self.game.board().get_clock()[if white { 0 } else { 1 }];
+
+        // after a ponder hit the search goes on like it would have without pondering
+        let ponder_time = match limits.move_time {
+            Some(time) => Some(time),
+            None if limits.uses_clock() => Some(TimeManager::new(remaining, &control).soft_limit()),
+            None => None,
+        };
+        let infinite = limits.infinite;
+        if ponder {
+            limits = SearchLimits::infinite();
+        }
+
+        let stop = StopToken::new();
+        let pv = Arc::new(Mutex::new(Vec::new()));
+        let reported = pv.clone();
+        limits.stop = Some(stop.clone());
+        limits.info = Some(InfoCallback::new(move |info| {
+            println!("{}", info_line(info, white));
+            if info.multipv == 1 {
+                *reported.lock() = info.pv.clone();
+            }
+        }));
+
+        let hold = Arc::new(AtomicBool::new(ponder || infinite));
+        let mut engine = self.engine.take().expect("the engine is only away during a search");
+        let game = self.game.clone();
+        let (thread_stop, thread_hold) = (stop.clone(), hold.clone());
+
+        let handle = thread::spawn(move || {
+            let best = engine.think(&game, control, &limits);
+
+            while thread_hold.load(Ordering::Relaxed) && !thread_stop.is_stopped() {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            match best {
+                Some(m) => {
+                    let pv = pv.lock();
+                    match pv.get(1) {
+                        Some(&reply) if pv[0] == m => println!("bestmove {} ponder {}", to_lan(m), to_lan(reply)),
+                        _ => println!("bestmove {}", to_lan(m)),
+                    }
+                }
+                None => println!("bestmove 0000"),
+            }
+            engine
+        });
+
+        self.search = Some(Search { stop, hold, infinite, ponder_time, handle });
+    }
+
+    /// The opponent played the move we were pondering on: the search goes on with the time it would have had.
+    fn ponder_hit(&mut self) {
+        let search = match &self.search {
+            Some(search) => search,
+            None => return,
+        };
+
+        search.hold.store(search.infinite, Ordering::Relaxed);
+        match search.ponder_time {
+            Some(time) if !search.infinite => {
+                let stop = search.stop.clone();
+                thread::spawn(move || {
+                    thread::sleep(time);
+                    stop.stop();
+                });
+            }
+            Some(_) => {}
+            None => search.stop.stop(),
+        }
+    }
+
+    /// Stops the running search, if there is one, and waits for it to print its move.
+    fn finish_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.stop();
+            self.engine = Some(search.handle.join().expect("search thread panicked"));
+        }
+    }
+}
+
+/// `position [startpos | fen <fen>] [moves <move>...]`
+fn parse_position(args: &[&str]) -> Option<Game> {
+    let moves_at = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+
+    let mut game = match *args.first()? {
+        "startpos" => Game::from_fen(START_FEN)?,
+        "fen" => Game::from_fen(&args[1..moves_at].join(" "))?,
+        _ => return None,
+    };
+
+    for lan in args.iter().skip(moves_at + 1) {
+        let m = parse_lan(game.board(), lan)?;
+        game.play(m);
+    }
+
+    Some(game)
+}
+
+/// The `info` line the GUI gets for the progress of a search, with the score for white when `white`.
+fn info_line(info: &SearchInfo, white: bool) -> String {
+    let pv: Vec<String> = info.pv.iter().copied().map(to_lan).collect();
+    let score = match Score::of(info, white) {
+        Score::Centipawns(cp) => format!("cp {}", cp),
+        Score::Mate(moves) => format!("mate {}", moves),
+    };
+
+    let mut line = format!(
+        "info depth {} score {} nodes {} nps {} time {}",
+        info.depth + 1, score, info.nodes, info.nps, info.time.as_millis(),
+    );
+    if info.multipv > 1 {
+        line.push_str(&format!(" multipv {}", info.multipv));
+    }
+    if !pv.is_empty() {
+        line.push_str(&format!(" pv {}", pv.join(" ")));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::Board;
+    use crate::game_engine::board::polyglot::polyglot_key;
+    use crate::game_engine::color::Color;
+    use crate::game_engine::lan::parse_lan;
+    use crate::protocol::uci::{info_line, parse_position, Uci};
+    use crate::protocol::{Engine, EngineOptions, Game};
+    use crate::solver::book::{BookEntry, OpeningBook};
+    use crate::solver::contempt::Contempt;
+    use crate::solver::control::SearchInfo;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::time_manager::TimeControl;
+    use std::time::Duration;
+
+    #[test]
+    fn test_position() {
+        let game = parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"]).unwrap();
+        assert_eq!(game.moves().len(), 3);
+        assert_eq!(game.board().current_player(), Color::Black);
+
+        let game = parse_position(&["fen", "4k3/8/8/8/8/8/8/4K2R", "w", "K", "-", "0", "1", "moves", "e1g1"]).unwrap();
+        assert!(game.board().piece_at((5, 7)).is_rook());
+
+        assert!(parse_position(&["startpos", "moves", "e2e5"]).is_none());
+        assert!(parse_position(&["fen", "8/8/8/8/8/8/8/8", "w", "-", "-"]).is_none());
+    }
+
+    #[test]
+    fn test_info_line() {
+        let game = parse_position(&["startpos", "moves", "e2e4"]).unwrap();
+        let pv = vec![parse_lan(game.board(), "e7e5").unwrap()];
+
+        // scores are white's, the GUI wants them for the side to move
+        let info = SearchInfo::new(3, 35.4, 1000, Duration::from_millis(500), pv.clone());
+        assert_eq!(info_line(&info, false), "info depth 4 score cp -35 nodes 1000 nps 2000 time 500 pv e7e5");
+
+        let info = SearchInfo::new(1, f64::NEG_INFINITY, 10, Duration::from_millis(0), pv);
+        assert_eq!(info_line(&info, false), "info depth 2 score mate 1 nodes 10 nps 0 time 0 pv e7e5");
+    }
+
+    #[test]
+    fn test_set_option() {
+        let mut uci = Uci {
+            engine: Some(Engine::new(EngineOptions::default())),
+            game: Game::standard(),
+            search: None,
+        };
+
+        uci.set_option(&["name", "MultiPV", "value", "3"]);
+        assert_eq!(uci.engine_mut().options().config.multi_pv, 3);
+        uci.set_option(&["name", "multipv", "value", "0"]);
+        assert_eq!(uci.engine_mut().options().config.multi_pv, 1);
+
+        uci.set_option(&["name", "Skill", "Level", "value", "3"]);
+        assert_eq!(uci.engine_mut().options().config.skill.as_ref().and_then(|skill| skill.max_depth), Some(2));
+        uci.set_option(&["name", "Skill", "Level", "value", "20"]);
+        assert!(uci.engine_mut().options().config.skill.is_none());
+
+        uci.set_option(&["name", "Contempt", "value", "20"]);
+        assert!(matches!(uci.engine_mut().options().search_config().contempt, Contempt::Fixed(c) if c == 20.0));
+        uci.set_option(&["name", "Contempt", "Mode", "value", "opponent"]);
+        uci.set_option(&["name", "Elo", "Difference", "value", "-300"]);
+        assert!(matches!(uci.engine_mut().options().search_config().contempt, Contempt::Opponent { elo_difference: -300 }));
+    }
+
+    #[test]
+    fn test_book() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let book_move = parse_lan(game.board(), "g1f1").unwrap();
+        let mate = parse_lan(game.board(), "a1a8").unwrap();
+
+        let path = std::env::temp_dir().join(format!("venom-chess-test-{}.bin", std::process::id()));
+        OpeningBook::from_entries(vec![BookEntry {
+            key: polyglot_key(game.board()),
+            mv: BookEntry::encode_move(book_move),
+            weight: 1,
+            learn: 0,
+        }]).save(&path).unwrap();
+
+        let mut uci = Uci {
+            engine: Some(Engine::new(EngineOptions::default())),
+            game: Game::standard(),
+            search: None,
+        };
+        uci.set_option(&["name", "BookFile", "value", path.to_str().unwrap()]);
+        uci.set_option(&["name", "OwnBook", "value", "true"]);
+        std::fs::remove_file(&path).unwrap();
+
+        let depth = SearchLimits {
+            depth: Some(1),
+            ..SearchLimits::default()
+        };
+        assert_eq!(uci.engine_mut().think(&game, TimeControl::default(), &depth), Some(book_move));
+
+        // looking for a mate means searching
+        let mate_limits = SearchLimits {
+            mate: Some(1),
+            ..SearchLimits::default()
+        };
+        assert_eq!(uci.engine_mut().think(&game, TimeControl::default(), &mate_limits), Some(mate));
+
+        uci.set_option(&["name", "OwnBook", "value", "false"]);
+        assert_eq!(uci.engine_mut().think(&game, TimeControl::default(), &depth), Some(mate));
+    }
+}
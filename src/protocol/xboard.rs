@@ -0,0 +1,460 @@
+//! The Chess Engine Communication Protocol (CECP) of xboard and WinBoard, version 2.
+//!
+//! Like with UCI, searches run on their own thread. In xboard the engine keeps track of the game
+//! itself, so the move of a search is only played when the main thread collects it. A search that
+//! is interrupted by `force`, `new`, `undo` and the like has its move thrown away, unless it was
+//! already sent to the GUI.
+
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::Move;
+use crate::game_engine::color::Color;
+use crate::game_engine::king_check::king_check;
+use crate::game_engine::lan::{parse_lan, to_lan};
+use crate::game_engine::pgn::GameResult;
+use crate::game_engine::san::to_san;
+use crate::protocol::{depth_limit, Engine, EngineOptions, Game, GameBoard, Score, UNTIMED};
+use crate::solver::control::{InfoCallback, SearchInfo, StopToken};
+use crate::solver::limits::SearchLimits;
+use crate::solver::time_manager::TimeControl;
+use parking_lot::Mutex;
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How xboard reports a mate in n moves: this plus n, or minus this minus n when getting mated.
+const MATE_SCORE: i64 = 100_000;
+
+/// The time control of `level`: `moves` moves in `base` time (0 for the whole game), plus `increment` per move.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+struct Level {
+    moves: u64,
+    base: Duration,
+    increment: Duration,
+}
+
+impl Level {
+    /// The moves left in the current period, after `plies` plies since the control started.
+    fn moves_to_go(&self, plies: usize) -> Option<u64> {
+        match self.moves {
+            0 => None,
+            moves => Some(moves - (plies as u64 / 2) % moves),
+        }
+    }
+}
+
+/// A search that is running on its own thread.
+struct Search {
+    stop: StopToken,
+    /// Set when the move of the search must not be played anymore.
+    /// The search thread holds the lock while it sends its move, so the move is sent or thrown away, never both.
+    discarded: Arc<Mutex<bool>>,
+    /// The solver comes back with the move that was sent, if it was
+    handle: JoinHandle<(Engine, Option<Move>)>,
+}
+
+struct XBoard {
+    /// `None` while a search has it
+    engine: Option<Engine>,
+    game: Game,
+    search: Option<Search>,
+
+    /// The side the engine plays, `None` in force mode
+    side: Option<Color>,
+    /// Send thinking output
+    post: bool,
+
+    level: Level,
+    /// The ply of the game at which `level` started the current control
+    level_start: usize,
+    /// Exactly this long per move, from `st`
+    move_time: Option<Duration>,
+    /// At most this many plies, from `sd`
+    depth: Option<u64>,
+    /// The clocks, from `time` and `otim`
+    time: Duration,
+    opponent_time: Duration,
+}
+
+/// Plays with the solvers over CECP on stdin and stdout, until the GUI says `quit`.
+pub fn run(options: EngineOptions) {
+    let mut xboard = XBoard {
+        engine: Some(Engine::new(options)),
+        game: Game::standard(),
+        search: None,
+        side: Some(Color::Black),
+        post: false,
+        level: Level { moves: 0, base: UNTIMED, increment: Duration::from_secs(0) },
+        level_start: 0,
+        move_time: None,
+        depth: None,
+        time: UNTIMED,
+        opponent_time: UNTIMED,
+    };
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !xboard.handle(&line) {
+            break;
+        }
+    }
+
+    xboard.abort();
+}
+
+impl XBoard {
+    /// Handles a command from the GUI. Returns false when the engine should quit.
+    fn handle(&mut self, line: &str) -> bool {
+        self.collect();
+
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return true,
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "protover" => self.features(),
+            "new" => {
+                self.abort();
+                self.engine_mut().new_game();
+                self.game = Game::standard();
+                self.level_start = 0;
+                self.side = Some(Color::Black);
+                self.depth = None;
+                self.time = self.level.base;
+                self.opponent_time = self.level.base;
+            }
+            "force" | "result" => {
+                self.abort();
+                self.side = None;
+            }
+            "go" => {
+                self.abort();
+                self.side = Some(self.game.board().current_player());
+                self.think();
+            }
+            "usermove" => match args.first() {
+                Some(lan) => self.user_move(lan),
+                None => println!("Error (no move given): {}", line),
+            },
+            "?" => self.move_now(),
+            "undo" => {
+                self.abort();
+                self.game.undo();
+            }
+            "remove" => {
+                self.abort();
+                self.game.undo();
+                self.game.undo();
+            }
+            "setboard" => {
+                self.abort();
+                match Game::from_fen(&args.join(" ")) {
+                    Some(game) => {
+                        self.game = game;
+                        self.level_start = 0;
+                    }
+                    None => println!("tellusererror Illegal position"),
+                }
+            }
+            "level" => match parse_level(&args) {
+                Some(level) => {
+                    self.level = level;
+                    self.level_start = self.game.moves().len();
+                    self.time = level.base;
+                    self.opponent_time = level.base;
+                    self.move_time = None;
+                }
+                None => println!("Error (invalid time control): {}", line),
+            },
+            "st" => match args.first().and_then(|s| s.parse::<f64>().ok()).filter(|&s| s > 0.0) {
+                Some(seconds) => self.move_time = Some(Duration::from_secs_f64(seconds)),
+                None => println!("Error (invalid time): {}", line),
+            },
+            "sd" => match args.first().and_then(|d| d.parse().ok()) {
+                Some(depth) => self.depth = Some(depth),
+                None => println!("Error (invalid depth): {}", line),
+            },
+            "time" => match args.first().and_then(|t| parse_centiseconds(t)) {
+                Some(time) => self.time = time,
+                None => println!("Error (invalid time): {}", line),
+            },
+            "otim" => match args.first().and_then(|t| parse_centiseconds(t)) {
+                Some(time) => self.opponent_time = time,
+                None => println!("Error (invalid time): {}", line),
+            },
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "ping" => println!("pong {}", args.first().unwrap_or(&"")),
+            // the ratings of the engine and its opponent, for the opponent contempt mode
+            "rating" => match (args.first().and_then(|r| r.parse::<i32>().ok()), args.get(1).and_then(|r| r.parse::<i32>().ok())) {
+                // unrated players are 0
+                (Some(own), Some(opponent)) if own > 0 && opponent > 0 => {
+                    self.abort();
+                    let mut options = self.engine_mut().options().clone();
+                    options.elo_difference = own - opponent;
+                    *self.engine_mut() = Engine::new(options);
+                }
+                (Some(_), Some(_)) => {}
+                _ => println!("Error (invalid ratings): {}", line),
+            },
+            "quit" => return false,
+            // things we don't do anything with
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "ics" | "draw" | "hint" | "bk" | "variant" => {}
+            // moves without `usermove`, for GUIs that don't know the feature
+            _ if parse_lan(self.game.board(), command).is_some() => self.user_move(command),
+            _ => println!("Error (unknown command): {}", command),
+        }
+
+        true
+    }
+
+    fn engine_mut(&mut self) -> &mut Engine {
+        self.engine.as_mut().expect("the engine is only away during a search")
+    }
+
+    fn features(&self) {
+        println!("feature myname=\"venom-chess\" variants=\"normal\" setboard=1 usermove=1 ping=1 time=1 reuse=1 san=0 colors=0 draw=0 analyze=0 sigint=0 sigterm=0");
+        println!("feature done=1");
+    }
+
+    fn user_move(&mut self, lan: &str) {
+        // the GUI only sends a move after ours, so a search can only be finishing
+        self.wait();
+
+        match parse_lan(self.game.board(), lan) {
+            Some(m) if result(self.game.board()).is_none() => {
+                self.play(m);
+                self.think();
+            }
+            _ => println!("Illegal move: {}", lan),
+        }
+    }
+
+    /// Plays `m` in the game, and tells the GUI when it ended the game.
+    fn play(&mut self, m: Move) {
+        self.game.play(m);
+
+        if let Some((result, reason)) = result(self.game.board()) {
+            println!("{} {{{}}}", result.as_str(), reason);
+        }
+    }
+
+    /// Starts a search when the engine is to move.
+    fn think(&mut self) {
+        let board = self.game.board();
+        let current = board.current_player();
+        if self.side != Some(current) || result(board).is_some() {
+            return;
+        }
+
+        let (white, black) = if current == Color::White {
+            (self.time, self.opponent_time)
+        } else {
+            (self.opponent_time, self.time)
+        };
+        self.game.set_clocks([white, black]);
+
+        let plies = self.game.moves().len().saturating_sub(self.level_start);
+        let control = TimeControl {
+            increment: self.level.increment,
+            moves_to_go: self.level.moves_to_go(plies),
+            ..TimeControl::default()
+        };
+
+        let stop = StopToken::new();
+        let mut limits = SearchLimits {
+            depth: self.depth.map(depth_limit),
+            move_time: self.move_time,
+            stop: Some(stop.clone()),
+            ..SearchLimits::default()
+        };
+        if self.post {
+            let board = self.game.board().clone();
+            limits.info = Some(InfoCallback::new(move |info| {
+                if info.multipv == 1 {
+                    println!("{}", thinking_line(&board, info));
+                }
+            }));
+        }
+
+        let discarded = Arc::new(Mutex::new(false));
+        let mut engine = self.engine.take().expect("the engine is only away during a search");
+        let game = self.game.clone();
+        let thread_discarded = discarded.clone();
+
+        let handle = thread::spawn(move || {
+            let best = engine.think(&game, control, &limits);
+
+            let discarded = thread_discarded.lock();
+            let played = best.filter(|_| !*discarded);
+            if let Some(m) = played {
+                println!("move {}", to_lan(m));
+            }
+            (engine, played)
+        });
+
+        self.search = Some(Search { stop, discarded, handle });
+    }
+
+    /// Plays the move of a search that is done.
+    fn collect(&mut self) {
+        if self.search.as_ref().is_some_and(|search| search.handle.is_finished()) {
+            self.wait();
+        }
+    }
+
+    /// Waits for the running search, if there is one, and plays its move.
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            let (engine, played) = search.handle.join().expect("search thread panicked");
+            self.engine = Some(engine);
+            if let Some(m) = played {
+                self.play(m);
+            }
+        }
+    }
+
+    /// `?`: stops the search, which plays the best move it found so far.
+    fn move_now(&mut self) {
+        if let Some(search) = &self.search {
+            search.stop.stop();
+        }
+        self.wait();
+    }
+
+    /// Stops the search without playing its move, unless it was already sent.
+    fn abort(&mut self) {
+        if let Some(search) = &self.search {
+            *search.discarded.lock() = true;
+            search.stop.stop();
+        }
+        self.wait();
+    }
+}
+
+/// How the game ended in `board`, with the reason the GUI shows, or `None` when it goes on.
+/// The clocks aren't looked at: the GUI knows when a flag fell.
+fn result(board: &GameBoard) -> Option<(GameResult, &'static str)> {
+    let current = board.current_player();
+
+    if board.all_moves().is_empty() {
+        if !king_check(board, current) {
+            Some((GameResult::Draw, "Stalemate"))
+        } else if current == Color::White {
+            Some((GameResult::BlackWins, "Black mates"))
+        } else {
+            Some((GameResult::WhiteWins, "White mates"))
+        }
+    } else if board.halfmove_clock() >= 100 {
+        Some((GameResult::Draw, "Fifty move rule"))
+    } else {
+        None
+    }
+}
+
+/// `level <moves> <base> <increment>`, where the base is in minutes or minutes:seconds,
+/// and the increment in seconds.
+fn parse_level(args: &[&str]) -> Option<Level> {
+    if args.len() != 3 {
+        return None;
+    }
+
+    let moves = args[0].parse().ok()?;
+    let base = match args[1].split_once(':') {
+        Some((minutes, seconds)) => minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?,
+        None => args[1].parse::<u64>().ok()? * 60,
+    };
+    let increment = args[2].parse::<f64>().ok().filter(|&inc| inc >= 0.0)?;
+
+    Some(Level {
+        moves,
+        base: Duration::from_secs(base),
+        increment: Duration::from_secs_f64(increment),
+    })
+}
+
+/// The clocks of `time` and `otim`, in centiseconds. A flag that fell still leaves
+/// a bit of time, because an empty clock would end the game for the solvers.
+fn parse_centiseconds(time: &str) -> Option<Duration> {
+    let centiseconds = time.parse::<i64>().ok()?.max(1);
+    Some(Duration::from_millis(centiseconds as u64 * 10))
+}
+
+/// The thinking output for `post`: depth, score in centipawns for the side to move,
+/// time in centiseconds, nodes and the principal variation in SAN.
+fn thinking_line(board: &GameBoard, info: &SearchInfo) -> String {
+    let score = match Score::of(info, board.current_player() == Color::White) {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => MATE_SCORE + moves,
+        Score::Mate(moves) => -MATE_SCORE + moves,
+    };
+
+    let mut position = board.clone();
+    let mut pv = Vec::new();
+    for &m in &info.pv {
+        pv.push(to_san(&position, m));
+        position = position.transition(m);
+    }
+
+    format!("{} {} {} {} {}", info.depth + 1, score, info.time.as_millis() / 10, info.nodes, pv.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::lan::parse_lan;
+    use crate::protocol::Game;
+    use crate::protocol::xboard::{parse_level, result, thinking_line, Level};
+    use crate::solver::control::SearchInfo;
+    use std::time::Duration;
+
+    #[test]
+    fn test_level() {
+        let level = parse_level(&["40", "5", "0"]).unwrap();
+        assert_eq!(level, Level { moves: 40, base: Duration::from_secs(300), increment: Duration::from_secs(0) });
+
+        let level = parse_level(&["0", "2:30", "1.5"]).unwrap();
+        assert_eq!(level, Level { moves: 0, base: Duration::from_secs(150), increment: Duration::from_millis(1500) });
+
+        assert!(parse_level(&["0", "2:x", "0"]).is_none());
+        assert!(parse_level(&["40", "5"]).is_none());
+    }
+
+    #[test]
+    fn test_moves_to_go() {
+        let level = parse_level(&["40", "5", "0"]).unwrap();
+        assert_eq!(level.moves_to_go(0), Some(40));
+        assert_eq!(level.moves_to_go(1), Some(40));
+        assert_eq!(level.moves_to_go(2), Some(39));
+        assert_eq!(level.moves_to_go(79), Some(1));
+        assert_eq!(level.moves_to_go(80), Some(40));
+
+        assert_eq!(parse_level(&["0", "5", "0"]).unwrap().moves_to_go(10), None);
+    }
+
+    #[test]
+    fn test_thinking_and_result() {
+        let mut game = Game::standard();
+        for lan in &["f2f3", "e7e5", "g2g4"] {
+            let m = parse_lan(game.board(), lan).unwrap();
+            game.play(m);
+        }
+        assert!(result(game.board()).is_none());
+
+        let mate = parse_lan(game.board(), "d8h4").unwrap();
+        let info = SearchInfo::new(0, f64::NEG_INFINITY, 50, Duration::from_millis(120), vec![mate]);
+        assert_eq!(thinking_line(game.board(), &info), "1 100001 12 50 Qh4#");
+
+        game.play(mate);
+        assert_eq!(result(game.board()).map(|(result, reason)| (result.as_str(), reason)), Some(("0-1", "Black mates")));
+
+        assert_eq!(game.undo(), Some(mate));
+        assert!(result(game.board()).is_none());
+    }
+}
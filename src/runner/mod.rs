@@ -4,6 +4,14 @@ use crate::solver::Solver;
 use crate::stats::Stats;
 use crate::game_engine::color::Color::White;
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::game_engine::board::BasicBoard;
+use crate::game_engine::board::polyglot::polyglot_key;
+use crate::game_engine::pgn::{PgnGame, GameResult};
+use crate::game_engine::san::to_san;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
 
 pub struct Runner<S1, S2> {
     black_solver: S1,
@@ -11,6 +19,9 @@ pub struct Runner<S1, S2> {
 
     black_stats: Stats,
     white_stats: Stats,
+
+    /// Finished games are appended to this file in PGN
+    pgn_file: Option<PathBuf>,
 }
 
 impl<S1: Solver, S2: Solver> Runner<S1, S2> {
@@ -21,13 +32,54 @@ impl<S1: Solver, S2: Solver> Runner<S1, S2> {
 
             black_solver,
             white_solver,
+
+            pgn_file: None,
+        }
+    }
+
+    /// Appends every game to `path` in PGN, so that it can be replayed or used to build an opening book.
+    pub fn with_pgn_file(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            pgn_file: Some(path.into()),
+            ..self
         }
     }
 
-    pub fn run<B: Board>(&mut self, board: B) -> Color {
+    fn record_game(&self, moves: Vec<String>, winner: Color) {
+        let path = match &self.pgn_file {
+            Some(path) => path,
+            None => return,
+        };
+
+        let game = PgnGame {
+            tags: vec![
+                ("Event".to_string(), "venom-chess self-play".to_string()),
+                ("White".to_string(), self.white_stats.algorithm_name().to_string()),
+                ("Black".to_string(), self.black_stats.algorithm_name().to_string()),
+            ],
+            moves,
+            result: GameResult::from_winner(winner),
+        };
+
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", game));
+
+        if let Err(e) = written {
+            println!("couldn't write the game to {:?}: {}", path, e);
+        }
+    }
+
+    pub fn run<B: Board + 'static>(&mut self, board: B) -> Color {
         let mut db = DisplayableBoard::new(board);
         let mut no_move_counter = 0;
 
+        // PGN games without a FEN tag start from the initial position
+        let start = BasicBoard::default_board(Duration::from_secs(0));
+        let mut moves = if polyglot_key(&db) == polyglot_key(&start) { Some(Vec::new()) } else { None };
+
         loop {
             if let Some(i) = db.is_terminal() {
                 println!("{}", db);
@@ -35,6 +87,10 @@ impl<S1: Solver, S2: Solver> Runner<S1, S2> {
 
                 println!("{:?} won", i);
 
+                if let Some(moves) = moves {
+                    self.record_game(moves, i);
+                }
+
                 return i;
             }
 
@@ -50,6 +106,7 @@ impl<S1: Solver, S2: Solver> Runner<S1, S2> {
                     // println!("white stats: {:?}", self.white_stats.last_entry());
                 }
 
+                let before = db.clone();
                 db = match self.black_solver.make_move(db.clone(), self.black_stats.clone()) {
                     Some(i) => i,
                     None => {
@@ -58,6 +115,14 @@ impl<S1: Solver, S2: Solver> Runner<S1, S2> {
                         continue;
                     }
                 };
+
+                if let Some(m) = db.last_move() {
+                    if let Some(moves) = &mut moves {
+                        moves.push(to_san(&before, m));
+                    }
+                    self.white_solver.opponent_played(&db, m);
+                }
+                self.black_solver.opponent_thinking(&db);
             } else {
                 if !S2::PRINT_OWN_BOARD {
                     println!("{}", db);
@@ -65,6 +130,7 @@ impl<S1: Solver, S2: Solver> Runner<S1, S2> {
                     // println!("black stats: {:?}", self.black_stats.last_entry());
                     // println!("white stats: {:?}", self.white_stats.last_entry());
                 }
+                let before = db.clone();
                 db = match self.white_solver.make_move(db.clone(), self.white_stats.clone()) {
                     Some(i) => i,
                     None => {
@@ -74,6 +140,14 @@ impl<S1: Solver, S2: Solver> Runner<S1, S2> {
                         continue;
                     }
                 };
+
+                if let Some(m) = db.last_move() {
+                    if let Some(moves) = &mut moves {
+                        moves.push(to_san(&before, m));
+                    }
+                    self.black_solver.opponent_played(&db, m);
+                }
+                self.white_solver.opponent_thinking(&db);
             }
         }
     }
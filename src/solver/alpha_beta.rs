@@ -2,35 +2,40 @@ use crate::game_engine::board::Board;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use crate::game_engine::color::Color::{White, Black};
-use crate::solver::Solver;
+use crate::solver::{tablebase_move, Solver};
 use crate::solver::move_order::order_moves;
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::solver::limits::SearchLimits;
+use crate::solver::contempt::{Contempt, terminal_score};
+use std::time::SystemTime;
 
 pub struct AlphaBeta {
-    search_depth: u64
+    search_depth: u64,
+    contempt: Contempt,
+    /// The score of a draw in the current search, from the contempt at its root
+    draw_score: f64,
 }
 
 impl AlphaBeta {
     pub fn new(search_depth: u64) -> Self {
+        Self::with_contempt(search_depth, Contempt::default())
+    }
+
+    pub fn with_contempt(search_depth: u64, contempt: Contempt) -> Self {
         Self {
-            search_depth
+            search_depth,
+            contempt,
+            draw_score: 0.0,
         }
     }
 
-    pub fn mini_max_ab(board: &impl Board, depth: u64, mut a: f64, mut b: f64, stats: &mut StatsEntry) -> f64 {
+    pub fn mini_max_ab(&self, board: &impl Board, depth: u64, mut a: f64, mut b: f64, stats: &mut StatsEntry) -> f64 {
         stats.seen_state();
 
         if depth == 0 || board.is_terminal().is_some() {
-            let terminal = board.is_terminal();
-            if terminal.is_some() {
-                return if terminal == Some(Black) {
-                    f64::NEG_INFINITY
-                } else if terminal == Some(White) {
-                    f64::INFINITY
-                } else {
-                    0.0
-                };
+            if let Some(winner) = board.is_terminal() {
+                return terminal_score(winner, self.draw_score);
             }
             return board.heuristic();
         }
@@ -39,7 +44,7 @@ impl AlphaBeta {
             let mut value = f64::NEG_INFINITY;
             for move_res in order_moves(board.all_moves(), board) {
 
-                value = value.max(Self::mini_max_ab(&move_res.board, depth - 1, a, b, stats));
+                value = value.max(self.mini_max_ab(&move_res.board, depth - 1, a, b, stats));
                 a = a.max(value);
                 if a >= b {
                     break;
@@ -50,7 +55,7 @@ impl AlphaBeta {
             let mut value = f64::INFINITY;
             for move_res in order_moves(board.all_moves(), board) {
 
-                value = value.min(Self::mini_max_ab(&move_res.board, depth - 1, a, b, stats));
+                value = value.min(self.mini_max_ab(&move_res.board, depth - 1, a, b, stats));
                 b = b.min(value);
                 if b <= a {
                     break;
@@ -62,15 +67,27 @@ impl AlphaBeta {
 }
 
 impl Solver for AlphaBeta {
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mut rng = thread_rng();
+        let start = SystemTime::now();
+        let search_depth = limits.fixed_depth(self.search_depth);
+        self.draw_score = self.contempt.draw_score(&board, board.current_player());
+
+        if let Some((m, score)) = tablebase_move(&board, self.draw_score, stats) {
+            stats.evaluation(score);
+            return Some(board.transition(m));
+        }
 
         let mut best_moves = Vec::new();
 
         if board.current_player() == White {
             let mut best = f64::NEG_INFINITY;
             for move_res in order_moves(board.all_moves(), &board) {
-                let score = Self::mini_max_ab(&move_res.board, self.search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
+                let score = self.mini_max_ab(&move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
                 if score > best {
                     best = score;
                     best_moves = Vec::new();
@@ -86,7 +103,11 @@ impl Solver for AlphaBeta {
         if board.current_player() == Black {
             let mut best = f64::INFINITY;
             for move_res in order_moves(board.all_moves(), &board) {
-                let score = Self::mini_max_ab(&move_res.board, self.search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
+                let score = self.mini_max_ab(&move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
                 if score < best {
                     best = score;
                     best_moves = Vec::new();
@@ -2,35 +2,36 @@ use crate::game_engine::board::Board;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use crate::game_engine::color::Color::{White, Black};
-use crate::solver::Solver;
-use crate::transposition_table::TranspositionTable;
-use crate::solver::move_order::order_moves;
+use crate::solver::{tablebase_move, Solver};
+use crate::transposition_table::{TranspositionTable, Entry, Bound};
+use crate::solver::move_order::{order_moves, order_moves_with_history};
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::solver::limits::SearchLimits;
+use crate::solver::contempt::{Contempt, terminal_score};
+use std::time::SystemTime;
 
 
 pub struct AlphaBetaTransp {
     search_depth: u64,
-    transposition_table: TranspositionTable<Entry>,
-}
-
-enum EntryType {
-    Exact,
-    Lower,
-    Upper,
-}
-
-struct Entry {
-    depth: u64,
-    value: f64,
-    tp: EntryType,
+    transposition_table: TranspositionTable,
+    contempt: Contempt,
+    /// The score of a draw in the current search, from the contempt at its root
+    draw_score: f64,
 }
 
 impl AlphaBetaTransp {
+    /// `transposition_size` is the size of the transposition table in megabytes
     pub fn new(search_depth: u64, transposition_size: u64) -> Self {
+        Self::with_contempt(search_depth, transposition_size, Contempt::default())
+    }
+
+    pub fn with_contempt(search_depth: u64, transposition_size: u64, contempt: Contempt) -> Self {
         Self {
             search_depth,
             transposition_table: TranspositionTable::new(transposition_size),
+            contempt,
+            draw_score: 0.0,
         }
     }
 
@@ -38,18 +39,21 @@ impl AlphaBetaTransp {
         stats.seen_state();
 
         let board_hash = board.hash();
+        let mut hash_move = None;
 
         // Transposition table match
         if let Some(entry) = self.transposition_table.get(board_hash) {
+            hash_move = entry.best_move;
+
             if entry.depth >= depth {
                 stats.transposition().hit();
 
-                match entry.tp {
-                    EntryType::Exact => return entry.value,
-                    EntryType::Lower if entry.value > a => {
+                match entry.bound {
+                    Bound::Exact => return entry.value,
+                    Bound::Lower if entry.value > a => {
                         a = entry.value;
                     }
-                    EntryType::Upper if entry.value < b => {
+                    Bound::Upper if entry.value < b => {
                         b = entry.value;
                     }
                     _ => (),
@@ -61,50 +65,37 @@ impl AlphaBetaTransp {
             }
         }
 
+        let (original_a, original_b) = (a, b);
+
         if depth == 0 || board.is_terminal().is_some() {
             let terminal = board.is_terminal();
 
-            let value = if terminal.is_some() {
-                if terminal == Some(Black) {
-                    f64::NEG_INFINITY
-                } else if terminal == Some(White) {
-                    f64::INFINITY
-                } else {
-                    0.0
-                }
-            } else {
-                board.heuristic()
+            let value = match terminal {
+                Some(winner) => terminal_score(winner, self.draw_score),
+                None => board.heuristic(),
             };
 
-            if value <= a {
-                self.transposition_table.insert(board_hash, Entry {
-                    depth,
-                    value,
-                    tp: EntryType::Lower
-                }, stats.transposition());
-            } else if value >= b {
-                self.transposition_table.insert(board_hash, Entry {
-                    depth,
-                    value,
-                    tp: EntryType::Upper
-                }, stats.transposition());
-            } else{
-                self.transposition_table.insert(board_hash, Entry {
-                    depth,
-                    value,
-                    tp: EntryType::Exact
-                }, stats.transposition());
-            }
+            self.transposition_table.insert(board_hash, Entry {
+                depth,
+                value,
+                bound: Bound::from_window(value, original_a, original_b),
+                best_move: None,
+            }, stats.transposition());
 
             return value;
         }
 
 
         let mut value;
+        let mut best_move = None;
         if board.current_player() == White {
             value = f64::NEG_INFINITY;
-            for move_res in order_moves(board.all_moves(),&board) {
-                value = value.max(self.mini_max_ab(move_res.board, depth - 1, a, b, stats));
+            for move_res in order_moves_with_history(board.all_moves(), &board, hash_move, None, 0, None) {
+                let score = self.mini_max_ab(move_res.board, depth - 1, a, b, stats);
+                if score > value || best_move.is_none() {
+                    value = score;
+                    best_move = Some(move_res.mv);
+                }
                 a = a.max(value);
                 if a >= b {
                     break;
@@ -113,8 +104,12 @@ impl AlphaBetaTransp {
 
         } else {
             value = f64::INFINITY;
-            for move_res in order_moves(board.all_moves(),&board) {
-                value = value.min(self.mini_max_ab(move_res.board, depth - 1, a, b, stats));
+            for move_res in order_moves_with_history(board.all_moves(), &board, hash_move, None, 0, None) {
+                let score = self.mini_max_ab(move_res.board, depth - 1, a, b, stats);
+                if score < value || best_move.is_none() {
+                    value = score;
+                    best_move = Some(move_res.mv);
+                }
                 b = b.min(value);
                 if b <= a {
                     break;
@@ -122,34 +117,30 @@ impl AlphaBetaTransp {
             }
         }
 
-        if value <= a {
-            self.transposition_table.insert(board_hash, Entry {
-                depth,
-                value,
-                tp: EntryType::Lower
-            }, stats.transposition());
-        } else if value >= b {
-            self.transposition_table.insert(board_hash, Entry {
-                depth,
-                value,
-                tp: EntryType::Upper
-            }, stats.transposition());
-        } else{
-            self.transposition_table.insert(board_hash, Entry {
-                depth,
-                value,
-                tp: EntryType::Exact
-            }, stats.transposition());
-        }
+        self.transposition_table.insert(board_hash, Entry {
+            depth,
+            value,
+            bound: Bound::from_window(value, original_a, original_b),
+            best_move,
+        }, stats.transposition());
 
         return value
     }
 }
 
 impl Solver for AlphaBetaTransp {
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mut rng = thread_rng();
+        let start = SystemTime::now();
+        let search_depth = limits.fixed_depth(self.search_depth);
 
+        self.transposition_table.new_search();
+        self.draw_score = self.contempt.draw_score(&board, board.current_player());
+
+        if let Some((m, score)) = tablebase_move(&board, self.draw_score, stats) {
+            stats.evaluation(score);
+            return Some(board.transition(m));
+        }
 
         let mut best_moves = Vec::new();
 
@@ -157,7 +148,11 @@ impl Solver for AlphaBetaTransp {
         if board.current_player() == White {
             best = f64::NEG_INFINITY;
             for move_res in order_moves(board.all_moves(),&board) {
-                let score = self.mini_max_ab(move_res.board, self.search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
+                let score = self.mini_max_ab(move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
                 if score > best {
                     best = score;
                     best_moves = Vec::new();
@@ -171,7 +166,11 @@ impl Solver for AlphaBetaTransp {
         if board.current_player() == Black {
             best = f64::INFINITY;
             for move_res in order_moves(board.all_moves(),&board) {
-                let score = self.mini_max_ab(move_res.board, self.search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
+                let score = self.mini_max_ab(move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
                 if score < best {
                     best = score;
                     best_moves = Vec::new();
@@ -184,15 +183,17 @@ impl Solver for AlphaBetaTransp {
 
         stats.evaluation(best);
 
+        let m = best_moves.into_iter().choose(&mut rng)?;
+
         let board_hash = board.hash();
         self.transposition_table.insert(board_hash, Entry {
-            depth: self.search_depth,
+            depth: search_depth + 1,
             value: best,
-            tp: EntryType::Exact
+            bound: Bound::Exact,
+            best_move: Some(m),
         }, stats.transposition());
 
-
-        let m = best_moves.into_iter().choose(&mut rng)?;
+        stats.transposition().hashfull(self.transposition_table.hashfull());
 
         Some(board.transition(m))
     }
@@ -200,8 +201,4 @@ impl Solver for AlphaBetaTransp {
     fn init_stats(&self, stats_folder: String) -> Stats {
         Stats::new("Minimax with Alpha-Beta pruning using a transposition table", Some(self.search_depth), Some(self.transposition_table.len()), stats_folder, true)
     }
-
-    // fn stats(&self) -> String {
-    //     format!("tp hits: {:.3}% explored: {} evaluation: {}, tu: {}, tc: {}", self.table_hits as f64 / self.explored as f64 * 100.0, self.explored, self.last_best, self.transposition_table.used, self.transposition_table.collisions)
-    // }
-}
\ No newline at end of file
+}
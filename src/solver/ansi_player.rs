@@ -11,6 +11,7 @@ use crate::game_engine::chess_move::{Move, Extra};
 use crate::game_engine::board::display::DisplayableBoard;
 use crate::game_engine::color::Color;
 use crate::game_engine::piece::{queen_of_color, rook_of_color, bishop_of_color, knight_of_color};
+use crate::solver::limits::SearchLimits;
 
 pub struct AnsiPlayer {
 
@@ -27,7 +28,7 @@ impl AnsiPlayer {
 impl Solver for AnsiPlayer {
     const PRINT_OWN_BOARD: bool = true;
 
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, _limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mv = match make_move_input(board.clone(), stats) {
             Ok(i) => i,
             Err(_) => {
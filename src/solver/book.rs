@@ -0,0 +1,263 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::board::polyglot::polyglot_key;
+use crate::game_engine::chess_move::{Move, Location, Extra};
+use crate::stats::StatsEntry;
+use rand::{thread_rng, Rng};
+use std::convert::TryInto;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::fs;
+
+/// The size of one entry of a Polyglot book on disk
+pub const ENTRY_SIZE: usize = 16;
+
+/// One move of a Polyglot book. On disk all fields are big endian.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BookEntry {
+    /// The `polyglot_key` of the position
+    pub key: u64,
+    /// The move, with three bits each for the file and rank of the destination, the file and
+    /// rank of the origin and the promotion piece. Castling is encoded as the king taking its rook.
+    pub mv: u16,
+    /// How good the move is, relative to the other moves of the position. 0 means never play it.
+    pub weight: u16,
+    /// Used by some programs to learn from games, ignored here
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        let mut key = [0; 8];
+        key.copy_from_slice(&bytes[0..8]);
+        let mut learn = [0; 4];
+        learn.copy_from_slice(&bytes[12..16]);
+
+        Self {
+            key: u64::from_be_bytes(key),
+            mv: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes(learn),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.mv.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+
+    /// `m` in the move encoding of Polyglot books.
+    pub fn encode_move(m: Move) -> u16 {
+        // polyglot counts ranks from white's side
+        let square = |l: Location| ((7 - l.y as u16) << 3) | l.x as u16;
+
+        // castling: the king "takes" its own rook
+        let to = match m.extra {
+            Extra::KingCastle => Location::new(7, m.to.y),
+            Extra::QueenCastle => Location::new(0, m.to.y),
+            _ => m.to,
+        };
+
+        (promotion_number(&m) << 12) | (square(m.from) << 6) | square(to)
+    }
+
+    /// The legal move of `board` this entry describes, if there is one.
+    pub fn decode_move(&self, board: &impl Board) -> Option<Move> {
+        let square = |bits: u16| {
+            // polyglot counts ranks from white's side
+            Location::new((bits & 7) as i8, 7 - ((bits >> 3) & 7) as i8)
+        };
+        let from = square(self.mv >> 6);
+        let mut to = square(self.mv);
+        let promotion = (self.mv >> 12) & 7;
+
+        // castling: the king "takes" its own rook
+        if board.piece_at(from).is_king() && from.x == 4 && board.piece_at(to).color() == board.piece_at(from).color() {
+            to = Location::new(if to.x == 7 { 6 } else { 2 }, to.y);
+        }
+
+        board.moves(from)
+            .into_iter()
+            .find(|m| m.to == to && promotion_number(m) == promotion)
+    }
+}
+
+/// A promotion in the numbering of Polyglot: 0 for none, then knight, bishop, rook and queen.
+fn promotion_number(m: &Move) -> u16 {
+    let extra = m.extra as u16;
+    if extra >= 8 { (extra & 3) + 1 } else { 0 }
+}
+
+/// An opening book in the Polyglot `.bin` format: entries sorted by key, so that all
+/// moves of a position are next to each other.
+#[derive(Clone, Debug, Default)]
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+}
+
+impl OpeningBook {
+    /// A book of `entries`, which don't have to be sorted.
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        // the best moves first, like other programs write them
+        entries.sort_by_key(|e| (e.key, std::cmp::Reverse(e.weight)));
+
+        Self {
+            entries,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the size of a polyglot book is a multiple of 16 bytes"));
+        }
+
+        let entries = bytes.chunks_exact(ENTRY_SIZE)
+            .map(|chunk| BookEntry::from_bytes(chunk.try_into().expect("chunks are ENTRY_SIZE long")))
+            .collect();
+
+        // books are supposed to be sorted, but lookups break if one isn't
+        Ok(Self::from_entries(entries))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let bytes: Vec<u8> = self.entries.iter()
+            .flat_map(|e| e.to_bytes().to_vec())
+            .collect();
+        fs::write(path, bytes)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The book moves of `board` with their weights. Entries that don't describe a legal move are skipped.
+    pub fn moves(&self, board: &impl Board) -> Vec<(Move, u16)> {
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|e| e.key < key);
+
+        self.entries[start..].iter()
+            .take_while(|e| e.key == key)
+            .filter_map(|e| Some((e.decode_move(board)?, e.weight)))
+            .collect()
+    }
+}
+
+/// How a move is picked when the book has several for a position.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BookSelection {
+    /// A random move, with a chance proportional to its weight
+    WeightedRandom,
+    /// Always the move with the highest weight
+    Best,
+}
+
+#[derive(Clone, Debug)]
+pub struct BookConfig {
+    pub book: Arc<OpeningBook>,
+    pub selection: BookSelection,
+    /// Stop using the book after this many plies of the game
+    pub max_depth: Option<u64>,
+}
+
+impl BookConfig {
+    pub fn new(book: OpeningBook) -> Self {
+        Self {
+            book: Arc::new(book),
+            selection: BookSelection::WeightedRandom,
+            max_depth: None,
+        }
+    }
+
+    /// The book move to play in `board`, or `None` when the search has to find one.
+    pub fn probe(&self, board: &impl Board, stats: &mut StatsEntry) -> Option<Move> {
+        if self.max_depth.is_some_and(|max| board.move_count() >= max) {
+            return None;
+        }
+
+        let moves: Vec<(Move, u16)> = self.book.moves(board)
+            .into_iter()
+            .filter(|&(_, weight)| weight > 0)
+            .collect();
+
+        let m = match self.selection {
+            BookSelection::Best => moves.iter().max_by_key(|&&(_, weight)| weight)?.0,
+            BookSelection::WeightedRandom => {
+                let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+                if total == 0 {
+                    return None;
+                }
+
+                let mut pick = thread_rng().gen_range(0..total);
+                moves.iter()
+                    .find(|&&(_, weight)| {
+                        if pick < weight as u32 {
+                            true
+                        } else {
+                            pick -= weight as u32;
+                            false
+                        }
+                    })?
+                    .0
+            }
+        };
+
+        stats.custom_int_entry("book_moves", moves.len() as i64);
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::BasicBoard;
+    use crate::game_engine::board::polyglot::polyglot_key;
+    use crate::game_engine::chess_move::Extra::DoublePawn;
+    use crate::solver::book::{OpeningBook, BookConfig, BookSelection};
+    use crate::stats::StatsEntry;
+    use std::time::Duration;
+
+    fn entry(key: u64, mv: u16, weight: u16) -> Vec<u8> {
+        let mut bytes = key.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&mv.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_probe() {
+        let b = BasicBoard::default_board(Duration::from_secs(1));
+        let key = polyglot_key(&b);
+
+        // e2e4 and d2d4, and an illegal e1e2 that must be skipped
+        let mut bytes = entry(key, (12 << 6) | 28, 5);
+        bytes.extend(entry(key, (11 << 6) | 27, 10));
+        bytes.extend(entry(key, (4 << 6) | 12, 100));
+        bytes.extend(entry(key + 1, (12 << 6) | 28, 1));
+
+        let book = OpeningBook::from_bytes(&bytes).unwrap();
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.moves(&b).len(), 2);
+
+        let mut config = BookConfig::new(book);
+        config.selection = BookSelection::Best;
+        let mut stats = StatsEntry::new(None);
+        assert_eq!(config.probe(&b, &mut stats), Some(((3, 6), (3, 4), DoublePawn).into()));
+
+        config.max_depth = Some(0);
+        assert_eq!(config.probe(&b, &mut stats), None);
+
+        assert!(OpeningBook::from_bytes(&bytes[1..]).is_err());
+    }
+}
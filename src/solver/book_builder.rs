@@ -0,0 +1,131 @@
+use crate::game_engine::board::{BasicBoard, Board};
+use crate::game_engine::board::polyglot::polyglot_key;
+use crate::game_engine::color::Color::{White, Black};
+use crate::game_engine::pgn::{PgnGame, GameResult, parse_pgn};
+use crate::solver::book::{BookEntry, OpeningBook};
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct BookBuilderConfig {
+    /// Only the first this many plies of every game are added
+    pub max_plies: u64,
+    /// Moves played in fewer games than this are left out
+    pub min_games: u64,
+    /// The points a move gets for every game its side won, drew or lost.
+    /// The weight of a move in the book is the sum of its points, so it depends
+    /// on how often it was played as well as on how well it did.
+    pub win_points: u64,
+    pub draw_points: u64,
+    pub loss_points: u64,
+}
+
+impl Default for BookBuilderConfig {
+    fn default() -> Self {
+        Self {
+            max_plies: 20,
+            min_games: 3,
+            win_points: 2,
+            draw_points: 1,
+            loss_points: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct MoveStats {
+    games: u64,
+    points: u64,
+}
+
+/// Builds an opening book from games, like PGN collections or games recorded by the `Runner`.
+pub struct BookBuilder {
+    config: BookBuilderConfig,
+    /// The statistics of every move, by the polyglot key of its position and the encoded move
+    moves: HashMap<(u64, u16), MoveStats>,
+    games: u64,
+    skipped: u64,
+}
+
+impl BookBuilder {
+    pub fn new() -> Self {
+        Self::with_config(BookBuilderConfig::default())
+    }
+
+    pub fn with_config(config: BookBuilderConfig) -> Self {
+        Self {
+            config,
+            moves: HashMap::new(),
+            games: 0,
+            skipped: 0,
+        }
+    }
+
+    /// The number of games that were added
+    pub fn games(&self) -> u64 {
+        self.games
+    }
+
+    /// The number of games that were skipped, because they don't start from the initial position
+    /// or their first move is illegal
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Adds the opening of `game`. Games that have an illegal move are added up to that move.
+    pub fn add_game(&mut self, game: &PgnGame) {
+        let start = BasicBoard::default_board(Duration::from_secs(0));
+        let positions = if game.has_setup() { Vec::new() } else { game.replay(start.clone()) };
+
+        if positions.is_empty() {
+            self.skipped += 1;
+            return;
+        }
+        self.games += 1;
+
+        let mut board = start;
+        for (m, next) in positions.into_iter().take(self.config.max_plies as usize) {
+            let points = match (game.result, board.current_player()) {
+                (GameResult::WhiteWins, White) | (GameResult::BlackWins, Black) => self.config.win_points,
+                (GameResult::WhiteWins, _) | (GameResult::BlackWins, _) => self.config.loss_points,
+                // unfinished games still show which moves are popular
+                (GameResult::Draw, _) | (GameResult::Unknown, _) => self.config.draw_points,
+            };
+
+            let stats = self.moves.entry((polyglot_key(&board), BookEntry::encode_move(m))).or_default();
+            stats.games += 1;
+            stats.points += points;
+
+            board = next;
+        }
+    }
+
+    /// Adds all games of a PGN file.
+    pub fn add_pgn(&mut self, text: &str) {
+        for game in parse_pgn(text) {
+            self.add_game(&game);
+        }
+    }
+
+    /// The book of all moves that were played often enough and have any points.
+    pub fn build(&self) -> OpeningBook {
+        let moves: Vec<(&(u64, u16), &MoveStats)> = self.moves.iter()
+            .filter(|(_, stats)| stats.games >= self.config.min_games && stats.points > 0)
+            .collect();
+
+        // weights have to fit in 16 bits
+        let max = moves.iter().map(|(_, stats)| stats.points).max().unwrap_or(0);
+        let scale = max.div_ceil(u16::MAX as u64).max(1);
+
+        let entries = moves.into_iter()
+            .map(|(&(key, mv), stats)| BookEntry {
+                key,
+                mv,
+                weight: (stats.points / scale).max(1) as u16,
+                learn: 0,
+            })
+            .collect();
+
+        OpeningBook::from_entries(entries)
+    }
+}
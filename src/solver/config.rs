@@ -0,0 +1,142 @@
+use crate::solver::time_manager::TimeControl;
+use crate::solver::skill::Skill;
+use crate::solver::contempt::Contempt;
+use crate::solver::book::BookConfig;
+
+/// Tunable parameters for the searching solvers.
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    /// Size of the transposition table in megabytes
+    pub transposition_table_size: u64,
+    /// The number of threads searching at the same time (lazy SMP). Helper threads search the
+    /// same position with slightly different depths and move orders, and only help the main
+    /// thread by filling the shared transposition table.
+    pub threads: usize,
+    /// Search every position again from an empty transposition table, once with one thread and once
+    /// with `threads`, and report how much faster the threads reach the same depth as the speedup.
+    /// This takes about three times as long per move, so it's only for measuring.
+    pub measure_speedup: bool,
+    /// Keep searching while the opponent is thinking, on the reply we expect them to play.
+    pub ponder: bool,
+    /// The number of best root moves that are reported with their own principal variation.
+    pub multi_pv: usize,
+    /// Play weaker than the engine can. `None` plays at full strength.
+    pub skill: Option<Skill>,
+    /// How much the engine dislikes draws.
+    pub contempt: Contempt,
+    /// Play moves from this opening book as long as it has them, instead of searching.
+    pub book: Option<BookConfig>,
+    /// Probe the installed endgame tablebases: at the root to play their best move,
+    /// and in the search to score positions that are in them without searching further.
+    pub tablebases: bool,
+    /// Only probe the installed Syzygy tables in the search for positions with at most this many pieces.
+    pub syzygy_probe_limit: usize,
+    pub time_control: TimeControl,
+
+    /// Try to prune nodes by letting the opponent move twice (a null move).
+    pub null_move_pruning: bool,
+    /// Null moves are only tried with at least this much depth left.
+    pub null_move_min_depth: u64,
+    /// The search after a null move is reduced by
+    /// `null_move_reduction + depth / null_move_depth_divisor` plies.
+    pub null_move_reduction: u64,
+    pub null_move_depth_divisor: u64,
+    /// Confirm every null move cutoff with a reduced search of the actual position.
+    /// This costs some nodes, but protects against zugzwang positions
+    /// where passing would be better than any legal move.
+    pub null_move_verification: bool,
+
+    /// Search quiet moves that are ordered late with reduced depth first.
+    pub late_move_reductions: bool,
+    /// Late move reductions are only done with at least this much depth left.
+    pub lmr_min_depth: u64,
+    /// The number of moves at every node that are always searched to full depth.
+    pub lmr_full_depth_moves: usize,
+    /// Plies to reduce a late move by. Moves after twice `lmr_full_depth_moves`
+    /// are reduced by one more.
+    pub lmr_reduction: u64,
+
+    /// Skip quiet moves near the leaves when the static evaluation plus a margin can't reach the window.
+    pub futility_pruning: bool,
+    /// The futility margin with 1, 2, ... plies left. No futility pruning is done beyond the last one.
+    pub futility_margins: Vec<f64>,
+
+    /// Search positions in check one ply deeper.
+    pub check_extensions: bool,
+    /// Search positions with only one legal move one ply deeper.
+    pub single_reply_extensions: bool,
+    /// Search pawn pushes to the 7th rank one ply deeper.
+    pub pawn_push_extensions: bool,
+    /// The most plies a single line can be extended by in total.
+    pub max_extensions: u64,
+
+    /// Search all moves in the quiescence search when in check, instead of only captures.
+    pub qsearch_check_evasions: bool,
+    /// Also search quiet moves that give check at the first ply of the quiescence search.
+    pub qsearch_checks: bool,
+    /// Skip captures in the quiescence search that can't reach the window, even when the captured piece is free.
+    pub delta_pruning: bool,
+    /// Extra margin on top of the captured piece for delta pruning, for the positional gain of a capture.
+    pub delta_margin: f64,
+    /// Skip captures in the quiescence search that lose material according to static exchange evaluation.
+    pub see_pruning: bool,
+
+    /// Look for a forced mate with a proof-number search before the normal search.
+    pub mate_search: bool,
+    /// The positions the mate search may expand.
+    pub mate_search_nodes: u64,
+
+    /// Drop into a quiescence search near the leaves when the static evaluation is far below the window.
+    pub razoring: bool,
+    /// The razoring margin with 1, 2, ... plies left. No razoring is done beyond the last one.
+    pub razoring_margins: Vec<f64>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            transposition_table_size: 16,
+            threads: 1,
+            measure_speedup: false,
+            ponder: false,
+            multi_pv: 1,
+            skill: None,
+            contempt: Contempt::default(),
+            book: None,
+            tablebases: true,
+            syzygy_probe_limit: 7,
+            time_control: TimeControl::default(),
+
+            null_move_pruning: true,
+            null_move_min_depth: 3,
+            null_move_reduction: 2,
+            null_move_depth_divisor: 4,
+            null_move_verification: false,
+
+            late_move_reductions: true,
+            lmr_min_depth: 3,
+            lmr_full_depth_moves: 4,
+            lmr_reduction: 1,
+
+            futility_pruning: true,
+            futility_margins: vec![200.0, 500.0],
+
+            check_extensions: true,
+            single_reply_extensions: true,
+            pawn_push_extensions: true,
+            max_extensions: 4,
+
+            qsearch_check_evasions: true,
+            qsearch_checks: false,
+            delta_pruning: true,
+            delta_margin: 200.0,
+            see_pruning: true,
+
+            mate_search: false,
+            mate_search_nodes: 10_000,
+
+            razoring: true,
+            razoring_margins: vec![300.0, 600.0],
+        }
+    }
+}
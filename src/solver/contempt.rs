@@ -0,0 +1,205 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::color::Color;
+use crate::game_engine::color::Color::White;
+
+/// Opponent contempt grows by this many centipawns for every 100 Elo the opponent is weaker.
+const CONTEMPT_PER_100_ELO: f64 = 10.0;
+/// Opponent contempt never gets larger than this, in centipawns.
+const MAX_OPPONENT_CONTEMPT: f64 = 100.0;
+/// Material contempt grows by this many centipawns for every centipawn the engine is ahead.
+const MATERIAL_CONTEMPT_FACTOR: f64 = 0.1;
+/// Material contempt never gets larger than this, in centipawns.
+const MAX_MATERIAL_CONTEMPT: f64 = 100.0;
+
+/// How much the engine dislikes draws. A positive contempt makes draws (stalemate, repetition
+/// and the fifty-move rule) score below 0 for the engine, so it avoids them when it can.
+/// Negative contempt makes it seek draws instead.
+#[derive(Clone, Debug)]
+pub enum Contempt {
+    /// Always the same contempt, in centipawns
+    Fixed(f64),
+    /// `base` plus `factor` times the material the engine is ahead at the root, at most `max` either way.
+    /// The engine avoids draws more when it's winning, and accepts them when it's losing.
+    Material { base: f64, factor: f64, max: f64 },
+    /// From how much stronger the engine is than its opponent, in Elo. Negative when the opponent is stronger.
+    Opponent { elo_difference: i32 },
+}
+
+/// The score of a finished game, from white's perspective: infinite for a win and `draw_score` for a draw
+/// (`EmptyColor`).
+pub fn terminal_score(winner: Color, draw_score: f64) -> f64 {
+    match winner {
+        Color::White => f64::INFINITY,
+        Color::Black => f64::NEG_INFINITY,
+        Color::EmptyColor => draw_score,
+    }
+}
+
+impl Default for Contempt {
+    fn default() -> Self {
+        Contempt::Fixed(0.0)
+    }
+}
+
+/// Which kind of `Contempt` the front ends set up.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ContemptMode {
+    Fixed,
+    Material,
+    Opponent,
+}
+
+impl ContemptMode {
+    pub const ALL: [ContemptMode; 3] = [ContemptMode::Fixed, ContemptMode::Material, ContemptMode::Opponent];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContemptMode::Fixed => "Fixed",
+            ContemptMode::Material => "Material",
+            ContemptMode::Opponent => "Opponent",
+        }
+    }
+
+    /// The mode with this name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Contempt {
+    /// The contempt of `mode`: `centipawns` when it's fixed, or the base of the material contempt.
+    /// The opponent contempt only depends on `elo_difference`, how much stronger the engine is.
+    pub fn new(mode: ContemptMode, centipawns: f64, elo_difference: i32) -> Self {
+        match mode {
+            ContemptMode::Fixed => Contempt::Fixed(centipawns),
+            ContemptMode::Material => Contempt::Material {
+                base: centipawns,
+                factor: MATERIAL_CONTEMPT_FACTOR,
+                max: MAX_MATERIAL_CONTEMPT,
+            },
+            ContemptMode::Opponent => Contempt::Opponent { elo_difference },
+        }
+    }
+
+    /// The contempt in centipawns, for `engine` searching `root`.
+    pub fn value(&self, root: &impl Board, engine: Color) -> f64 {
+        match *self {
+            Contempt::Fixed(contempt) => contempt,
+            Contempt::Material { base, factor, max } => {
+                let material = root.get_material_score() as f64;
+                let advantage = if engine == White { material } else { -material };
+                (base + factor * advantage).clamp(-max, max)
+            }
+            Contempt::Opponent { elo_difference } => {
+                (elo_difference as f64 / 100.0 * CONTEMPT_PER_100_ELO).clamp(-MAX_OPPONENT_CONTEMPT, MAX_OPPONENT_CONTEMPT)
+            }
+        }
+    }
+
+    /// The score of a draw for `engine` searching `root`, from white's perspective like all scores.
+    pub fn draw_score(&self, root: &impl Board, engine: Color) -> f64 {
+        let contempt = self.value(root, engine);
+        if engine == White { -contempt } else { contempt }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::display::DisplayableBoard;
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::chess_move::Move;
+    use crate::game_engine::color::Color;
+    use crate::game_engine::fen::parse_fen;
+    use crate::game_engine::lan::parse_lan;
+    use crate::solver::Solver;
+    use crate::solver::alpha_beta::AlphaBeta;
+    use crate::solver::alpha_beta_transp::AlphaBetaTransp;
+    use crate::solver::config::SearchConfig;
+    use crate::solver::contempt::Contempt;
+    use crate::solver::iterative_deepening::IterativeDeepening;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::mcts::{Mcts, MctsConfig};
+    use crate::solver::minimax::Minimax;
+    use crate::solver::proof_number::{ProofNumberConfig, ProofNumberSearch};
+    use crate::solver::quiescence::Quiescence;
+    use crate::solver::time_manager::TimeControl;
+    use crate::stats::StatsEntry;
+    use std::time::Duration;
+
+    fn board(fen: &str) -> DisplayableBoard<BasicBoard> {
+        DisplayableBoard::new(parse_fen(fen, Duration::from_secs(600)).unwrap())
+    }
+
+    #[test]
+    fn test_modes() {
+        // white is a rook up
+        let root = board("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+
+        let material = Contempt::Material { base: 10.0, factor: 0.1, max: 50.0 };
+        assert_eq!(material.value(&root, Color::White), 50.0);
+        assert_eq!(material.value(&root, Color::Black), -40.0);
+        assert_eq!(material.draw_score(&root, Color::White), -50.0);
+        assert_eq!(material.draw_score(&root, Color::Black), -40.0);
+
+        let weaker = Contempt::Opponent { elo_difference: 300 };
+        assert_eq!(weaker.value(&root, Color::White), 30.0);
+        assert_eq!(weaker.draw_score(&root, Color::Black), 30.0);
+        let stronger = Contempt::Opponent { elo_difference: -2000 };
+        assert_eq!(stronger.value(&root, Color::White), -100.0);
+    }
+
+    #[test]
+    fn test_fifty_moves() {
+        let board = board("4k3/8/8/8/8/8/8/R3K3 w - - 99 80");
+        assert_eq!(board.is_terminal(), None);
+
+        // the hundredth move without a capture or pawn move draws
+        let drawn = board.transition(parse_lan(&board, "a1a2").unwrap());
+        assert_eq!(drawn.halfmove_clock(), 100);
+        assert_eq!(drawn.is_terminal(), Some(Color::EmptyColor));
+    }
+
+    /// The moves of all searching solvers in `board`, with `contempt`.
+    fn moves(board: &DisplayableBoard<BasicBoard>, contempt: Contempt) -> Vec<Move> {
+        // deep enough to see the draw, but not that g7+ Kg8 Kg6 is stalemate too
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..SearchLimits::default()
+        };
+        let config = SearchConfig {
+            contempt: contempt.clone(),
+            ..SearchConfig::default()
+        };
+        let mcts = MctsConfig {
+            contempt: contempt.clone(),
+            playouts: Some(500),
+            seed: Some(1),
+            tablebases: false,
+            ..MctsConfig::default()
+        };
+
+        let mut stats = StatsEntry::new(None);
+        vec![
+            IterativeDeepening::with_config(TimeControl::default(), contempt.clone()).make_move_impl(board.clone(), &limits, &mut stats),
+            AlphaBeta::with_contempt(1, contempt.clone()).make_move_impl(board.clone(), &limits, &mut stats),
+            AlphaBetaTransp::with_contempt(1, 1, contempt.clone()).make_move_impl(board.clone(), &limits, &mut stats),
+            Quiescence::with_config(1, config).make_move_impl(board.clone(), &limits, &mut stats),
+            Mcts::with_config(mcts).make_move_impl(board.clone(), &limits, &mut stats),
+            Minimax::with_contempt(1, contempt.clone()).make_move_impl(board.clone(), &limits, &mut stats),
+            // there is no mate in one, so it plays the move that looks best
+            ProofNumberSearch::with_config(ProofNumberConfig { max_moves: Some(1), contempt: contempt.clone(), ..ProofNumberConfig::default() })
+                .make_move_impl(board.clone(), &limits, &mut stats),
+        ].into_iter().map(|played| played.unwrap().last_move().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_draw_score() {
+        // Kf7 stalemates, every other move keeps the extra pawn
+        let board = board("7k/8/5KP1/8/8/8/8/8 w - - 0 1");
+        let stalemate = parse_lan(&board, "f6f7").unwrap();
+
+        assert!(!moves(&board, Contempt::default()).contains(&stalemate));
+        // a draw is better than a pawn to an engine that wants one badly enough
+        assert_eq!(moves(&board, Contempt::Fixed(-500.0)), vec![stalemate; 7]);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::game_engine::chess_move::Move;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::fmt;
+
+/// Can be set from any thread to abort a running search. The search then returns
+/// the best move of the last iteration it completed.
+#[derive(Clone, Debug, Default)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress of a search, reported after every completed iteration.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: u64,
+    /// From white's perspective, like all scores
+    pub score: f64,
+    /// Positions searched by the main search thread
+    pub nodes: u64,
+    /// Nodes per second
+    pub nps: u64,
+    pub time: Duration,
+    /// The principal variation, starting with the best move
+    pub pv: Vec<Move>,
+    /// The rank of the root move this line starts with, 1 for the best move
+    pub multipv: usize,
+}
+
+impl SearchInfo {
+    pub fn new(depth: u64, score: f64, nodes: u64, time: Duration, pv: Vec<Move>) -> Self {
+        let nps = if time.as_nanos() > 0 {
+            (nodes as u128 * 1_000_000_000 / time.as_nanos()) as u64
+        } else {
+            0
+        };
+
+        Self {
+            depth,
+            score,
+            nodes,
+            nps,
+            time,
+            pv,
+            multipv: 1,
+        }
+    }
+
+    pub fn with_multipv(self, multipv: usize) -> Self {
+        Self { multipv, ..self }
+    }
+}
+
+/// One of the best root moves found by an analysis.
+#[derive(Clone, Debug)]
+pub struct PvLine {
+    /// From white's perspective, like all scores
+    pub score: f64,
+    /// The principal variation, starting with the root move
+    pub pv: Vec<Move>,
+}
+
+/// The best root moves of the last completed iteration, best first.
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    pub depth: u64,
+    pub lines: Vec<PvLine>,
+}
+
+/// Called by iterative solvers with the result of every completed iteration.
+/// It's called from the searching thread, so it should return quickly.
+#[derive(Clone)]
+pub struct InfoCallback(Arc<dyn Fn(&SearchInfo) + Send + Sync>);
+
+impl InfoCallback {
+    pub fn new(f: impl Fn(&SearchInfo) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn call(&self, info: &SearchInfo) {
+        (self.0)(info)
+    }
+}
+
+impl fmt::Debug for InfoCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InfoCallback")
+    }
+}
@@ -0,0 +1,147 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::{Extra, Location, Move};
+use crate::game_engine::piece::Piece;
+use crate::game_engine::king_check::king_check;
+use crate::solver::config::SearchConfig;
+use crate::stats::StatsEntry;
+
+/// Whether `m` moves a pawn to the rank before it promotes.
+pub fn is_pawn_push_to_seventh(board: &impl Board, m: Move) -> bool {
+    match board.piece_at(m.from) {
+        Piece::WhitePawn => m.to.y == 1,
+        Piece::BlackPawn => m.to.y == 6,
+        _ => false,
+    }
+}
+
+/// Whether the player to move in `child` is in check, so the move that led to it gave check.
+pub fn gives_check(child: &impl Board) -> bool {
+    king_check(child, child.current_player())
+}
+
+/// Whether `m` could give check to the king on `king`, without making the move. Pieces in the way
+/// aren't looked at, so this is only ever wrong by saying yes. Used to skip creating the boards of
+/// moves that are pruned unless they give check.
+pub fn may_give_check(board: &impl Board, m: Move, king: Location) -> bool {
+    if m.extra == Extra::KingCastle || m.extra == Extra::QueenCastle || m.extra == Extra::EnPassantCaptures || m.extra.is_promotion() {
+        return true;
+    }
+
+    let on_line = |from: Location, diagonal: bool, straight: bool| {
+        let (dx, dy) = ((king.x - from.x).abs(), (king.y - from.y).abs());
+        (diagonal && dx == dy) || (straight && (dx == 0 || dy == 0))
+    };
+
+    // a piece that moves away from a line to the king can uncover a check
+    if on_line(m.from, true, true) {
+        return true;
+    }
+
+    let piece = board.piece_at(m.from);
+    let (dx, dy) = (king.x - m.to.x, king.y - m.to.y);
+    match piece {
+        Piece::WhitePawn => dy == -1 && dx.abs() == 1,
+        Piece::BlackPawn => dy == 1 && dx.abs() == 1,
+        _ if piece.is_knight() => (dx.abs() == 1 && dy.abs() == 2) || (dx.abs() == 2 && dy.abs() == 1),
+        _ => on_line(m.to, piece.is_bishop() || piece.is_queen(), piece.is_rook() || piece.is_queen()),
+    }
+}
+
+/// Searches forcing positions deeper, so the search doesn't stop in the middle of a forcing sequence.
+/// Keeps track of the extensions on the line that is currently searched, so they stay within
+/// `max_extensions` and the search still finishes.
+pub struct Extensions {
+    used: u64,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self {
+            used: 0,
+        }
+    }
+
+    fn extend(&mut self, config: &SearchConfig, stats: &mut StatsEntry, counter: &'static str) -> u64 {
+        if self.used >= config.max_extensions {
+            return 0;
+        }
+
+        stats.custom_int_entry_add(counter);
+        self.used += 1;
+        1
+    }
+
+    /// The plies to extend a node with, when it's in check or has only a single legal move.
+    /// Must be given back with `release` when the node is done.
+    pub fn node(&mut self, config: &SearchConfig, in_check: bool, legal_moves: usize, stats: &mut StatsEntry) -> u64 {
+        if in_check && config.check_extensions {
+            self.extend(config, stats, "check_extensions")
+        } else if legal_moves == 1 && config.single_reply_extensions {
+            self.extend(config, stats, "single_reply_extensions")
+        } else {
+            0
+        }
+    }
+
+    /// The plies to extend the search of move `m` in `board` with.
+    /// Must be given back with `release` when the move is searched.
+    pub fn pawn_push(&mut self, config: &SearchConfig, board: &impl Board, m: Move, stats: &mut StatsEntry) -> u64 {
+        if config.pawn_push_extensions && is_pawn_push_to_seventh(board, m) {
+            self.extend(config, stats, "pawn_push_extensions")
+        } else {
+            0
+        }
+    }
+
+    pub fn release(&mut self, plies: u64) {
+        self.used -= plies;
+    }
+}
+
+/// The moves to search in a quiescence search: every move when in check,
+/// otherwise only the captures, and with `checks` also the quiet moves that give check.
+pub fn quiescence_moves(board: &impl Board, in_check: bool, checks: bool) -> Vec<Move> {
+    if in_check {
+        return board.all_moves();
+    }
+
+    board.all_moves()
+        .into_iter()
+        .filter(|&m| m.extra.is_capturing() || (checks && gives_check(&board.transition(m))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::king_check::king_location;
+    use crate::solver::extensions::{gives_check, may_give_check};
+    use rand::rngs::StdRng;
+    use rand::seq::IteratorRandom;
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    #[test]
+    fn test_may_give_check() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut checks = 0;
+
+        for _ in 0..20 {
+            let mut board = BasicBoard::default_board(Duration::from_secs(60));
+            while board.is_terminal().is_none() {
+                let king = king_location(&board, board.current_player().other()).unwrap();
+                for m in board.all_moves() {
+                    if gives_check(&board.transition(m)) {
+                        checks += 1;
+                        assert!(may_give_check(&board, m, king), "{:?} gives check", m);
+                    }
+                }
+
+                let m = board.all_moves().into_iter().choose(&mut rng).unwrap();
+                board = board.transition(m);
+            }
+        }
+
+        assert!(checks > 0);
+    }
+}
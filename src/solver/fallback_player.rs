@@ -5,6 +5,7 @@ use crate::game_engine::chess_move::{Extra, Move};
 use crate::game_engine::board::display::DisplayableBoard;
 use std::io::Write;
 use crate::game_engine::color::Color;
+use crate::solver::limits::SearchLimits;
 
 pub struct FallbackPlayer {
 
@@ -21,7 +22,7 @@ impl FallbackPlayer {
 impl Solver for FallbackPlayer {
     const PRINT_OWN_BOARD: bool = true;
 
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, _limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mv = make_move_input(board.clone(), stats)?;
 
         Some(board.transition(mv))
@@ -0,0 +1,107 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::{Move, Location};
+
+/// Quiet moves that caused a cutoff at the same ply in a sibling node are likely to do it again.
+const KILLER_SCORE: [i64; 2] = [2_000_000, 1_900_000];
+/// The move that refuted the previous move last time is a likely refutation again.
+const COUNTER_MOVE_SCORE: i64 = 1_800_000;
+/// History scores are halved once one of them grows past this,
+/// so they always stay below the killer and counter move scores.
+const MAX_HISTORY: i64 = 1_000_000;
+
+fn square(l: Location) -> usize {
+    l.y as usize * 8 + l.x as usize
+}
+
+/// Remembers which quiet moves caused cutoffs during the search, so they can be tried first.
+/// Captures are already ordered well by material, but quiet moves all look the same without this.
+pub struct MoveHistory {
+    /// The two most recent quiet moves that caused a cutoff at every ply
+    killers: Vec<[Option<Move>; 2]>,
+    /// Butterfly history, indexed by moving piece and destination square
+    history: [[i64; 64]; 12],
+    /// The quiet move that refuted a move, indexed by the piece that moved and its destination square
+    counter_moves: [[Option<Move>; 64]; 12],
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        Self {
+            killers: Vec::new(),
+            history: [[0; 64]; 12],
+            counter_moves: [[None; 64]; 12],
+        }
+    }
+
+    /// Scores a quiet move for ordering, higher is better. `previous` is the move
+    /// that led to `board`, if any.
+    pub fn quiet_score(&self, board: &impl Board, m: Move, ply: usize, previous: Option<Move>) -> i64 {
+        if let Some(killers) = self.killers.get(ply) {
+            for (killer, score) in killers.iter().zip(KILLER_SCORE.iter()) {
+                if *killer == Some(m) {
+                    return *score;
+                }
+            }
+        }
+
+        if let Some(previous) = previous {
+            let previous_piece = board.piece_at(previous.to);
+            if !previous_piece.is_empty()
+                && self.counter_moves[previous_piece.to_number()][square(previous.to)] == Some(m) {
+                return COUNTER_MOVE_SCORE;
+            }
+        }
+
+        let piece = board.piece_at(m.from);
+        if piece.is_empty() {
+            return 0;
+        }
+        self.history[piece.to_number()][square(m.to)]
+    }
+
+    /// Records that the quiet move `m` caused a cutoff in `board` with `depth` plies left.
+    pub fn store_cutoff(&mut self, board: &impl Board, m: Move, ply: usize, depth: u64, previous: Option<Move>) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+
+        if let Some(previous) = previous {
+            let previous_piece = board.piece_at(previous.to);
+            if !previous_piece.is_empty() {
+                self.counter_moves[previous_piece.to_number()][square(previous.to)] = Some(m);
+            }
+        }
+
+        let piece = board.piece_at(m.from);
+        if piece.is_empty() {
+            return;
+        }
+        let entry = &mut self.history[piece.to_number()][square(m.to)];
+        *entry += (depth * depth) as i64;
+
+        if *entry > MAX_HISTORY {
+            self.halve_history();
+        }
+    }
+
+    /// Makes older information count less. Should be called between searches:
+    /// killers are forgotten since the plies don't line up anymore,
+    /// and the history scores are halved.
+    pub fn age(&mut self) {
+        self.killers.clear();
+        self.halve_history();
+    }
+
+    fn halve_history(&mut self) {
+        for piece in self.history.iter_mut() {
+            for entry in piece.iter_mut() {
+                *entry /= 2;
+            }
+        }
+    }
+}
@@ -2,39 +2,265 @@ use crate::game_engine::board::Board;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use crate::game_engine::color::Color::{White, Black};
-use crate::solver::Solver;
-use crate::solver::move_order::order_moves;
+use crate::solver::{tablebase_move, Solver};
+use crate::solver::book::BookConfig;
+use crate::solver::move_order::{order_moves, order_moves_with_history};
+use crate::solver::history::MoveHistory;
+use crate::transposition_table::{TranspositionTable, Entry, Bound};
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
-use std::time::{SystemTime, Duration};
+use std::time::{SystemTime, Duration, Instant};
 use std::ops::Add;
 use std::convert::TryInto;
 use crate::game_engine::chess_move::Move;
+use crate::game_engine::color::Color;
+use crate::game_engine::king_check::{king_check, king_location};
+use crate::solver::config::SearchConfig;
+use crate::solver::time_manager::{TimeControl, TimeManager};
+use crate::solver::limits::SearchLimits;
+use crate::solver::control::{StopToken, SearchInfo, Analysis, PvLine};
+use crate::solver::qsearch_pruning::prune_capture;
+use crate::solver::proof_number::{ProofNumberSearch, MateResult};
+use crate::solver::skill::Skill;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crate::solver::extensions::{Extensions, is_pawn_push_to_seventh, gives_check, may_give_check, quiescence_moves};
+use crate::solver::move_order::MoveRes;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::mem;
+use crate::tablebase::syzygy;
 
 const TIME_DECAY: f64 = 0.99999;
 
+/// Width of the window used when we only need to know whether a score is above or below a bound.
+const NULL_WINDOW: f64 = 1e-6;
+
+/// The best score, the moves with that score and the last completed depth of a search.
+type SearchResult = (f64, Vec<Move>, u64);
+
+/// A search running in the background on the position we expect after the opponent's move.
+struct Ponder {
+    expected: Move,
+    /// The hash of the position after the expected move
+    hash: u64,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<(IDQuiescence, SearchResult, StatsEntry)>,
+}
+
 pub struct IDQuiescence {
+    config: SearchConfig,
+    history: MoveHistory,
+    extensions: Extensions,
+    /// Shared with the helper threads
+    transposition_table: Arc<TranspositionTable>,
+    /// Set when the main thread is done, to stop the helper threads
+    stop: Arc<AtomicBool>,
+    /// Set by whoever asked for the current move, to abort the search
+    external_stop: Option<StopToken>,
+
+    /// The moves from the root to the node currently being searched (None for null moves)
+    path: Vec<Option<Move>>,
+    /// Hashes of the positions of the game since the last capture or pawn move, up to the current one
+    game_history: Vec<u64>,
+    /// `game_history` followed by the positions from the root to the node currently being searched
+    positions: Vec<u64>,
+    /// The score of a draw in the current search, which depends on the contempt
+    draw_score: f64,
+
+    ponder: Option<Ponder>,
+    /// Whether the last ponder search was aborted because the opponent played something else
+    ponder_missed: bool,
+
+    /// Set when the current iteration ran out of time. The scores it returns are meaningless then.
+    aborted: bool,
+    /// The maximum number of nodes this thread may search for the current move
+    node_limit: Option<u64>,
+
+    /// The best root moves of the last completed iteration of the main thread
+    analysis: Analysis,
+    /// Chooses the moves of a weakened engine
+    skill_rng: StdRng,
 }
 
 impl IDQuiescence {
     pub fn new() -> Self {
+        Self::with_config(SearchConfig::default())
+    }
+
+    pub fn with_config(config: SearchConfig) -> Self {
+        let skill_rng = config.skill.as_ref().map_or_else(StdRng::from_entropy, Skill::rng);
+
         Self {
+            transposition_table: Arc::new(TranspositionTable::new(config.transposition_table_size)),
+            stop: Arc::new(AtomicBool::new(false)),
+            external_stop: None,
+            config,
+            history: MoveHistory::new(),
+            extensions: Extensions::new(),
+            path: Vec::new(),
+            game_history: Vec::new(),
+            positions: Vec::new(),
+            draw_score: 0.0,
+            ponder: None,
+            ponder_missed: false,
+            aborted: false,
+            node_limit: None,
+            analysis: Analysis::default(),
+            skill_rng,
         }
     }
 
-    pub fn mini_max_ab(&mut self, board: &impl Board, depth: u64, mut a: f64, mut b: f64,deadline:SystemTime, stats: &mut StatsEntry) -> f64 {
+    /// A solver for a helper thread, sharing the transposition table and stop signal with this one.
+    fn helper(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            history: MoveHistory::new(),
+            extensions: Extensions::new(),
+            transposition_table: self.transposition_table.clone(),
+            stop: self.stop.clone(),
+            external_stop: self.external_stop.clone(),
+            path: Vec::new(),
+            game_history: self.game_history.clone(),
+            positions: Vec::new(),
+            draw_score: 0.0,
+            ponder: None,
+            ponder_missed: false,
+            aborted: false,
+            node_limit: None,
+            analysis: Analysis::default(),
+            skill_rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Checks whether the search should be aborted. Once it is, it stays aborted until the next iteration.
+    fn out_of_time(&mut self, deadline: SystemTime, stats: &StatsEntry) -> bool {
+        if !self.aborted && (self.stop.load(Ordering::Relaxed)
+            || self.external_stop.as_ref().is_some_and(StopToken::is_stopped)
+            || SystemTime::now() > deadline
+            || self.node_limit.is_some_and(|max| stats.states_seen() >= max)) {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    /// Searches the position reached by playing `mv`, keeping track of the path from the root.
+    fn search_move(&mut self, board: &impl Board, mv: Option<Move>, depth: u64, a: f64, b: f64, null_move_allowed: bool, deadline: SystemTime, stats: &mut StatsEntry) -> f64 {
+        self.path.push(mv);
+        self.positions.push(board.hash());
+        let score = self.mini_max_ab(board, depth, a, b, null_move_allowed, deadline, stats);
+        self.positions.pop();
+        self.path.pop();
+        score
+    }
+
+    /// Whether the last position in `positions`, which is `board`, occurred before in the game or the search.
+    /// Only positions with the same player to move since the last capture or pawn move can be the same.
+    /// A single repetition is scored as a draw: if repeating is best once, it's best again.
+    fn is_repetition(&self, board: &impl Board) -> bool {
+        let hash = board.hash();
+        let current = self.positions.len() - 1;
+        let window = (board.halfmove_clock() as usize).min(current);
+
+        (2..=window).step_by(2).any(|back| self.positions[current - back] == hash)
+    }
+
+    /// Adds a position of the game to the history used to detect repetitions.
+    fn record_position(&mut self, board: &impl Board) {
+        if self.game_history.last() == Some(&board.hash()) {
+            return;
+        }
+
+        // positions before the last capture or pawn move can't come back
+        let keep = board.halfmove_clock() as usize;
+        if self.game_history.len() > keep {
+            self.game_history.drain(..self.game_history.len() - keep);
+        }
+        self.game_history.push(board.hash());
+    }
+
+    /// The game history for a search of the opponent's replies to `board`. After our own move,
+    /// `board` is already the last position in it.
+    fn ponder_history(&self, board: &impl Board) -> Vec<u64> {
+        let mut history = self.game_history.clone();
+        if history.last() != Some(&board.hash()) {
+            history.push(board.hash());
+        }
+        history
+    }
+
+    /// Bookkeeping for a cutoff caused by the `index`th move searched in `board`.
+    fn store_cutoff(&mut self, board: &impl Board, m: Move, index: usize, depth: u64, stats: &mut StatsEntry) {
+        stats.custom_int_entry_add("cutoffs");
+        if index == 0 {
+            stats.custom_int_entry_add("first_move_cutoffs");
+        }
+
+        if is_quiet(m) {
+            let ply = self.path.len();
+            let previous = self.path.last().copied().flatten();
+            self.history.store_cutoff(board, m, ply, depth, previous);
+        }
+    }
+
+    pub fn mini_max_ab(&mut self, board: &impl Board, depth: u64, mut a: f64, mut b: f64, null_move_allowed: bool, deadline:SystemTime, stats: &mut StatsEntry) -> f64 {
         stats.seen_state();
 
-        if SystemTime::now()>deadline{
+        if self.out_of_time(deadline, stats) {
             return 0.0
         }
 
+        if self.is_repetition(board) {
+            stats.custom_int_entry_add("repetitions");
+            return self.draw_score;
+        }
+
+        if self.config.tablebases {
+            if let Some(probe) = board.probe_tablebase() {
+                stats.custom_int_entry_add("tablebase_hits");
+                return probe.score(board.current_player(), self.draw_score);
+            }
+
+            // the results of Syzygy tables count the fifty-move rule from a capture or pawn move
+            if board.halfmove_clock() == 0 {
+                if let Some(wdl) = syzygy::probe_wdl(board, self.config.syzygy_probe_limit) {
+                    stats.custom_int_entry_add("syzygy_hits");
+                    return wdl.score(board.current_player(), self.draw_score);
+                }
+            }
+        }
+
         if depth == 0 {
             // return board.get_material_score();
             stats.custom_int_entry_sub("deep_nodes");
-            return Self::quiescense(self, board, a, b, deadline,stats);
+            return Self::quiescense(self, board, a, b, self.config.qsearch_checks, deadline, stats);
+        }
+
+        let board_hash = board.hash();
+        let mut hash_move = None;
+
+        if let Some(entry) = self.transposition_table.get(board_hash) {
+            hash_move = entry.best_move;
+
+            if entry.depth >= depth {
+                stats.transposition().hit();
+
+                match entry.bound {
+                    Bound::Exact => return entry.value,
+                    Bound::Lower => a = a.max(entry.value),
+                    Bound::Upper => b = b.min(entry.value),
+                }
+
+                if a >= b {
+                    return entry.value;
+                }
+            }
         }
 
+        let (original_a, original_b) = (a, b);
+
         if depth == 0 || board.is_terminal().is_some() {
             let terminal = board.is_terminal();
             if terminal.is_some() {
@@ -43,47 +269,290 @@ impl IDQuiescence {
                 } else if terminal == Some(White) {
                     f64::INFINITY
                 } else {
-                    0.0
+                    self.draw_score
                 };
             }
             return board.heuristic();
         }
 
-        if board.current_player() == White {
+        let in_check = king_check(board, board.current_player());
+        let static_eval = board.heuristic();
+
+        if !in_check {
+            if let Some(value) = self.razoring(board, depth, static_eval, a, b, deadline, stats) {
+                return value;
+            }
+
+            if null_move_allowed && self.config.null_move_pruning && depth >= self.config.null_move_min_depth {
+                if let Some(value) = self.null_move_search(board, depth, static_eval, a, b, deadline, stats) {
+                    return value;
+                }
+            }
+        }
+
+        let moves = board.all_moves();
+        let extension = self.extensions.node(&self.config, in_check, moves.len(), stats);
+        let depth = depth + extension;
+
+        let ply = self.path.len();
+        let previous = self.path.last().copied().flatten();
+
+        // Near the leaves, quiet moves can't make up for a large deficit in the static evaluation
+        let futility_margin = if self.config.futility_pruning && !in_check {
+            self.config.futility_margins.get(depth as usize - 1).copied()
+        } else {
+            None
+        };
+
+        let their_king = futility_margin.and(king_location(board, board.current_player().other()));
+
+        let mut best_move = None;
+        let value = if board.current_player() == White {
             let mut value = f64::NEG_INFINITY;
-            for move_res in order_moves(board.all_moves(), board) {
+            for (index, mv) in order_moves_with_history(moves, board, hash_move, Some(&self.history), ply, previous).without_boards().enumerate() {
+                let quiet = is_quiet(mv) && !is_pawn_push_to_seventh(board, mv);
+
+                // the board after a futile move is only created to see whether it gives check
+                let futile = futility_margin.map(|margin| static_eval + margin).filter(|&bound| quiet && bound <= a);
+                let child = match futile {
+                    Some(bound) => match their_king.filter(|&king| may_give_check(board, mv, king)).map(|_| board.transition(mv)) {
+                        Some(child) if gives_check(&child) => child,
+                        _ => {
+                            stats.custom_int_entry_add("futility_pruned");
+                            value = value.max(bound);
+                            continue;
+                        }
+                    },
+                    None => board.transition(mv),
+                };
+                let move_res = MoveRes { board: child, mv };
 
-                value = value.max(TIME_DECAY*Self::mini_max_ab(self,&move_res.board, depth - 1, a, b, deadline, stats));
+                let push = self.extensions.pawn_push(&self.config, board, move_res.mv, stats);
+                let child_depth = depth - 1 + push;
+
+                let score = match self.late_move_reduction(depth, index, || quiet && !in_check && !gives_check(&move_res.board)) {
+                    Some(reduction) => {
+                        stats.custom_int_entry_add("late_move_reductions");
+                        let score = self.search_move(&move_res.board, Some(move_res.mv), child_depth - reduction, a, a + NULL_WINDOW, true, deadline, stats);
+                        if score > a {
+                            stats.custom_int_entry_add("late_move_re_searches");
+                            self.search_move(&move_res.board, Some(move_res.mv), child_depth, a, b, true, deadline, stats)
+                        } else {
+                            score
+                        }
+                    }
+                    None => self.search_move(&move_res.board, Some(move_res.mv), child_depth, a, b, true, deadline, stats),
+                };
+                self.extensions.release(push);
+
+                if TIME_DECAY*score > value || best_move.is_none() {
+                    value = value.max(TIME_DECAY*score);
+                    best_move = Some(move_res.mv);
+                }
                 a = a.max(value);
                 if a >= b {
+                    self.store_cutoff(board, move_res.mv, index, depth, stats);
                     break;
                 }
             }
-            return value;
+            value
         } else {
             let mut value = f64::INFINITY;
-            for move_res in order_moves(board.all_moves(), board) {
+            for (index, mv) in order_moves_with_history(moves, board, hash_move, Some(&self.history), ply, previous).without_boards().enumerate() {
+                let quiet = is_quiet(mv) && !is_pawn_push_to_seventh(board, mv);
+
+                // the board after a futile move is only created to see whether it gives check
+                let futile = futility_margin.map(|margin| static_eval - margin).filter(|&bound| quiet && bound >= b);
+                let child = match futile {
+                    Some(bound) => match their_king.filter(|&king| may_give_check(board, mv, king)).map(|_| board.transition(mv)) {
+                        Some(child) if gives_check(&child) => child,
+                        _ => {
+                            stats.custom_int_entry_add("futility_pruned");
+                            value = value.min(bound);
+                            continue;
+                        }
+                    },
+                    None => board.transition(mv),
+                };
+                let move_res = MoveRes { board: child, mv };
+
+                let push = self.extensions.pawn_push(&self.config, board, move_res.mv, stats);
+                let child_depth = depth - 1 + push;
+
+                let score = match self.late_move_reduction(depth, index, || quiet && !in_check && !gives_check(&move_res.board)) {
+                    Some(reduction) => {
+                        stats.custom_int_entry_add("late_move_reductions");
+                        let score = self.search_move(&move_res.board, Some(move_res.mv), child_depth - reduction, b - NULL_WINDOW, b, true, deadline, stats);
+                        if score < b {
+                            stats.custom_int_entry_add("late_move_re_searches");
+                            self.search_move(&move_res.board, Some(move_res.mv), child_depth, a, b, true, deadline, stats)
+                        } else {
+                            score
+                        }
+                    }
+                    None => self.search_move(&move_res.board, Some(move_res.mv), child_depth, a, b, true, deadline, stats),
+                };
+                self.extensions.release(push);
 
-                value = value.min(TIME_DECAY*Self::mini_max_ab(self,&move_res.board, depth - 1, a, b,deadline, stats));
+                if TIME_DECAY*score < value || best_move.is_none() {
+                    value = value.min(TIME_DECAY*score);
+                    best_move = Some(move_res.mv);
+                }
                 b = b.min(value);
                 if b <= a {
+                    self.store_cutoff(board, move_res.mv, index, depth, stats);
                     break;
                 }
             }
-            return value;
+            value
+        };
+        self.extensions.release(extension);
+
+        // results of searches that ran out of time are unreliable
+        if !self.out_of_time(deadline, stats) {
+            self.transposition_table.insert(board_hash, Entry {
+                depth,
+                value,
+                bound: Bound::from_window(value, original_a, original_b),
+                best_move,
+            }, stats.transposition());
         }
+
+        value
     }
 
-    pub fn quiescense(&mut self, board: &impl Board, mut a: f64, mut b: f64,deadline:SystemTime, stats: &mut StatsEntry) -> f64 {
+    /// Late move reductions: moves ordered late are unlikely to be any good,
+    /// so quiet ones are first searched with reduced depth and a null window.
+    /// Only when such a move unexpectedly beats the window, it gets a full search.
+    /// Returns the number of plies to reduce the move with, if it should be reduced at all.
+    /// `reducible` tells whether the move may be reduced, and is only called for moves late enough.
+    fn late_move_reduction(&self, depth: u64, index: usize, reducible: impl FnOnce() -> bool) -> Option<u64> {
+        if !self.config.late_move_reductions {
+            return None;
+        }
+        if depth < self.config.lmr_min_depth || index < self.config.lmr_full_depth_moves || !reducible() {
+            return None;
+        }
+
+        let mut reduction = self.config.lmr_reduction;
+        if index >= 2 * self.config.lmr_full_depth_moves {
+            reduction += 1;
+        }
+
+        Some(reduction.min(depth - 1))
+    }
+
+    /// Razoring: when the static evaluation is hopelessly outside the window close to the leaves,
+    /// check with a quiescence search whether some capture can still save us.
+    /// If not, the node is cut off. Returns the value to cut off with, if any.
+    fn razoring(&mut self, board: &impl Board, depth: u64, static_eval: f64, a: f64, b: f64, deadline: SystemTime, stats: &mut StatsEntry) -> Option<f64> {
+        if !self.config.razoring {
+            return None;
+        }
+        let margin = *self.config.razoring_margins.get(depth as usize - 1)?;
+
+        if board.current_player() == White {
+            if a.is_infinite() || static_eval + margin > a {
+                return None;
+            }
+
+            let score = self.quiescense(board, a, a + NULL_WINDOW, self.config.qsearch_checks, deadline, stats);
+            if score > a {
+                return None;
+            }
+
+            stats.custom_int_entry_add("razoring_cutoffs");
+            Some(score)
+        } else {
+            if b.is_infinite() || static_eval - margin < b {
+                return None;
+            }
+
+            let score = self.quiescense(board, b - NULL_WINDOW, b, self.config.qsearch_checks, deadline, stats);
+            if score < b {
+                return None;
+            }
+
+            stats.custom_int_entry_add("razoring_cutoffs");
+            Some(score)
+        }
+    }
+
+    /// Null move pruning: give the opponent a free move, and search the result with reduced depth.
+    /// If we are still outside the window after that, a real move will almost certainly be too,
+    /// and the node can be cut off. Returns the value to cut off with, if any.
+    ///
+    /// This goes wrong in zugzwang, where passing would be better than any legal move.
+    /// That's why no null moves are made in check (checked by the caller), with only pawns left or twice in a row,
+    /// and why cutoffs can optionally be verified by a reduced search of the real position.
+    fn null_move_search(&mut self, board: &impl Board, depth: u64, static_eval: f64, a: f64, b: f64, deadline: SystemTime, stats: &mut StatsEntry) -> Option<f64> {
+        let player = board.current_player();
+        if !has_non_pawn_material(board, player) {
+            return None;
+        }
+
+        let reduction = self.config.null_move_reduction + depth / self.config.null_move_depth_divisor;
+        let null_board = board.null_move();
+
+        if player == White {
+            if static_eval < b {
+                return None;
+            }
+
+            let score = self.search_move(&null_board, None, depth.saturating_sub(reduction + 1), b - NULL_WINDOW, b, false, deadline, stats);
+            if score < b {
+                return None;
+            }
+
+            if self.config.null_move_verification {
+                let verified = self.mini_max_ab(board, depth.saturating_sub(reduction), b - NULL_WINDOW, b, false, deadline, stats);
+                if verified < b {
+                    stats.custom_int_entry_add("null_move_verification_failures");
+                    return None;
+                }
+            }
+
+            stats.custom_int_entry_add("null_move_cutoffs");
+            Some(b)
+        } else {
+            if static_eval > a {
+                return None;
+            }
+
+            let score = self.search_move(&null_board, None, depth.saturating_sub(reduction + 1), a, a + NULL_WINDOW, false, deadline, stats);
+            if score > a {
+                return None;
+            }
+
+            if self.config.null_move_verification {
+                let verified = self.mini_max_ab(board, depth.saturating_sub(reduction), a, a + NULL_WINDOW, false, deadline, stats);
+                if verified > a {
+                    stats.custom_int_entry_add("null_move_verification_failures");
+                    return None;
+                }
+            }
+
+            stats.custom_int_entry_add("null_move_cutoffs");
+            Some(a)
+        }
+    }
+
+    /// Searches captures until the position is quiet. When in check all evasions are searched,
+    /// and with `checks` the quiet moves that give check are searched too.
+    pub fn quiescense(&mut self, board: &impl Board, mut a: f64, mut b: f64, checks: bool, deadline:SystemTime, stats: &mut StatsEntry) -> f64 {
         stats.custom_int_entry_add("deep_nodes");
 
-        if SystemTime::now()>deadline{
+        if self.out_of_time(deadline, stats) {
             return 0.0
         }
 
+        let in_check = self.config.qsearch_check_evasions && king_check(board, board.current_player());
         let cur_score = board.heuristic();
 
-        if board.current_player() == White {
+        // when in check, standing pat isn't an option
+        if in_check {
+            stats.custom_int_entry_add("qsearch_evasions");
+        } else if board.current_player() == White {
             if cur_score >= b {
                 return b;
             }
@@ -107,7 +576,7 @@ impl IDQuiescence {
                 } else if terminal == Some(White) {
                     f64::INFINITY
                 } else {
-                    0.0
+                    self.draw_score
                 };
             }
             return board.heuristic();
@@ -116,12 +585,15 @@ impl IDQuiescence {
 
         if board.current_player() == White {
             let mut value = f64::NEG_INFINITY;
-            let moves: Vec<Move> = board.all_moves().into_iter().filter(|a| a.extra.is_capturing()).collect();
+            let moves = quiescence_moves(board, in_check, checks);
             if moves.len()==0{
                 return cur_score;
             }
             for move_res in order_moves(moves, board) {
-                value = value.max(TIME_DECAY*Self::quiescense(self, &move_res.board, a, b, deadline,stats));
+                if !in_check && prune_capture(&self.config, board, move_res.mv, cur_score, a, stats) {
+                    continue;
+                }
+                value = value.max(TIME_DECAY*Self::quiescense(self, &move_res.board, a, b, false, deadline,stats));
                 a = a.max(value);
                 if a >= b {
                     break;
@@ -130,12 +602,15 @@ impl IDQuiescence {
             return a;
         } else {
             let mut value = f64::INFINITY;
-            let moves: Vec<Move> = board.all_moves().into_iter().filter(|a| a.extra.is_capturing()).collect();
+            let moves = quiescence_moves(board, in_check, checks);
             if moves.len()==0{
                 return cur_score;
             }
             for move_res in order_moves(moves, board) {
-                value = value.min(TIME_DECAY*Self::quiescense(self, &move_res.board, a, b, deadline,stats));
+                if !in_check && prune_capture(&self.config, board, move_res.mv, cur_score, b, stats) {
+                    continue;
+                }
+                value = value.min(TIME_DECAY*Self::quiescense(self, &move_res.board, a, b, false, deadline,stats));
                 b = b.min(value);
                 if b <= a {
                     break;
@@ -144,36 +619,49 @@ impl IDQuiescence {
             return b;
         }
     }
-}
 
-impl Solver for IDQuiescence {
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
-        let mut rng = thread_rng();
+    /// Iterative deepening from the root until the time manager says to stop, or until the search is stopped.
+    /// Thread 0 is the main thread. Helper threads skip the first ply on every other thread
+    /// and start with a different root move, so they don't all search exactly the same tree.
+    /// Returns the best score, the moves with that score and the last completed depth.
+    fn iterate<B: Board>(&mut self, board: &DisplayableBoard<B>, thread: usize, time: &mut TimeManager, limits: &SearchLimits, stats: &mut StatsEntry) -> SearchResult {
+        let deadline = time.deadline();
+        self.node_limit = limits.nodes;
 
-        let remaining_time = if board.current_player() == White{
-            board.get_clock()[0]
-        } else {
-            board.get_clock()[1]
-        };
+        let mut best_moves_backup = Vec::new();
+        let mut best_backup = 0.0;
 
-        let time_budget = remaining_time / 20;
+        let mut iteration = (thread % 2) as u64;
+        let mut completed_depth = 0;
 
-        let deadline = SystemTime::now()
-            .add(time_budget);
+        self.positions = self.game_history.clone();
+        if self.positions.last() != Some(&board.hash()) {
+            self.positions.push(board.hash());
+        }
+        self.draw_score = self.config.contempt.draw_score(board, board.current_player());
 
-        let mut best_moves = Vec::new();
-        let mut best_moves_backup = Vec::new();
-        let mut best_backup = 0.0;
+        self.aborted = false;
+        while time.should_start_iteration()
+            && limits.deepen(iteration)
+            && !self.out_of_time(deadline, stats) {
+            iteration += 1;
+            let search_depth = limits.iteration_depth(iteration);
+            let mut best_moves = Vec::new();
 
-        let mut search_depth = 0;
+            let hash_move = best_moves_backup.first().copied();
+            let mut root_moves: Vec<MoveRes<_>> = order_moves_with_history(board.all_moves(), board, hash_move, Some(&self.history), 0, None).collect();
+            if thread > 0 && !root_moves.is_empty() {
+                let len = root_moves.len();
+                root_moves.rotate_left(thread % len);
+            }
 
-        if board.current_player() == White {
-            while SystemTime::now()<deadline {
-                search_depth += 1;
-                best_moves = Vec::new();
+            let mut root_scores = Vec::new();
+
+            if board.current_player() == White {
                 let mut best = f64::NEG_INFINITY;
-                for move_res in order_moves(board.all_moves(), &board) {
-                    let score = Self::mini_max_ab(self,&move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY,deadline, stats);
+                for move_res in root_moves {
+                    let score = self.search_move(&move_res.board, Some(move_res.mv), search_depth, f64::NEG_INFINITY, f64::INFINITY, true, deadline, stats);
+                    root_scores.push((move_res.mv, score));
                     if score > best {
                         best = score;
                         best_moves = Vec::new();
@@ -182,21 +670,25 @@ impl Solver for IDQuiescence {
                         best_moves.push(move_res.mv);
                     }
                 }
-                if SystemTime::now()<deadline {
+                if !self.aborted {
                     best_backup = best;
                     best_moves_backup = best_moves.clone();
-                }
-            }
-            stats.evaluation(best_backup);
-        }
+                    completed_depth = search_depth;
+                    time.iteration_finished(best_moves_backup.first().copied());
+
+                    if thread == 0 {
+                        self.record_analysis(board, search_depth, root_scores, time, limits, stats);
+                    }
 
-        if board.current_player() == Black {
-            while SystemTime::now()<deadline {
-                search_depth += 1;
-                best_moves = Vec::new();
+                    if limits.mate_found(best, true) {
+                        break;
+                    }
+                }
+            } else {
                 let mut best = f64::INFINITY;
-                for move_res in order_moves(board.all_moves(), &board) {
-                    let score = Self::mini_max_ab(self,&move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, deadline, stats);
+                for move_res in root_moves {
+                    let score = self.search_move(&move_res.board, Some(move_res.mv), search_depth, f64::NEG_INFINITY, f64::INFINITY, true, deadline, stats);
+                    root_scores.push((move_res.mv, score));
                     if score < best {
                         best = score;
                         best_moves = Vec::new();
@@ -205,27 +697,575 @@ impl Solver for IDQuiescence {
                         best_moves.push(move_res.mv);
                     }
                 }
-                if SystemTime::now()<deadline {
+                if !self.aborted {
                     best_backup = best;
                     best_moves_backup = best_moves.clone();
+                    completed_depth = search_depth;
+                    time.iteration_finished(best_moves_backup.first().copied());
+
+                    if thread == 0 {
+                        self.record_analysis(board, search_depth, root_scores, time, limits, stats);
+                    }
+
+                    if limits.mate_found(best, false) {
+                        break;
+                    }
                 }
             }
-            stats.evaluation(best_backup);
         }
 
+        (best_backup, best_moves_backup, completed_depth)
+    }
+
+    /// Keeps the best `multi_pv` root moves of a completed iteration with their principal variations,
+    /// and reports them to the info callback.
+    fn record_analysis<B: Board>(&mut self, board: &B, depth: u64, mut root_scores: Vec<(Move, f64)>, time: &TimeManager, limits: &SearchLimits, stats: &StatsEntry) {
+        if board.current_player() == White {
+            root_scores.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        } else {
+            root_scores.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        }
+
+        // a weakened engine chooses among more moves than it reports
+        let skill_lines = self.config.skill.as_ref().map_or(1, |skill| skill.multi_pv);
+        let lines = root_scores.into_iter()
+            .take(self.config.multi_pv.max(skill_lines))
+            .map(|(m, score)| PvLine {
+                score,
+                pv: self.principal_variation(board, Some(m), depth as usize + 1),
+            })
+            .collect();
+
+        self.analysis = Analysis { depth, lines };
+
+        for (rank, line) in self.analysis.lines.iter().take(self.config.multi_pv.max(1)).enumerate() {
+            limits.report(SearchInfo::new(depth, line.score, stats.states_seen(), time.elapsed(), line.pv.clone()).with_multipv(rank + 1));
+        }
+    }
+
+    /// Searches `board` like `make_move` does, and returns its best `multi_pv` moves instead of playing one.
+    pub fn analyse<B: Board>(&mut self, board: &DisplayableBoard<B>, multi_pv: usize, limits: &SearchLimits, stats: &mut StatsEntry) -> Analysis {
+        if let Some(ponder) = self.ponder.take() {
+            self.stop_pondering(ponder);
+        }
+
+        let remaining_time = if board.current_player() == White{
+            board.get_clock()[0]
+        } else {
+            board.get_clock()[1]
+        };
+        let mut time = limits.time_manager(remaining_time, &self.config.time_control);
+        self.external_stop = limits.stop.clone();
+
+        self.history.age();
+        self.path.clear();
+        self.transposition_table.new_search();
+        self.stop.store(false, Ordering::Relaxed);
+
+        let configured = mem::replace(&mut self.config.multi_pv, multi_pv);
+        let (_, _, depth) = self.search(board, &mut time, limits, stats);
+        self.config.multi_pv = configured;
+
+        stats.search_depth(depth);
+        mem::take(&mut self.analysis)
+    }
+
+    /// The expected line of play starting with `first`, following the best moves in the transposition table.
+    fn principal_variation<B: Board>(&self, board: &B, first: Option<Move>, max_length: usize) -> Vec<Move> {
+        let first = match first {
+            Some(m) => m,
+            None => return Vec::new(),
+        };
+
+        let mut pv = vec![first];
+        let mut position = board.transition(first);
+        let mut seen = vec![board.hash(), position.hash()];
+
+        while pv.len() < max_length {
+            let m = match self.transposition_table.get(position.hash()).and_then(|e| e.best_move) {
+                Some(m) if position.all_moves().contains(&m) => m,
+                _ => break,
+            };
+
+            position = position.transition(m);
+            // a repetition would go on forever
+            if seen.contains(&position.hash()) {
+                break;
+            }
+            seen.push(position.hash());
+            pv.push(m);
+        }
+
+        pv
+    }
+
+    /// Searches `board` with all configured threads until the time is up, or until `stop` is set.
+    fn search<B: Board>(&mut self, board: &DisplayableBoard<B>, time: &mut TimeManager, limits: &SearchLimits, stats: &mut StatsEntry) -> SearchResult {
+        self.analysis = Analysis::default();
+        let helpers: Vec<Self> = (1..self.config.threads).map(|_| self.helper()).collect();
+
+        let (result, helper_stats) = thread::scope(|scope| {
+            let handles: Vec<_> = helpers.into_iter()
+                .enumerate()
+                .map(|(i, mut helper)| {
+                    let mut time = time.clone();
+                    scope.spawn(move || {
+                        let mut helper_stats = StatsEntry::new(None);
+                        helper.iterate(board, i + 1, &mut time, limits, &mut helper_stats);
+                        helper_stats
+                    })
+                })
+                .collect();
+
+            let result = self.iterate(board, 0, time, limits, stats);
+            self.stop.store(true, Ordering::Relaxed);
+
+            let helper_stats: Vec<StatsEntry> = handles.into_iter()
+                .map(|h| h.join().expect("helper thread panicked"))
+                .collect();
+
+            (result, helper_stats)
+        });
+
+        if !helper_stats.is_empty() {
+            let main_nodes = stats.states_seen();
+            let mut nodes = vec![main_nodes];
+            nodes.extend(helper_stats.iter().map(StatsEntry::states_seen));
+            stats.thread_nodes(nodes);
+
+            // all nodes searched over the nodes of the main thread. This is not the speedup: helpers search
+            // many of the same nodes, so it only shows how busy the helper threads were. See `measure_speedup`.
+            if main_nodes > 0 {
+                stats.custom_float_entry("node_ratio", stats.states_seen() as f64 / main_nodes as f64);
+            }
+        }
+
+        result
+    }
+
+    /// Reports how much faster the configured threads search `board` to `depth` than a single thread,
+    /// both from an empty transposition table, as the time of one thread over the time of all threads.
+    fn measure_speedup<B: Board>(&self, board: &DisplayableBoard<B>, depth: u64, stats: &mut StatsEntry) {
+        let time_to_depth = |threads| {
+            let mut solver = Self::with_config(SearchConfig {
+                threads,
+                measure_speedup: false,
+                ponder: false,
+                multi_pv: 1,
+                skill: None,
+                book: None,
+                mate_search: false,
+                ..self.config.clone()
+            });
+            let limits = SearchLimits {
+                depth: Some(depth),
+                ..SearchLimits::default()
+            };
+
+            let start = Instant::now();
+            solver.make_move_impl(board.clone(), &limits, &mut StatsEntry::new(None));
+            start.elapsed()
+        };
+
+        let single = time_to_depth(1);
+        let parallel = time_to_depth(self.config.threads);
+        if parallel > Duration::ZERO {
+            stats.custom_float_entry("speedup", single.as_secs_f64() / parallel.as_secs_f64());
+        }
+    }
+
+    /// The best move of the endgame tablebases, when the position is in them, see `tablebase_move`.
+    fn find_tablebase_move(&self, board: &impl Board, stats: &mut StatsEntry) -> Option<SearchResult> {
+        if !self.config.tablebases {
+            return None;
+        }
+
+        let (m, score) = tablebase_move(board, self.draw_score, stats)?;
+        Some((score, vec![m], 0))
+    }
+
+    /// Runs the proof-number mate search when it's enabled, and returns a mate for the side to move if it finds one.
+    fn find_mate(&self, board: &impl Board, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<SearchResult> {
+        if !self.config.mate_search {
+            return None;
+        }
+
+        let mate_limits = SearchLimits {
+            nodes: Some(self.config.mate_search_nodes),
+            stop: limits.stop.clone(),
+            ..SearchLimits::default()
+        };
+
+        match ProofNumberSearch::new().search(board, board.current_player(), &mate_limits, stats) {
+            MateResult::Mate(line) => {
+                stats.custom_int_entry_add("proof_number_mates");
+                let score = if board.current_player() == White { f64::INFINITY } else { f64::NEG_INFINITY };
+                Some((score, vec![line[0]], line.len() as u64))
+            }
+            _ => None,
+        }
+    }
+
+    /// Stops the background search, and takes its move history back.
+    fn stop_pondering(&mut self, ponder: Ponder) -> (SearchResult, StatsEntry) {
+        ponder.stop.store(true, Ordering::Relaxed);
+        let (mut ponderer, result, stats) = ponder.handle.join().expect("ponder thread panicked");
+        self.history = mem::replace(&mut ponderer.history, MoveHistory::new());
+        (result, stats)
+    }
+
+    /// The opponent played the move we were pondering on: keep searching until the soft limit,
+    /// with everything that was searched so far.
+    fn ponder_hit(&mut self, ponder: Ponder, time: &TimeManager, stats: &mut StatsEntry) -> SearchResult {
+        while time.elapsed() < time.soft_limit()
+            && !ponder.handle.is_finished()
+            && !self.external_stop.as_ref().is_some_and(StopToken::is_stopped) {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let (result, ponder_stats) = self.stop_pondering(ponder);
+        stats.custom_int_entry_add("ponder_hits");
+        stats.custom_int_entry("ponder_nodes", ponder_stats.states_seen() as i64);
+        result
+    }
+}
+
+impl Drop for IDQuiescence {
+    fn drop(&mut self) {
+        if let Some(ponder) = &self.ponder {
+            ponder.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Solver for IDQuiescence {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+        let mut rng = thread_rng();
+
+        let skill_limits;
+        let limits = match &self.config.skill {
+            Some(skill) => {
+                skill_limits = skill.limit(limits);
+                &skill_limits
+            }
+            None => limits,
+        };
+        self.analysis = Analysis::default();
+        self.record_position(&board);
+
+        let remaining_time = if board.current_player() == White{
+            board.get_clock()[0]
+        } else {
+            board.get_clock()[1]
+        };
+
+        let mut time = limits.time_manager(remaining_time, &self.config.time_control);
+
+        self.external_stop = limits.stop.clone();
+
+        if mem::take(&mut self.ponder_missed) {
+            stats.custom_int_entry_add("ponder_misses");
+        }
+
+        let pondered = match self.ponder.take() {
+            Some(ponder) if ponder.hash == board.hash() && limits.uses_clock() => Some(self.ponder_hit(ponder, &time, stats)),
+            // the prediction was right, but a search to a depth, node count or time of its own has to
+            // start over. It still finds the table and history the ponder search filled.
+            Some(ponder) if ponder.hash == board.hash() => {
+                let (_, ponder_stats) = self.stop_pondering(ponder);
+                stats.custom_int_entry_add("ponder_discarded");
+                stats.custom_int_entry("ponder_nodes", ponder_stats.states_seen() as i64);
+                None
+            }
+            Some(ponder) => {
+                self.stop_pondering(ponder);
+                stats.custom_int_entry_add("ponder_misses");
+                None
+            }
+            None => None,
+        };
+
+        let (best, best_moves, search_depth) = match pondered {
+            Some(result) if !result.1.is_empty() => result,
+            _ => match self.find_tablebase_move(&board, stats).or_else(|| self.find_mate(&board, limits, stats)) {
+                Some(result) => result,
+                None => {
+                    self.history.age();
+                    self.path.clear();
+                    self.transposition_table.new_search();
+                    self.stop.store(false, Ordering::Relaxed);
+
+                    let result = self.search(&board, &mut time, limits, stats);
+                    if self.config.measure_speedup && self.config.threads > 1 && result.2 > 0 {
+                        self.measure_speedup(&board, result.2, stats);
+                    }
+                    result
+                }
+            }
+        };
+
+        stats.evaluation(best);
         stats.search_depth(search_depth);
+        stats.transposition().hashfull(self.transposition_table.hashfull());
+
+        let cutoffs = stats.get_custom_int_entry("cutoffs");
+        if cutoffs > 0 {
+            let first_move_cutoffs = stats.get_custom_int_entry("first_move_cutoffs");
+            stats.custom_float_entry("first_move_cutoff_rate", first_move_cutoffs as f64 / cutoffs as f64);
+        }
+
+        let weakened = match &self.config.skill {
+            Some(skill) => skill.choose(&self.analysis, board.current_player(), &mut self.skill_rng, stats),
+            None => None,
+        };
 
-        let m = if best_moves_backup.is_empty(){
+        let m = if let Some(m) = weakened {
+            m
+        } else if best_moves.is_empty(){
             board.all_moves().into_iter().choose(&mut rng)?
         } else{
-            best_moves_backup.into_iter().choose(&mut rng)?
+            best_moves.into_iter().choose(&mut rng)?
+        };
+
+        let new_state = board.transition(m);
+        self.record_position(&new_state);
+
+        Some(new_state)
+    }
+
+    fn opponent_thinking<B: Board + 'static>(&mut self, board: &DisplayableBoard<B>) {
+        if !self.config.ponder || board.is_terminal().is_some() {
+            return;
+        }
+
+        // the reply we expect is the next move of our principal variation
+        let expected = match self.transposition_table.get(board.hash()).and_then(|e| e.best_move) {
+            Some(m) if board.all_moves().contains(&m) => m,
+            _ => return,
+        };
+
+        let next = board.transition(expected);
+        if next.is_terminal().is_some() {
+            return;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let game_history = self.ponder_history(board);
+
+        let mut ponderer = Self {
+            config: self.config.clone(),
+            history: mem::replace(&mut self.history, MoveHistory::new()),
+            extensions: Extensions::new(),
+            transposition_table: self.transposition_table.clone(),
+            stop: stop.clone(),
+            external_stop: None,
+            path: Vec::new(),
+            game_history,
+            positions: Vec::new(),
+            draw_score: 0.0,
+            ponder: None,
+            ponder_missed: false,
+            aborted: false,
+            node_limit: None,
+            analysis: Analysis::default(),
+            skill_rng: StdRng::from_entropy(),
         };
 
+        let hash = next.hash();
+        let handle = thread::spawn(move || {
+            let mut stats = StatsEntry::new(None);
+
+            ponderer.history.age();
+            ponderer.transposition_table.new_search();
+
+            // only stopped by the opponent's move
+            let mut time = TimeManager::unlimited();
+            let result = ponderer.search(&next, &mut time, &SearchLimits::infinite(), &mut stats);
+            (ponderer, result, stats)
+        });
+
+        self.ponder = Some(Ponder {
+            expected,
+            hash,
+            stop,
+            handle,
+        });
+    }
+
+    fn opponent_played<B: Board>(&mut self, board: &DisplayableBoard<B>, m: Move) {
+        self.record_position(board);
+
+        let missed = match &self.ponder {
+            Some(ponder) => ponder.expected != m || ponder.hash != board.hash() || board.is_terminal().is_some(),
+            None => false,
+        };
+
+        if missed {
+            // the transposition table is still warmed by the search
+            let ponder = self.ponder.take().unwrap();
+            self.stop_pondering(ponder);
+            self.ponder_missed = true;
+        }
+    }
+
+    fn set_time_control(&mut self, control: TimeControl) {
+        self.config.time_control = control;
+    }
 
-        Some(board.transition(m))
+    fn book(&self) -> Option<&BookConfig> {
+        self.config.book.as_ref()
     }
 
     fn init_stats(&self, stats_folder: String) -> Stats {
-        Stats::new("Minimax with Alpha-Beta pruning and ID", None,None, stats_folder, true)
+        Stats::new("Minimax with Alpha-Beta pruning and ID", None, Some(self.transposition_table.len()), stats_folder, true)
+    }
+}
+/// Whether the given player has any pieces left besides their king and pawns.
+/// Positions with only pawns are where zugzwang is most common.
+fn has_non_pawn_material(board: &impl Board, color: Color) -> bool {
+    for x in 0..8 {
+        for y in 0..8 {
+            let p = board.piece_at((x, y));
+            if p.color() == color && !p.is_pawn() && !p.is_king() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Quiet moves don't change the material balance, so pruning them based on a
+/// static evaluation is relatively safe.
+fn is_quiet(m: Move) -> bool {
+    !m.extra.is_capturing() && !m.extra.is_promotion()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::display::DisplayableBoard;
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::fen::parse_fen;
+    use crate::game_engine::lan::parse_lan;
+    use crate::game_engine::san::parse_san;
+    use crate::solver::Solver;
+    use crate::solver::config::SearchConfig;
+    use crate::solver::id_quiescence::IDQuiescence;
+    use crate::solver::limits::SearchLimits;
+    use crate::stats::StatsEntry;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ponder_history() {
+        let mut solver = IDQuiescence::new();
+        let mut board = BasicBoard::default_board(Duration::from_secs(60));
+        solver.record_position(&board);
+        // the knights go out and back, the last move is ours
+        for san in &["Nf3", "Nf6", "Ng1", "Ng8"] {
+            board = board.transition(parse_san(&board, san).unwrap());
+            solver.record_position(&board);
+        }
+
+        let history = solver.ponder_history(&board);
+        assert_eq!(history, solver.game_history);
+
+        // when the opponent plays Nf3 again, its position came back with the same player to move
+        let next = board.transition(parse_san(&board, "Nf3").unwrap());
+        solver.positions = history;
+        solver.positions.push(next.hash());
+        assert!(solver.is_repetition(&next));
+    }
+
+    #[test]
+    fn test_ponder_with_own_limits() {
+        let mut solver = IDQuiescence::with_config(SearchConfig {
+            ponder: true,
+            ..SearchConfig::default()
+        });
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+
+        let board = DisplayableBoard::new(BasicBoard::default_board(Duration::from_secs(60)));
+        let board = solver.make_move_impl(board, &limits, &mut StatsEntry::new(None)).unwrap();
+        solver.opponent_thinking(&board);
+
+        // the opponent plays the move we expected, but the search is to a depth, not on the clock
+        let expected = solver.ponder.as_ref().unwrap().expected;
+        let next = board.transition(expected);
+        solver.opponent_played(&next, expected);
+
+        let mut stats = StatsEntry::new(None);
+        solver.make_move_impl(next, &limits, &mut stats).unwrap();
+        assert_eq!(stats.get_custom_int_entry("ponder_discarded"), 1);
+        assert_eq!(stats.get_custom_int_entry("ponder_misses"), 0);
+    }
+
+    #[test]
+    fn test_repetition_window() {
+        let mut solver = IDQuiescence::new();
+        let start = BasicBoard::default_board(Duration::from_secs(60));
+        solver.positions.push(start.hash());
+
+        let mut board = start.clone();
+        for san in &["Nf3", "Nf6", "Ng1", "Ng8"] {
+            board = board.transition(parse_san(&board, san).unwrap());
+            solver.positions.push(board.hash());
+        }
+        assert_eq!(board.hash(), start.hash());
+        assert!(solver.is_repetition(&board));
+
+        // positions before a capture or pawn move are never looked at
+        let after_pawn_move = parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3 3", Duration::from_secs(60)).unwrap();
+        assert_eq!(after_pawn_move.hash(), start.hash());
+        assert!(!solver.is_repetition(&after_pawn_move));
+
+        // only positions an even number of plies back have the same player to move
+        solver.positions = vec![start.hash(), 1, start.hash()];
+        assert!(solver.is_repetition(&after_pawn_move));
+        solver.positions = vec![start.hash(), start.hash()];
+        assert!(!solver.is_repetition(&after_pawn_move));
+    }
+
+    #[test]
+    fn test_find_mate() {
+        let board = parse_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", Duration::from_secs(600)).unwrap();
+        let mut stats = StatsEntry::new(None);
+
+        assert!(IDQuiescence::new().find_mate(&board, &SearchLimits::default(), &mut stats).is_none());
+
+        let solver = IDQuiescence::with_config(SearchConfig {
+            mate_search: true,
+            ..SearchConfig::default()
+        });
+        let (score, moves, plies) = solver.find_mate(&board, &SearchLimits::default(), &mut stats).unwrap();
+        assert_eq!(score, f64::INFINITY);
+        assert_eq!(moves, vec![parse_lan(&board, "a1a6").unwrap()]);
+        assert_eq!(plies, 3);
+
+        // the mate search gives up on positions without a quick mate
+        let board = BasicBoard::default_board(Duration::from_secs(600));
+        assert!(solver.find_mate(&board, &SearchLimits::default(), &mut stats).is_none());
+    }
+
+    #[test]
+    fn test_analyse() {
+        let board = DisplayableBoard::new(parse_fen("2r3k1/5ppp/8/3q4/1b6/2N5/5PPP/3R2K1 b - - 0 1", Duration::from_secs(600)).unwrap());
+        let limits = SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        };
+
+        let analysis = IDQuiescence::new().analyse(&board, 4, &limits, &mut StatsEntry::new(None));
+        assert_eq!(analysis.lines.len(), 4);
+
+        // black's best line first
+        for pair in analysis.lines.windows(2) {
+            assert!(pair[0].score <= pair[1].score);
+        }
+        let first: Vec<_> = analysis.lines.iter().map(|line| line.pv[0]).collect();
+        for (i, m) in first.iter().enumerate() {
+            assert!(!first[..i].contains(m));
+        }
     }
-}
\ No newline at end of file
+}
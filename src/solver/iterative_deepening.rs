@@ -1,52 +1,86 @@
 use crate::game_engine::board::Board;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
-use crate::game_engine::color::Color::{White, Black};
-use crate::solver::Solver;
+use crate::game_engine::color::Color::White;
+use crate::game_engine::chess_move::Move;
+use crate::solver::{tablebase_move, Solver};
 use crate::solver::move_order::order_moves;
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
-use std::time::{SystemTime, Duration};
-use std::ops::Add;
-use std::convert::TryInto;
+use crate::solver::time_manager::TimeControl;
+use crate::solver::limits::SearchLimits;
+use crate::solver::control::SearchInfo;
+use crate::solver::contempt::{Contempt, terminal_score};
+use std::time::SystemTime;
 
 const TIME_DECAY: f64 = 0.99999;
 
+/// Width of the window used to prove that a non-PV move is no better than the current best.
+/// Scores are floats, so this only has to be smaller than any meaningful difference in evaluation.
+const NULL_WINDOW: f64 = 1e-6;
+
+/// Initial half width of the aspiration window around the score of the previous iteration.
+const ASPIRATION_WINDOW: f64 = 50.0;
+
+/// Once the aspiration window grows past this, we give up and search with an infinite window.
+const MAX_ASPIRATION_WINDOW: f64 = 1000.0;
+
 pub struct IterativeDeepening {
+    time_control: TimeControl,
+    contempt: Contempt,
+    /// The score of a draw in the current search, from the contempt at its root
+    draw_score: f64,
 }
 
 impl IterativeDeepening {
     pub fn new() -> Self {
+        Self::with_time_control(TimeControl::default())
+    }
+
+    pub fn with_time_control(time_control: TimeControl) -> Self {
+        Self::with_config(time_control, Contempt::default())
+    }
+
+    pub fn with_config(time_control: TimeControl, contempt: Contempt) -> Self {
         Self {
+            time_control,
+            contempt,
+            draw_score: 0.0,
         }
     }
 
-    pub fn mini_max_ab(board: &impl Board, depth: u64, mut a: f64, mut b: f64,deadline:SystemTime, stats: &mut StatsEntry) -> f64 {
+    pub fn mini_max_ab(&self, board: &impl Board, depth: u64, mut a: f64, mut b: f64,deadline: SystemTime, limits: &SearchLimits, stats: &mut StatsEntry) -> f64 {
         stats.seen_state();
 
-        if SystemTime::now()>deadline{
+        if limits.aborted(deadline, stats.states_seen()) {
             return 0.0
         }
 
         if depth == 0 || board.is_terminal().is_some() {
-            let terminal = board.is_terminal();
-            if terminal.is_some() {
-                return if terminal == Some(Black) {
-                    f64::NEG_INFINITY
-                } else if terminal == Some(White) {
-                    f64::INFINITY
-                } else {
-                    0.0
-                };
+            if let Some(winner) = board.is_terminal() {
+                return terminal_score(winner, self.draw_score);
             }
             return board.heuristic();
         }
 
         if board.current_player() == White {
             let mut value = f64::NEG_INFINITY;
-            for move_res in order_moves(board.all_moves(), board) {
+            for (index, move_res) in order_moves(board.all_moves(), board).enumerate() {
+                let score = if index == 0 {
+                    self.mini_max_ab(&move_res.board, depth - 1, a, b, deadline, limits, stats)
+                } else {
+                    // Principal variation search: assume the first move was the best one,
+                    // and only do a full search if this move turns out to be better.
+                    let score = self.mini_max_ab(&move_res.board, depth - 1, a, a + NULL_WINDOW, deadline, limits, stats);
+                    if score > a && score < b {
+                        stats.custom_int_entry_add("pvs_re_searches");
+                        self.mini_max_ab(&move_res.board, depth - 1, a, b, deadline, limits, stats)
+                    } else {
+                        score
+                    }
+                };
 
-                value = value.max(TIME_DECAY*Self::mini_max_ab(&move_res.board, depth - 1, a, b, deadline, stats));
+                value = value.max(TIME_DECAY*score);
                 a = a.max(value);
                 if a >= b {
                     break;
@@ -55,9 +89,20 @@ impl IterativeDeepening {
             return value;
         } else {
             let mut value = f64::INFINITY;
-            for move_res in order_moves(board.all_moves(), board) {
+            for (index, move_res) in order_moves(board.all_moves(), board).enumerate() {
+                let score = if index == 0 {
+                    self.mini_max_ab(&move_res.board, depth - 1, a, b, deadline, limits, stats)
+                } else {
+                    let score = self.mini_max_ab(&move_res.board, depth - 1, b - NULL_WINDOW, b, deadline, limits, stats);
+                    if score < b && score > a {
+                        stats.custom_int_entry_add("pvs_re_searches");
+                        self.mini_max_ab(&move_res.board, depth - 1, a, b, deadline, limits, stats)
+                    } else {
+                        score
+                    }
+                };
 
-                value = value.min(TIME_DECAY*Self::mini_max_ab(&move_res.board, depth - 1, a, b,deadline, stats));
+                value = value.min(TIME_DECAY*score);
                 b = b.min(value);
                 if b <= a {
                     break;
@@ -66,10 +111,122 @@ impl IterativeDeepening {
             return value;
         }
     }
+
+    /// Searches all root moves within the window (a, b), using principal variation search.
+    /// The moves are searched in the given order, so the best move of the previous iteration
+    /// should come first. Returns the best score and all moves that reach it. Moves are only
+    /// counted as reaching the best score when it's their exact score, not a bound.
+    pub fn search_root(&self, board: &impl Board, root_moves: &[Move], depth: u64, mut a: f64, mut b: f64, deadline: SystemTime, limits: &SearchLimits, stats: &mut StatsEntry) -> (f64, Vec<Move>) {
+        let mut best_moves = Vec::new();
+
+        if board.current_player() == White {
+            let mut best = f64::NEG_INFINITY;
+            for (index, &mv) in root_moves.iter().enumerate() {
+                let new_board = board.transition(mv);
+                let score = if index == 0 {
+                    self.mini_max_ab(&new_board, depth, a, b, deadline, limits, stats)
+                } else {
+                    let score = self.mini_max_ab(&new_board, depth, a, a + NULL_WINDOW, deadline, limits, stats);
+                    if score > a && score < b {
+                        stats.custom_int_entry_add("pvs_re_searches");
+                        self.mini_max_ab(&new_board, depth, a, b, deadline, limits, stats)
+                    } else if score == best {
+                        // failing low on the null window only proves that the move is no better,
+                        // search it again with a window that can tell whether it's as good
+                        stats.custom_int_entry_add("pvs_tie_searches");
+                        self.mini_max_ab(&new_board, depth, best - NULL_WINDOW, b, deadline, limits, stats)
+                    } else {
+                        score
+                    }
+                };
+
+                if score > best {
+                    best = score;
+                    best_moves = Vec::new();
+                    best_moves.push(mv)
+                } else if score == best {
+                    best_moves.push(mv);
+                }
+
+                a = a.max(best);
+                if a >= b {
+                    break;
+                }
+            }
+            (best, best_moves)
+        } else {
+            let mut best = f64::INFINITY;
+            for (index, &mv) in root_moves.iter().enumerate() {
+                let new_board = board.transition(mv);
+                let score = if index == 0 {
+                    self.mini_max_ab(&new_board, depth, a, b, deadline, limits, stats)
+                } else {
+                    let score = self.mini_max_ab(&new_board, depth, b - NULL_WINDOW, b, deadline, limits, stats);
+                    if score < b && score > a {
+                        stats.custom_int_entry_add("pvs_re_searches");
+                        self.mini_max_ab(&new_board, depth, a, b, deadline, limits, stats)
+                    } else if score == best {
+                        stats.custom_int_entry_add("pvs_tie_searches");
+                        self.mini_max_ab(&new_board, depth, a, best + NULL_WINDOW, deadline, limits, stats)
+                    } else {
+                        score
+                    }
+                };
+
+                if score < best {
+                    best = score;
+                    best_moves = Vec::new();
+                    best_moves.push(mv)
+                } else if score == best {
+                    best_moves.push(mv);
+                }
+
+                b = b.min(best);
+                if b <= a {
+                    break;
+                }
+            }
+            (best, best_moves)
+        }
+    }
+
+    /// Searches the root with an aspiration window around the score of the previous iteration.
+    /// Whenever the result falls outside of the window, the window is widened on that side and
+    /// the root is searched again.
+    pub fn search_aspiration(&self, board: &impl Board, root_moves: &[Move], depth: u64, previous: Option<f64>, deadline: SystemTime, limits: &SearchLimits, stats: &mut StatsEntry) -> (f64, Vec<Move>) {
+        let previous = match previous {
+            Some(i) if i.is_finite() => i,
+            _ => return self.search_root(board, root_moves, depth, f64::NEG_INFINITY, f64::INFINITY, deadline, limits, stats),
+        };
+
+        let mut low_window = ASPIRATION_WINDOW;
+        let mut high_window = ASPIRATION_WINDOW;
+
+        loop {
+            let a = if low_window > MAX_ASPIRATION_WINDOW { f64::NEG_INFINITY } else { previous - low_window };
+            let b = if high_window > MAX_ASPIRATION_WINDOW { f64::INFINITY } else { previous + high_window };
+
+            let (best, best_moves) = self.search_root(board, root_moves, depth, a, b, deadline, limits, stats);
+
+            if limits.aborted(deadline, stats.states_seen()) {
+                return (best, best_moves);
+            }
+
+            if best <= a && a > f64::NEG_INFINITY {
+                stats.custom_int_entry_add("aspiration_fail_low");
+                low_window *= 4.0;
+            } else if best >= b && b < f64::INFINITY {
+                stats.custom_int_entry_add("aspiration_fail_high");
+                high_window *= 4.0;
+            } else {
+                return (best, best_moves);
+            }
+        }
+    }
 }
 
 impl Solver for IterativeDeepening {
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mut rng = thread_rng();
 
         let remaining_time = if board.current_player() == White{
@@ -78,64 +235,56 @@ impl Solver for IterativeDeepening {
             board.get_clock()[1]
         };
 
-        let time_budget = remaining_time / 20;
+        let mut time = limits.time_manager(remaining_time, &self.time_control);
+        let deadline = time.deadline();
 
-        let deadline = SystemTime::now()
-            .add(time_budget);
+        self.draw_score = self.contempt.draw_score(&board, board.current_player());
+
+        if let Some((m, score)) = tablebase_move(&board, self.draw_score, stats) {
+            stats.evaluation(score);
+            return Some(board.transition(m));
+        }
+
+        let mut root_moves = order_moves(board.all_moves(), &board).into_moves();
 
-        let mut best_moves = Vec::new();
         let mut best_moves_backup = Vec::new();
-        let mut best_backup = 0.0;
+        let mut best_backup = None;
 
-        let mut search_depth = 0;
+        let mut iteration = 0;
+        let mut completed_depth = 0;
 
-        if board.current_player() == White {
-            while SystemTime::now()<deadline {
-                search_depth += 1;
-                best_moves = Vec::new();
-                let mut best = f64::NEG_INFINITY;
-                for move_res in order_moves(board.all_moves(), &board) {
-                    let score = Self::mini_max_ab(&move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY,deadline, stats);
-                    if score > best {
-                        best = score;
-                        best_moves = Vec::new();
-                        best_moves.push(move_res.mv)
-                    } else if score == best {
-                        best_moves.push(move_res.mv);
-                    }
-                }
-                if SystemTime::now()<deadline {
-                    best_backup = best;
-                    best_moves_backup = best_moves.clone();
-                }
-            }
-            stats.evaluation(best_backup);
-        }
+        while time.should_start_iteration() && limits.deepen(iteration) {
+            iteration += 1;
+            let search_depth = limits.iteration_depth(iteration);
 
-        if board.current_player() == Black {
-            while SystemTime::now()<deadline {
-                search_depth += 1;
-                best_moves = Vec::new();
-                let mut best = f64::INFINITY;
-                for move_res in order_moves(board.all_moves(), &board) {
-                    let score = Self::mini_max_ab(&move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, deadline, stats);
-                    if score < best {
-                        best = score;
-                        best_moves = Vec::new();
-                        best_moves.push(move_res.mv)
-                    } else if score == best {
-                        best_moves.push(move_res.mv);
-                    }
-                }
-                if SystemTime::now()<deadline {
-                    best_backup = best;
-                    best_moves_backup = best_moves.clone();
+            let (best, best_moves) = self.search_aspiration(&board, &root_moves, search_depth, best_backup, deadline, limits, stats);
+
+            // an iteration that hit the deadline returned made up scores somewhere
+            if !limits.aborted(deadline, stats.states_seen()) {
+                // search the best moves of this iteration first in the next one,
+                // which makes the null windows of the PVS much more effective.
+                root_moves.retain(|i| !best_moves.contains(i));
+                root_moves.splice(0..0, best_moves.iter().copied());
+
+                time.iteration_finished(best_moves.first().copied());
+
+                // without a transposition table, the best move is all we know of the principal variation
+                limits.report(SearchInfo::new(search_depth, best, stats.states_seen(), time.elapsed(), best_moves.iter().copied().take(1).collect()));
+
+                best_backup = Some(best);
+                best_moves_backup = best_moves;
+                completed_depth = search_depth;
+
+                if limits.mate_found(best, board.current_player() == White) {
+                    break;
                 }
+            } else {
+                break;
             }
-            stats.evaluation(best_backup);
         }
 
-        stats.search_depth(search_depth);
+        stats.evaluation(best_backup.unwrap_or(0.0));
+        stats.search_depth(completed_depth);
 
         let m = if best_moves_backup.is_empty(){
             board.all_moves().into_iter().choose(&mut rng)?
@@ -147,7 +296,64 @@ impl Solver for IterativeDeepening {
         Some(board.transition(m))
     }
 
+    fn set_time_control(&mut self, control: TimeControl) {
+        self.time_control = control;
+    }
+
     fn init_stats(&self, stats_folder: String) -> Stats {
-        Stats::new("Minimax with Alpha-Beta pruning and ID", None,None, stats_folder, true)
+        Stats::new("Minimax with Alpha-Beta pruning, PVS and ID", None,None, stats_folder, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::Board;
+    use crate::game_engine::board::display::DisplayableBoard;
+    use crate::game_engine::fen::parse_fen;
+    use crate::game_engine::lan::parse_lan;
+    use crate::solver::Solver;
+    use crate::solver::iterative_deepening::IterativeDeepening;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::move_order::order_moves;
+    use crate::stats::StatsEntry;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_best_moves_are_exact() {
+        // Nd4 hangs the knight to the queen, but QxN loses material by SEE and is ordered after
+        // the quiet replies, which fail low on the null window with the same score as the best move
+        let board = parse_fen("3q3k/8/8/8/8/4PN2/8/7K w - - 0 1", Duration::from_secs(600)).unwrap();
+        let deadline = SystemTime::now() + Duration::from_secs(600);
+        let limits = SearchLimits::default();
+        let mut stats = StatsEntry::new(None);
+
+        let root_moves = order_moves(board.all_moves(), &board).into_moves();
+        let solver = IterativeDeepening::new();
+        let (best, best_moves) = solver.search_root(&board, &root_moves, 1, f64::NEG_INFINITY, f64::INFINITY, deadline, &limits, &mut stats);
+
+        assert!(!best_moves.contains(&parse_lan(&board, "f3d4").unwrap()));
+        // every move that may be played at random among the best has to be as good as the best
+        for &m in &best_moves {
+            let exact = solver.mini_max_ab(&board.transition(m), 1, f64::NEG_INFINITY, f64::INFINITY, deadline, &limits, &mut stats);
+            assert_eq!(exact, best);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_root_only() {
+        // the pawn on d5 is only free if the recapture isn't searched
+        let board = DisplayableBoard::new(parse_fen("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", Duration::from_secs(600)).unwrap());
+        let capture = parse_lan(&board, "d1d5").unwrap();
+
+        let played = |depth| {
+            let limits = SearchLimits {
+                depth: Some(depth),
+                ..SearchLimits::default()
+            };
+            IterativeDeepening::new().make_move_impl(board.clone(), &limits, &mut StatsEntry::new(None)).unwrap().last_move()
+        };
+
+        assert_eq!(played(0), Some(capture));
+        assert_ne!(played(1), Some(capture));
+    }
+}
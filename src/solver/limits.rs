@@ -0,0 +1,125 @@
+use crate::solver::time_manager::{TimeManager, TimeControl};
+use crate::solver::control::{StopToken, InfoCallback, SearchInfo};
+use std::time::{Duration, SystemTime};
+
+/// Limits for the search of a single move. Without any limits set, solvers search with
+/// their own depth, or manage the time on the clock. Setting any limit disables the clock.
+///
+/// Depths are counted the way the solvers count them: the number of plies searched after each root move.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    /// Search at most this deep, in plies after the root move. At 0 only the root moves are searched.
+    pub depth: Option<u64>,
+    /// Stop after this many positions were searched. With multiple threads, this is the limit for every thread.
+    pub nodes: Option<u64>,
+    /// Search for exactly this long
+    pub move_time: Option<Duration>,
+    /// Only search deep enough to find a mate in this many moves, and stop when one is found
+    pub mate: Option<u64>,
+    /// Search until stopped with `stop`
+    pub infinite: bool,
+
+    /// Aborts the search when set. This is not a limit, and doesn't disable the clock.
+    pub stop: Option<StopToken>,
+    /// Receives the progress of the search
+    pub info: Option<InfoCallback>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u64) -> Self {
+        Self { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        Self { nodes: Some(nodes), ..Default::default() }
+    }
+
+    pub fn move_time(time: Duration) -> Self {
+        Self { move_time: Some(time), ..Default::default() }
+    }
+
+    pub fn mate(moves: u64) -> Self {
+        Self { mate: Some(moves), ..Default::default() }
+    }
+
+    pub fn infinite() -> Self {
+        Self { infinite: true, ..Default::default() }
+    }
+
+    /// Whether the time on the clock decides how long to search.
+    pub fn uses_clock(&self) -> bool {
+        self.depth.is_none() && self.nodes.is_none() && self.move_time.is_none() && self.mate.is_none() && !self.infinite
+    }
+
+    /// The deepest the search may go. A mate in n moves takes 2n - 1 plies, the first of which is the root move.
+    pub fn max_depth(&self) -> Option<u64> {
+        let mate_depth = self.mate.map(|moves| (2 * moves).saturating_sub(2).max(1));
+
+        match (self.depth, mate_depth) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Whether an iterative deepening search may start its iteration after `iteration`. A depth limit
+    /// of 0 still gets one iteration, which only searches the root moves.
+    pub fn deepen(&self, iteration: u64) -> bool {
+        self.max_depth().is_none_or(|max| iteration < max.max(1))
+    }
+
+    /// The depth that iteration `iteration` of an iterative deepening search searches with.
+    pub fn iteration_depth(&self, iteration: u64) -> u64 {
+        self.max_depth().map_or(iteration, |max| iteration.min(max))
+    }
+
+    /// The depth to search with for solvers that search to a fixed depth.
+    pub fn fixed_depth(&self, default: u64) -> u64 {
+        self.max_depth().unwrap_or(default)
+    }
+
+    pub fn nodes_exceeded(&self, nodes: u64) -> bool {
+        self.nodes.is_some_and(|max| nodes >= max)
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.stop.as_ref().is_some_and(StopToken::is_stopped)
+    }
+
+    /// Whether a search that started at `start` and has searched `nodes` positions has to stop.
+    /// Solvers without their own time management check this between root moves.
+    pub fn exceeded(&self, start: SystemTime, nodes: u64) -> bool {
+        let out_of_time = self.move_time.is_some_and(|time| {
+            SystemTime::now().duration_since(start).unwrap_or_default() >= time
+        });
+
+        out_of_time || self.nodes_exceeded(nodes) || self.stopped()
+    }
+
+    /// Whether a search with its own time management has to be aborted at once.
+    pub fn aborted(&self, deadline: SystemTime, nodes: u64) -> bool {
+        SystemTime::now() > deadline || self.nodes_exceeded(nodes) || self.stopped()
+    }
+
+    /// Passes the progress of the search to the info callback, if there is one.
+    pub fn report(&self, info: SearchInfo) {
+        if let Some(callback) = &self.info {
+            callback.call(&info);
+        }
+    }
+
+    /// Whether a score found by the side to move means it can stop searching.
+    pub fn mate_found(&self, score: f64, white_to_move: bool) -> bool {
+        self.mate.is_some() && score == if white_to_move { f64::INFINITY } else { f64::NEG_INFINITY }
+    }
+
+    /// The time manager for a search that has `remaining` time on the clock.
+    pub fn time_manager(&self, remaining: Duration, control: &TimeControl) -> TimeManager {
+        if let Some(time) = self.move_time {
+            TimeManager::fixed(time)
+        } else if self.uses_clock() {
+            TimeManager::new(remaining, control)
+        } else {
+            TimeManager::unlimited()
+        }
+    }
+}
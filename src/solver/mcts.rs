@@ -0,0 +1,429 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::board::display::DisplayableBoard;
+use crate::game_engine::chess_move::Move;
+use crate::game_engine::color::Color;
+use crate::game_engine::color::Color::{White, Black};
+use crate::game_engine::king_check::king_check;
+use crate::solver::{tablebase_move, Solver};
+use crate::solver::book::BookConfig;
+use crate::solver::contempt::Contempt;
+use crate::solver::control::SearchInfo;
+use crate::solver::limits::SearchLimits;
+use crate::solver::time_manager::TimeControl;
+use crate::stats::{StatsEntry, Stats};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+/// Searches with only a depth or mate limit have no natural end for MCTS, so they get this many playouts.
+const DEFAULT_PLAYOUTS: u64 = 20_000;
+
+/// Progress is reported to the info callback this often.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The chance that a heuristic playout plays a random move instead of the best looking one,
+/// so that playouts from the same position don't all end the same.
+const HEURISTIC_RANDOMNESS: f64 = 0.2;
+
+/// How the result of a position is estimated when it's added to the tree.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Playout {
+    /// Play random moves
+    Random,
+    /// Play the move with the best heuristic for the side to move, with some randomness
+    Heuristic,
+}
+
+#[derive(Clone, Debug)]
+pub struct MctsConfig {
+    /// The exploration constant of UCT. Higher values search more moves, lower values search the best moves deeper.
+    pub exploration: f64,
+    pub playout: Playout,
+    /// Playouts that don't end the game in this many plies are scored with the heuristic
+    pub playout_depth: u64,
+    /// Stop after this many playouts, in addition to the limits of the search
+    pub playouts: Option<u64>,
+    /// Keep the part of the tree that is still reachable after our move and the opponent's reply
+    pub reuse_tree: bool,
+    /// Play moves from this opening book as long as it has them, instead of searching.
+    pub book: Option<BookConfig>,
+    /// Play the best move of the installed endgame tablebases (and Syzygy tables) when the position is in them.
+    pub tablebases: bool,
+    /// How much the engine dislikes draws.
+    pub contempt: Contempt,
+    pub time_control: TimeControl,
+    /// With the same seed, the same positions get the same moves, as long as the search is
+    /// limited by playouts or nodes rather than by time.
+    pub seed: Option<u64>,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            exploration: std::f64::consts::SQRT_2,
+            playout: Playout::Random,
+            playout_depth: 40,
+            playouts: None,
+            reuse_tree: true,
+            book: None,
+            tablebases: true,
+            contempt: Contempt::default(),
+            time_control: TimeControl::default(),
+            seed: None,
+        }
+    }
+}
+
+/// The chance that white wins in a position with this score, like the Elo formula.
+/// Mates map to exactly 0 or 1.
+fn win_probability(score: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-score / 400.0))
+}
+
+/// The inverse of `win_probability`
+fn probability_score(p: f64) -> f64 {
+    400.0 * (p / (1.0 - p)).log10()
+}
+
+/// The winner of a position with these legal moves, when the game is over.
+/// `EmptyColor` is a draw.
+fn outcome(board: &impl Board, moves: &[Move]) -> Option<Color> {
+    if !moves.is_empty() {
+        None
+    } else if king_check(board, board.current_player()) {
+        Some(board.current_player().other())
+    } else {
+        Some(Color::EmptyColor)
+    }
+}
+
+/// The result of a finished game for white, where a draw is worth `draw_result`.
+fn outcome_result(winner: Color, draw_result: f64) -> f64 {
+    match winner {
+        White => 1.0,
+        Black => 0.0,
+        Color::EmptyColor => draw_result,
+    }
+}
+
+struct Node {
+    /// The move that led here, `None` for the root
+    mv: Option<Move>,
+    /// Hash of the position, to check that a reused tree belongs to the position that is searched
+    hash: u64,
+    children: Vec<Node>,
+    /// Moves of which no child was made yet
+    untried: Vec<Move>,
+    terminal: Option<Color>,
+
+    visits: u64,
+    /// The sum of the results of all playouts through this node, from white's perspective
+    white_wins: f64,
+    /// How many plies the tree below this node goes deep, kept up to date by the playouts
+    depth: u64,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, board: &impl Board, rng: &mut StdRng) -> Self {
+        let mut untried = board.all_moves();
+        let terminal = outcome(board, &untried);
+        untried.shuffle(rng);
+
+        Self {
+            mv,
+            hash: board.hash(),
+            children: Vec::new(),
+            untried,
+            terminal,
+
+            visits: 0,
+            white_wins: 0.0,
+            depth: 0,
+        }
+    }
+
+    /// The average result for `color`
+    fn value(&self, color: Color) -> f64 {
+        let white = self.white_wins / self.visits as f64;
+        if color == White { white } else { 1.0 - white }
+    }
+
+    fn uct(&self, color: Color, parent_visits: u64, exploration: f64) -> f64 {
+        self.value(color) + exploration * ((parent_visits as f64).ln() / self.visits as f64).sqrt()
+    }
+
+    fn most_visited(&self) -> Option<&Node> {
+        self.children.iter().max_by_key(|c| c.visits)
+    }
+
+    /// Following the most visited children.
+    fn principal_variation(&self) -> Vec<Move> {
+        let mut pv = Vec::new();
+        let mut node = self;
+        while let Some(child) = node.most_visited() {
+            pv.extend(child.mv);
+            node = child;
+        }
+        pv
+    }
+}
+
+/// Monte Carlo tree search (MCTS) with UCT. Instead of searching every move to a fixed depth,
+/// it grows a tree towards the moves that did best in playouts so far.
+pub struct Mcts {
+    config: MctsConfig,
+    rng: StdRng,
+    tree: Option<Node>,
+    /// The result of a draw for white, from the contempt at the root
+    draw_result: f64,
+}
+
+impl Mcts {
+    pub fn with_config(config: MctsConfig) -> Self {
+        Self {
+            rng: config.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+            tree: None,
+            draw_result: 0.5,
+            config,
+        }
+    }
+
+    /// Estimates the chance that white wins from `board`, by playing the game out.
+    fn simulate(&mut self, board: &impl Board, stats: &mut StatsEntry) -> f64 {
+        let mut board = board.clone();
+
+        for _ in 0..self.config.playout_depth {
+            let moves = board.all_moves();
+            if let Some(winner) = outcome(&board, &moves) {
+                return outcome_result(winner, self.draw_result);
+            }
+
+            let m = if self.config.playout == Playout::Heuristic && !self.rng.gen_bool(HEURISTIC_RANDOMNESS) {
+                Self::best_looking_move(&board, moves)
+            } else {
+                *moves.choose(&mut self.rng).expect("not terminal, so there are moves")
+            };
+
+            board = board.transition(m);
+            stats.seen_state();
+        }
+
+        win_probability(board.heuristic())
+    }
+
+    fn best_looking_move(board: &impl Board, moves: Vec<Move>) -> Move {
+        let sign = if board.current_player() == White { 1.0 } else { -1.0 };
+
+        moves.into_iter()
+            .map(|m| (m, sign * board.transition(m).heuristic()))
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(m, _)| m)
+            .expect("not terminal, so there are moves")
+    }
+
+    /// One iteration of MCTS: selects a path down the tree, expands it with one new node,
+    /// plays out from there and backs the result up. Returns the result from white's perspective.
+    fn playout(&mut self, node: &mut Node, board: &impl Board, stats: &mut StatsEntry) -> f64 {
+        let result = if let Some(winner) = node.terminal {
+            outcome_result(winner, self.draw_result)
+        } else if let Some(m) = node.untried.pop() {
+            let child_board = board.transition(m);
+            stats.seen_state();
+
+            let mut child = Node::new(Some(m), &child_board, &mut self.rng);
+            let result = self.simulate(&child_board, stats);
+            child.visits += 1;
+            child.white_wins += result;
+            node.children.push(child);
+            node.depth = node.depth.max(1);
+
+            result
+        } else {
+            let color = board.current_player();
+            let exploration = self.config.exploration;
+            let parent_visits = node.visits;
+
+            let child = node.children.iter_mut()
+                .max_by(|a, b| {
+                    a.uct(color, parent_visits, exploration)
+                        .partial_cmp(&b.uct(color, parent_visits, exploration))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .expect("not terminal and nothing untried, so there are children");
+
+            let child_board = board.transition(child.mv.expect("only the root has no move"));
+            let result = self.playout(child, &child_board, stats);
+            node.depth = node.depth.max(child.depth + 1);
+            result
+        };
+
+        node.visits += 1;
+        node.white_wins += result;
+        result
+    }
+
+    /// The tree to continue with for `board`, which is the old one if it was searched from this position.
+    fn take_tree(&mut self, board: &impl Board, stats: &mut StatsEntry) -> Node {
+        match self.tree.take() {
+            Some(tree) if self.config.reuse_tree && tree.hash == board.hash() => {
+                stats.custom_int_entry("reused_playouts", tree.visits as i64);
+                Node { mv: None, ..tree }
+            }
+            _ => Node::new(None, board, &mut self.rng),
+        }
+    }
+}
+
+impl Solver for Mcts {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+        let draw_score = self.config.contempt.draw_score(&board, board.current_player());
+        if self.config.tablebases {
+            if let Some((m, score)) = tablebase_move(&board, draw_score, stats) {
+                stats.evaluation(score);
+                return Some(board.transition(m));
+            }
+        }
+
+        let remaining_time = if board.current_player() == White {
+            board.get_clock()[0]
+        } else {
+            board.get_clock()[1]
+        };
+
+        let time = limits.time_manager(remaining_time, &self.config.time_control);
+        self.draw_result = win_probability(draw_score);
+
+        // depth means nothing to MCTS, but the search has to end somewhere
+        let budget = self.config.playouts.or_else(|| {
+            if limits.max_depth().is_some() && limits.nodes.is_none() && !limits.infinite {
+                Some(DEFAULT_PLAYOUTS)
+            } else {
+                None
+            }
+        });
+
+        let mut root = self.take_tree(&board, stats);
+        let mut playouts = 0;
+        let mut next_report = REPORT_INTERVAL;
+
+        while root.terminal.is_none()
+            && budget.is_none_or(|max| playouts < max)
+            && time.should_start_iteration()
+            && !limits.nodes_exceeded(stats.states_seen())
+            && !limits.stopped() {
+            self.playout(&mut root, &board, stats);
+            playouts += 1;
+
+            if time.elapsed() >= next_report {
+                next_report += REPORT_INTERVAL;
+                limits.report(SearchInfo::new(root.depth, probability_score(root.value(White)), stats.states_seen(), time.elapsed(), root.principal_variation()));
+            }
+        }
+
+        // the search can be stopped before a single playout
+        let (m, score) = match root.most_visited() {
+            Some(best) => (best.mv.expect("only the root has no move"), probability_score(best.value(White))),
+            None => (*board.all_moves().choose(&mut self.rng)?, 0.0),
+        };
+
+        limits.report(SearchInfo::new(root.depth, score, stats.states_seen(), time.elapsed(), root.principal_variation()));
+
+        stats.evaluation(score);
+        stats.search_depth(root.depth);
+        stats.custom_int_entry("playouts", playouts as i64);
+
+        self.tree = root.children.into_iter().find(|c| c.mv == Some(m));
+
+        Some(board.transition(m))
+    }
+
+    fn opponent_played<B: Board>(&mut self, board: &DisplayableBoard<B>, m: Move) {
+        self.tree = self.tree.take()
+            .and_then(|tree| tree.children.into_iter().find(|c| c.mv == Some(m)))
+            .filter(|tree| tree.hash == board.hash());
+    }
+
+    fn set_time_control(&mut self, control: TimeControl) {
+        self.config.time_control = control;
+    }
+
+    fn book(&self) -> Option<&BookConfig> {
+        self.config.book.as_ref()
+    }
+
+    fn init_stats(&self, stats_folder: String) -> Stats {
+        Stats::new("Monte Carlo tree search", None, None, stats_folder, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::display::DisplayableBoard;
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::fen::parse_fen;
+    use crate::game_engine::lan::parse_lan;
+    use crate::solver::Solver;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::mcts::{Mcts, MctsConfig};
+    use crate::stats::StatsEntry;
+    use std::time::Duration;
+
+    fn solver(playouts: u64) -> Mcts {
+        Mcts::with_config(MctsConfig {
+            playouts: Some(playouts),
+            tablebases: false,
+            seed: Some(1),
+            ..MctsConfig::default()
+        })
+    }
+
+    fn board(fen: &str) -> DisplayableBoard<BasicBoard> {
+        DisplayableBoard::new(parse_fen(fen, Duration::from_secs(600)).unwrap())
+    }
+
+    #[test]
+    fn test_mate_in_one() {
+        let board = board("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        let mut mcts = solver(2000);
+
+        let played = mcts.make_move_impl(board.clone(), &SearchLimits::default(), &mut StatsEntry::new(None)).unwrap();
+        assert_eq!(played.last_move(), parse_lan(&board, "a1a8"));
+    }
+
+    #[test]
+    fn test_playouts() {
+        let board = board("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut mcts = solver(300);
+        let mut stats = StatsEntry::new(None);
+
+        mcts.make_move_impl(board, &SearchLimits::default(), &mut stats).unwrap();
+        assert_eq!(stats.get_custom_int_entry("playouts"), 300);
+
+        // what is left of the tree is the part below the move that was played
+        let tree = mcts.tree.as_ref().unwrap();
+        assert!(tree.visits > 0 && tree.visits <= 300);
+        assert!(tree.depth >= 1);
+    }
+
+    #[test]
+    fn test_reuse_tree() {
+        let board = board("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut mcts = solver(500);
+
+        let played = mcts.make_move_impl(board, &SearchLimits::default(), &mut StatsEntry::new(None)).unwrap();
+
+        // the reply that was searched the most
+        let reply = mcts.tree.as_ref().unwrap().most_visited().unwrap();
+        let (m, visits) = (reply.mv.unwrap(), reply.visits);
+        assert!(visits > 0);
+
+        let replied = played.transition(m);
+        mcts.opponent_played(&replied, m);
+        assert_eq!(mcts.tree.as_ref().map(|tree| tree.visits), Some(visits));
+
+        let mut stats = StatsEntry::new(None);
+        mcts.make_move_impl(replied, &SearchLimits::default(), &mut stats).unwrap();
+        assert_eq!(stats.get_custom_int_entry("reused_playouts"), visits as i64);
+    }
+}
@@ -2,34 +2,39 @@ use crate::game_engine::board::Board;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use crate::game_engine::color::Color::{White, Black};
-use crate::solver::Solver;
+use crate::solver::{tablebase_move, Solver};
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::solver::limits::SearchLimits;
+use crate::solver::contempt::{Contempt, terminal_score};
+use std::time::SystemTime;
 
 pub struct Minimax {
     search_depth: u64,
+    contempt: Contempt,
+    /// The score of a draw in the current search, from the contempt at its root
+    draw_score: f64,
 }
 
 impl Minimax {
     pub fn new(search_depth: u64) -> Self {
+        Self::with_contempt(search_depth, Contempt::default())
+    }
+
+    pub fn with_contempt(search_depth: u64, contempt: Contempt) -> Self {
         Self {
-            search_depth
+            search_depth,
+            contempt,
+            draw_score: 0.0,
         }
     }
 
-    pub fn mini_max(board: &impl Board, depth: u64, stats: &mut StatsEntry) -> f64 {
+    pub fn mini_max(&self, board: &impl Board, depth: u64, stats: &mut StatsEntry) -> f64 {
         stats.seen_state();
 
         if depth == 0 || board.is_terminal().is_some() {
-            let terminal = board.is_terminal();
-            if terminal.is_some() {
-                return if terminal == Some(Black) {
-                    f64::NEG_INFINITY
-                } else if terminal == Some(White) {
-                    f64::INFINITY
-                } else {
-                    0.0
-                };
+            if let Some(winner) = board.is_terminal() {
+                return terminal_score(winner, self.draw_score);
             }
             return board.heuristic();
         }
@@ -38,14 +43,14 @@ impl Minimax {
             let mut value = f64::NEG_INFINITY;
             for m in board.all_moves() {
                 let new_board = board.transition(m);
-                value = value.max(Self::mini_max(&new_board, depth - 1, stats));
+                value = value.max(self.mini_max(&new_board, depth - 1, stats));
             }
             return value;
         } else {
             let mut value = f64::INFINITY;
             for m in board.all_moves() {
                 let new_board = board.transition(m);
-                value = value.min(Self::mini_max(&new_board, depth - 1, stats));
+                value = value.min(self.mini_max(&new_board, depth - 1, stats));
             }
             return value;
         }
@@ -53,16 +58,28 @@ impl Minimax {
 }
 
 impl Solver for Minimax {
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mut rng = thread_rng();
+        let start = SystemTime::now();
+        let search_depth = limits.fixed_depth(self.search_depth);
+        self.draw_score = self.contempt.draw_score(&board, board.current_player());
+
+        if let Some((m, score)) = tablebase_move(&board, self.draw_score, stats) {
+            stats.evaluation(score);
+            return Some(board.transition(m));
+        }
 
         let mut best_moves = Vec::new();
 
         if board.current_player() == White {
             let mut best = f64::NEG_INFINITY;
             for m in board.all_moves() {
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
                 let new_board = board.transition(m);
-                let score = Self::mini_max(&new_board, self.search_depth, stats);
+                let score = self.mini_max(&new_board, search_depth, stats);
                 if score > best {
                     best = score;
                     best_moves = Vec::new();
@@ -78,8 +95,12 @@ impl Solver for Minimax {
         if board.current_player() == Black {
             let mut best = f64::INFINITY;
             for m in board.all_moves() {
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
                 let new_board = board.transition(m);
-                let score = Self::mini_max(&new_board, self.search_depth, stats);
+                let score = self.mini_max(&new_board, search_depth, stats);
                 if score < best {
                     best = score;
                     best_moves = Vec::new();
@@ -1,6 +1,11 @@
 use crate::game_engine::board::Board;
 use crate::stats::{Stats, StatsEntry};
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::game_engine::chess_move::Move;
+use crate::solver::limits::SearchLimits;
+use crate::solver::book::BookConfig;
+use crate::solver::time_manager::TimeControl;
+use crate::tablebase::{self, syzygy};
 
 pub mod random_play;
 pub mod minimax;
@@ -13,6 +18,19 @@ pub mod fallback_player;
 pub mod player;
 pub mod iterative_deepening;
 pub mod id_quiescence;
+pub mod config;
+pub mod history;
+pub mod time_manager;
+pub mod limits;
+pub mod control;
+pub mod extensions;
+pub mod qsearch_pruning;
+pub mod mcts;
+pub mod proof_number;
+pub mod skill;
+pub mod contempt;
+pub mod book;
+pub mod book_builder;
 
 pub trait Solver {
     /// If a solver sets this to true, it is responsible
@@ -20,15 +38,105 @@ pub trait Solver {
     const PRINT_OWN_BOARD: bool = false;
 
     fn make_move<B: Board>(&mut self, board: DisplayableBoard<B>, stats: Stats) -> Option<DisplayableBoard<B>> {
+        self.make_move_with_limits(board, &SearchLimits::default(), stats)
+    }
+
+    fn make_move_with_limits<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: Stats) -> Option<DisplayableBoard<B>> {
         let mut entry = stats.new_entry();
-        let res = self.make_move_impl(board, &mut entry);
+        // the book knows nothing about mates, and an infinite search is for analysis
+        let book = if limits.mate.is_some() || limits.infinite { None } else { self.book() };
+        let res = match book.and_then(|book| book.probe(&board, &mut entry)) {
+            Some(m) => Some(board.transition(m)),
+            None => self.make_move_impl(board, limits, &mut entry),
+        };
         stats.finish_entry(entry);
         res
     }
 
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>>;
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>>;
+
+    /// Called after this solver made a move, while the opponent is thinking about `board`.
+    /// Solvers can use this to search in the background (pondering), but must not block.
+    fn opponent_thinking<B: Board + 'static>(&mut self, _board: &DisplayableBoard<B>) {}
 
+    /// Called when the opponent played `m`, which resulted in `board`.
+    /// This is always before the next call to `make_move`.
+    fn opponent_played<B: Board>(&mut self, _board: &DisplayableBoard<B>, _m: Move) {}
+
+    /// Changes how the clock works for the next moves, for front ends that only learn the
+    /// increment and the moves until the next time control from their GUI.
+    /// Solvers that don't manage their time ignore it.
+    fn set_time_control(&mut self, _control: TimeControl) {}
+
+    /// The opening book this solver plays from before it searches, if any.
+    fn book(&self) -> Option<&BookConfig> {
+        None
+    }
 
     fn init_stats(&self, stats_folder: String) -> Stats;
 }
 
+/// The best move of the installed endgame tablebases, or else of the Syzygy tables, with its score,
+/// when `board` is in them. Draws score `draw_score`. Searching solvers play it at the root instead of searching.
+/// The tables of the `generator` know the fastest mate, Syzygy tables the move that keeps the result
+/// with the fifty-move rule.
+pub fn tablebase_move(board: &impl Board, draw_score: f64, stats: &mut StatsEntry) -> Option<(Move, f64)> {
+    if let Some((m, probe)) = tablebase::best_move(board) {
+        stats.custom_int_entry_add("tablebase_moves");
+        return Some((m, probe.score(board.current_player(), draw_score)));
+    }
+
+    let (m, wdl) = syzygy::best_move(board)?;
+    stats.custom_int_entry_add("syzygy_moves");
+    Some((m, wdl.score(board.current_player(), draw_score)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::display::DisplayableBoard;
+    use crate::game_engine::fen::parse_fen;
+    use crate::solver::Solver;
+    use crate::solver::alpha_beta::AlphaBeta;
+    use crate::solver::alpha_beta_transp::AlphaBetaTransp;
+    use crate::solver::iterative_deepening::IterativeDeepening;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::minimax::Minimax;
+    use crate::solver::proof_number::ProofNumberSearch;
+    use crate::solver::quiescence::Quiescence;
+    use crate::stats::StatsEntry;
+    use crate::tablebase::{self, Tablebases};
+    use crate::tablebase::generator::generate_all;
+    use crate::tablebase::position::Material;
+    use std::time::Duration;
+
+    #[test]
+    fn test_tablebase_moves() {
+        let mut tablebases = Tablebases::new();
+        generate_all(&Material::parse("KQvK").unwrap(), &mut tablebases, &mut |_| {});
+        tablebase::install_for_test(Some(tablebases));
+
+        // the mate is too far away to be found at depth 1
+        let board = DisplayableBoard::new(parse_fen("8/8/8/4k3/8/8/8/3QK3 w - - 0 1", Duration::from_secs(600)).unwrap());
+        let (best, _) = tablebase::best_move(&board).unwrap();
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..SearchLimits::default()
+        };
+
+        let mut stats = StatsEntry::new(None);
+        let played = vec![
+            IterativeDeepening::new().make_move_impl(board.clone(), &limits, &mut stats),
+            AlphaBeta::new(1).make_move_impl(board.clone(), &limits, &mut stats),
+            AlphaBetaTransp::new(1, 1).make_move_impl(board.clone(), &limits, &mut stats),
+            Quiescence::new(1).make_move_impl(board.clone(), &limits, &mut stats),
+            Minimax::new(1).make_move_impl(board.clone(), &limits, &mut stats),
+            ProofNumberSearch::new().make_move_impl(board.clone(), &limits, &mut stats),
+        ];
+        tablebase::install_for_test(None);
+
+        for board in played {
+            assert_eq!(board.unwrap().last_move(), Some(best));
+        }
+    }
+}
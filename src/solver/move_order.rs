@@ -1,6 +1,18 @@
-use crate::game_engine::chess_move::Move;
+use crate::game_engine::chess_move::{Move, Extra};
 use crate::game_engine::board::Board;
 use crate::game_engine::color::Color;
+use crate::game_engine::piece::Piece;
+use crate::game_engine::see::{static_exchange_evaluation, piece_value};
+use crate::solver::history::MoveHistory;
+
+/// The move stored for this position by an earlier search is tried first
+const HASH_MOVE_SCORE: i64 = 100_000_000;
+/// Captures that don't lose material, ordered by MVV-LVA
+const GOOD_CAPTURE_SCORE: i64 = 50_000_000;
+/// Promotions come right after the good captures, ordered by the piece promoted to
+const PROMOTION_SCORE: i64 = 40_000_000;
+/// Captures that lose material according to SEE are searched after all quiet moves
+const BAD_CAPTURE_SCORE: i64 = -50_000_000;
 
 #[derive(Debug)]
 pub struct MoveRes<B>{
@@ -8,17 +20,114 @@ pub struct MoveRes<B>{
     pub mv: Move,
 }
 
-pub fn order_moves(moves: Vec<Move>, board: & impl Board) -> Vec<MoveRes<impl Board>> {
-    let mut orderd = Vec::new();
-    for m in moves {
-        orderd.push(MoveRes {board:board.transition(m), mv: m});
+/// Moves in the order they should be searched. Moves are only scored up front:
+/// the best remaining one is selected every time the next move is needed, and its
+/// board is only created at that point. When a search cuts off after the first few moves,
+/// the rest is never sorted or transitioned to.
+pub struct OrderedMoves<'a, B> {
+    board: &'a B,
+    moves: Vec<(Move, i64)>,
+    next: usize,
+}
+
+impl<'a, B: Board> OrderedMoves<'a, B> {
+    /// Selects the remaining move with the highest score (the first one on ties).
+    fn pick(&mut self) -> Option<Move> {
+        if self.next >= self.moves.len() {
+            return None;
+        }
+
+        let mut best = self.next;
+        for i in self.next + 1..self.moves.len() {
+            if self.moves[i].1 > self.moves[best].1 {
+                best = i;
+            }
+        }
+
+        self.moves.swap(self.next, best);
+        self.next += 1;
+
+        Some(self.moves[self.next - 1].0)
+    }
+
+    /// The remaining moves in order, without creating their boards. For searches that
+    /// prune some moves before they need the board after them.
+    pub fn without_boards(mut self) -> impl Iterator<Item = Move> + 'a {
+        std::iter::from_fn(move || self.pick())
     }
 
-    if board.current_player() == Color::White {
-        orderd.sort_by_cached_key(|a| -a.board.get_material_score());
+    /// All remaining moves in order, without creating their boards.
+    pub fn into_moves(mut self) -> Vec<Move> {
+        let mut res = Vec::new();
+        while let Some(m) = self.pick() {
+            res.push(m);
+        }
+        res
+    }
+}
+
+impl<'a, B: Board> Iterator for OrderedMoves<'a, B> {
+    type Item = MoveRes<B>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mv = self.pick()?;
+        Some(MoveRes { board: self.board.transition(mv), mv })
+    }
+}
+
+/// Most valuable victim, least valuable attacker.
+fn mvv_lva(board: &impl Board, m: Move) -> i64 {
+    let victim = if m.extra == Extra::EnPassantCaptures {
+        Piece::WhitePawn
     } else {
-        orderd.sort_by_cached_key(|a| a.board.get_material_score());
+        board.piece_at(m.to)
+    };
+
+    (10 * piece_value(victim) - piece_value(board.piece_at(m.from))) as i64
+}
+
+/// Scores a move for ordering, higher is searched earlier.
+fn score_move(board: &impl Board, m: Move, hash_move: Option<Move>, quiet_score: impl Fn(Move) -> i64) -> i64 {
+    if hash_move == Some(m) {
+        return HASH_MOVE_SCORE;
+    }
+
+    if m.extra.is_capturing() {
+        let base = if static_exchange_evaluation(board, m) >= 0 {
+            GOOD_CAPTURE_SCORE
+        } else {
+            BAD_CAPTURE_SCORE
+        };
+        return base + mvv_lva(board, m);
+    }
+
+    if let Some(promotion) = m.extra.promotion_of_color(Color::White) {
+        return PROMOTION_SCORE + piece_value(promotion) as i64;
     }
 
-    orderd
-}
\ No newline at end of file
+    quiet_score(m)
+}
+
+pub fn order_moves<B: Board>(moves: Vec<Move>, board: &B) -> OrderedMoves<'_, B> {
+    order_moves_with_history(moves, board, None, None, 0, None)
+}
+
+/// Orders moves by hash move first, then captures by SEE and MVV-LVA, and then promotions.
+/// When a history is given, quiet moves are ordered with the killer, counter move and history
+/// heuristics, otherwise they stay in the order they were generated in.
+pub fn order_moves_with_history<'a, B: Board>(moves: Vec<Move>, board: &'a B, hash_move: Option<Move>, history: Option<&MoveHistory>, ply: usize, previous: Option<Move>) -> OrderedMoves<'a, B> {
+    let moves = moves.into_iter()
+        .map(|m| {
+            let score = score_move(board, m, hash_move, |m| {
+                history.map_or(0, |history| history.quiet_score(board, m, ply, previous))
+            });
+            (m, score)
+        })
+        .collect();
+
+    OrderedMoves {
+        board,
+        moves,
+        next: 0,
+    }
+}
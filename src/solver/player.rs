@@ -5,6 +5,7 @@ use crate::game_engine::board::Board;
 use crossterm::terminal::{enable_raw_mode, disable_raw_mode};
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::solver::limits::SearchLimits;
 
 pub struct Player {
     ansi: AnsiPlayer,
@@ -23,13 +24,13 @@ impl Player {
 impl Solver for Player {
     const PRINT_OWN_BOARD: bool = true;
 
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         if enable_raw_mode().is_ok() {
             disable_raw_mode().unwrap();
-            self.ansi.make_move_impl(board, stats)
+            self.ansi.make_move_impl(board, limits, stats)
         } else {
             println!("using fallback");
-            self.fallback.make_move_impl(board, stats)
+            self.fallback.make_move_impl(board, limits, stats)
         }
     }
 
@@ -0,0 +1,481 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::board::display::DisplayableBoard;
+use crate::game_engine::chess_move::{Move, Extra};
+use crate::game_engine::color::Color;
+use crate::game_engine::color::Color::White;
+use crate::game_engine::king_check::king_check;
+use crate::solver::{tablebase_move, Solver};
+use crate::solver::limits::SearchLimits;
+use crate::solver::contempt::{Contempt, terminal_score};
+use crate::solver::time_manager::{TimeControl, TimeManager};
+use crate::stats::{StatsEntry, Stats};
+use rand::seq::IteratorRandom;
+use rand::thread_rng;
+use std::mem;
+
+/// Proof and disproof numbers are infinite for nodes that can't be proven or disproven anymore.
+const INFINITE: u64 = u64::MAX;
+
+#[derive(Clone, Debug)]
+pub struct ProofNumberConfig {
+    /// The size of the search tree in megabytes. The search gives up when it's full.
+    pub memory_limit: u64,
+    /// The search gives up after expanding this many positions
+    pub node_limit: u64,
+    /// Only look for mates in at most this many moves
+    pub max_moves: Option<u64>,
+    /// How much the engine dislikes draws, when there is no mate
+    pub contempt: Contempt,
+    pub time_control: TimeControl,
+}
+
+impl Default for ProofNumberConfig {
+    fn default() -> Self {
+        Self {
+            memory_limit: 64,
+            node_limit: 1_000_000,
+            max_moves: None,
+            contempt: Contempt::default(),
+            time_control: TimeControl::default(),
+        }
+    }
+}
+
+/// The outcome of a mate search.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MateResult {
+    /// The attacker can force mate. Contains the mating line, in which the defender
+    /// delays the mate as long as the proof allows.
+    Mate(Vec<Move>),
+    /// There is no forced mate, within `max_moves` if it was set
+    NoMate,
+    /// A limit was hit before the search could decide
+    Unknown,
+}
+
+struct PnNode {
+    /// The move that led here. The root's move is never used.
+    mv: Move,
+    parent: u32,
+    /// Children are stored next to each other, starting at `first_child`
+    first_child: u32,
+    children: u32,
+    expanded: bool,
+    /// Plies from the root
+    depth: u32,
+    hash: u64,
+
+    proof: u64,
+    disproof: u64,
+}
+
+/// The tree of a proof-number search, stored in one vector to keep it compact.
+struct PnTree {
+    nodes: Vec<PnNode>,
+    attacker: Color,
+    /// Whether the attacker is to move at the root
+    root_attacking: bool,
+    /// Positions this many plies deep that aren't mate disprove the mate
+    max_plies: Option<u64>,
+}
+
+impl PnTree {
+    fn new(board: &impl Board, attacker: Color, max_plies: Option<u64>) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            attacker,
+            root_attacking: board.current_player() == attacker,
+            max_plies,
+        };
+
+        let (proof, disproof) = tree.evaluate(board, 0, None);
+        tree.nodes.push(PnNode {
+            mv: Move::new((0, 0).into(), (0, 0).into(), Extra::Quiet),
+            parent: 0,
+            first_child: 0,
+            children: 0,
+            expanded: false,
+            depth: 0,
+            hash: board.hash(),
+
+            proof,
+            disproof,
+        });
+
+        tree
+    }
+
+    /// Whether the attacker is to move in the node, which makes it an OR node.
+    fn attacking(&self, node: usize) -> bool {
+        self.nodes[node].depth.is_multiple_of(2) == self.root_attacking
+    }
+
+    /// Whether the position `hash`, which would be a child of `parent`, already occurred on the path to it.
+    /// Repetitions are draws, so they disprove the mate.
+    fn repeats(&self, parent: Option<usize>, hash: u64) -> bool {
+        let mut current = parent;
+        while let Some(node) = current {
+            if self.nodes[node].hash == hash {
+                return true;
+            }
+            current = if node == 0 { None } else { Some(self.nodes[node].parent as usize) };
+        }
+        false
+    }
+
+    /// The initial proof and disproof numbers of a new node at `depth`.
+    fn evaluate(&self, board: &impl Board, depth: u64, parent: Option<usize>) -> (u64, u64) {
+        if board.all_moves().is_empty() {
+            // mate by the attacker proves the node, everything else disproves it
+            return if board.current_player() != self.attacker && king_check(board, board.current_player()) {
+                (0, INFINITE)
+            } else {
+                (INFINITE, 0)
+            };
+        }
+
+        let too_deep = self.max_plies.is_some_and(|max| depth >= max);
+        if too_deep || self.repeats(parent, board.hash()) {
+            return (INFINITE, 0);
+        }
+
+        (1, 1)
+    }
+
+    fn children(&self, node: usize) -> std::ops::Range<usize> {
+        let first = self.nodes[node].first_child as usize;
+        first..first + self.nodes[node].children as usize
+    }
+
+    /// Walks down to the most proving node, transitioning `board` along the way.
+    fn most_proving<B: Board>(&self, mut board: B) -> (usize, B) {
+        let mut node = 0;
+
+        while self.nodes[node].expanded {
+            let attacking = self.attacking(node);
+            node = self.children(node)
+                .min_by_key(|&c| if attacking { self.nodes[c].proof } else { self.nodes[c].disproof })
+                .expect("expanded nodes that aren't solved have children");
+            board = board.transition(self.nodes[node].mv);
+        }
+
+        (node, board)
+    }
+
+    fn expand(&mut self, node: usize, board: &impl Board) {
+        let depth = self.nodes[node].depth + 1;
+        let first_child = self.nodes.len() as u32;
+
+        for m in board.all_moves() {
+            let child = board.transition(m);
+            let (proof, disproof) = self.evaluate(&child, depth as u64, Some(node));
+
+            self.nodes.push(PnNode {
+                mv: m,
+                parent: node as u32,
+                first_child: 0,
+                children: 0,
+                expanded: false,
+                depth,
+                hash: child.hash(),
+
+                proof,
+                disproof,
+            });
+        }
+
+        let children = self.nodes.len() as u32 - first_child;
+        let n = &mut self.nodes[node];
+        n.first_child = first_child;
+        n.children = children;
+        n.expanded = true;
+    }
+
+    /// Recomputes the proof and disproof numbers from `node` up to the root.
+    fn update_ancestors(&mut self, mut node: usize) {
+        loop {
+            let children = self.children(node);
+            let proofs = children.clone().map(|c| self.nodes[c].proof);
+            let disproofs = children.map(|c| self.nodes[c].disproof);
+
+            let (proof, disproof) = if self.attacking(node) {
+                (proofs.min().unwrap_or(INFINITE), disproofs.fold(0, u64::saturating_add))
+            } else {
+                (proofs.fold(0, u64::saturating_add), disproofs.min().unwrap_or(INFINITE))
+            };
+
+            let n = &mut self.nodes[node];
+            if n.proof == proof && n.disproof == disproof {
+                return;
+            }
+            n.proof = proof;
+            n.disproof = disproof;
+
+            if node == 0 {
+                return;
+            }
+            node = n.parent as usize;
+        }
+    }
+
+    /// The length in plies of the mate the proven `node` leads to, with the attacker
+    /// mating as fast as possible and the defender delaying as long as possible.
+    fn mate_length(&self, node: usize) -> u64 {
+        let proven = self.children(node).filter(|&c| self.nodes[c].proof == 0);
+        let lengths = proven.map(|c| self.mate_length(c) + 1);
+
+        if self.attacking(node) {
+            lengths.min().unwrap_or(0)
+        } else {
+            lengths.max().unwrap_or(0)
+        }
+    }
+
+    fn mating_line(&self) -> Vec<Move> {
+        let mut line = Vec::new();
+        let mut node = 0;
+
+        while self.nodes[node].expanded {
+            let proven = self.children(node).filter(|&c| self.nodes[c].proof == 0);
+            let next = if self.attacking(node) {
+                proven.min_by_key(|&c| self.mate_length(c))
+            } else {
+                proven.max_by_key(|&c| self.mate_length(c))
+            };
+
+            match next {
+                Some(c) => {
+                    line.push(self.nodes[c].mv);
+                    node = c;
+                }
+                None => break,
+            }
+        }
+
+        line
+    }
+}
+
+/// What is left of the limits of a search, shared by the searches for shorter mates.
+struct Budget<'a> {
+    time: TimeManager,
+    limits: &'a SearchLimits,
+    /// Expansions that may still be done
+    nodes: u64,
+}
+
+impl Budget<'_> {
+    fn exhausted(&self) -> bool {
+        self.nodes == 0 || !self.time.should_start_iteration() || self.limits.stopped()
+    }
+}
+
+/// Proves or disproves forced mates with proof-number search, instead of estimating
+/// positions with the heuristic. Best suited for puzzles and endgames.
+pub struct ProofNumberSearch {
+    config: ProofNumberConfig,
+}
+
+impl ProofNumberSearch {
+    pub fn new() -> Self {
+        Self::with_config(ProofNumberConfig::default())
+    }
+
+    pub fn with_config(config: ProofNumberConfig) -> Self {
+        Self {
+            config,
+        }
+    }
+
+    /// Searches for a forced mate by `attacker` in `board`, where either side may be to move.
+    /// The `nodes`, `mate`, `move_time` and `stop` limits override the configuration.
+    ///
+    /// Proof-number search finds a mate, not the shortest one, so every mate that is found
+    /// is searched again for a shorter one while the limits allow it.
+    pub fn search<B: Board>(&self, board: &B, attacker: Color, limits: &SearchLimits, stats: &mut StatsEntry) -> MateResult {
+        let remaining_time = if board.current_player() == White {
+            board.get_clock()[0]
+        } else {
+            board.get_clock()[1]
+        };
+        let mut budget = Budget {
+            time: limits.time_manager(remaining_time, &self.config.time_control),
+            limits,
+            nodes: limits.nodes.unwrap_or(self.config.node_limit),
+        };
+
+        // the attacker's n-th move ends at ply 2n - 1, or at ply 2n when the defender is to move at the root
+        let defender_first = if board.current_player() == attacker { 0 } else { 1 };
+        let max_plies = |moves: u64| (2 * moves).saturating_sub(1) + defender_first;
+
+        let mut result = self.prove(board, attacker, limits.mate.or(self.config.max_moves).map(max_plies), &mut budget, stats);
+
+        while let MateResult::Mate(line) = &result {
+            let moves = (line.len() as u64).saturating_sub(defender_first).div_ceil(2);
+            if moves <= 1 {
+                break;
+            }
+
+            match self.prove(board, attacker, Some(max_plies(moves - 1)), &mut budget, stats) {
+                shorter @ MateResult::Mate(_) => result = shorter,
+                _ => break,
+            }
+        }
+
+        result
+    }
+
+    /// One proof-number search, which spends expansions from `budget`.
+    fn prove<B: Board>(&self, board: &B, attacker: Color, max_plies: Option<u64>, budget: &mut Budget, stats: &mut StatsEntry) -> MateResult {
+        let max_nodes = (self.config.memory_limit * 1024 * 1024) as usize / mem::size_of::<PnNode>();
+        let mut tree = PnTree::new(board, attacker, max_plies);
+
+        while tree.nodes[0].proof != 0 && tree.nodes[0].disproof != 0 {
+            if budget.exhausted() || tree.nodes.len() >= max_nodes {
+                stats.custom_int_entry("proof_number_nodes", tree.nodes.len() as i64);
+                return MateResult::Unknown;
+            }
+
+            let (node, node_board) = tree.most_proving(board.clone());
+            tree.expand(node, &node_board);
+            tree.update_ancestors(node);
+
+            budget.nodes -= 1;
+            stats.seen_state();
+        }
+
+        stats.custom_int_entry("proof_number_nodes", tree.nodes.len() as i64);
+
+        if tree.nodes[0].proof == 0 {
+            MateResult::Mate(tree.mating_line())
+        } else {
+            MateResult::NoMate
+        }
+    }
+}
+
+impl Solver for ProofNumberSearch {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+        let attacker = board.current_player();
+        let sign = if attacker == White { 1.0 } else { -1.0 };
+        let draw_score = self.config.contempt.draw_score(&board, attacker);
+
+        if let Some((m, score)) = tablebase_move(&board, draw_score, stats) {
+            stats.evaluation(score);
+            return Some(board.transition(m));
+        }
+
+        let m = match self.search(&board, attacker, limits, stats) {
+            MateResult::Mate(line) => {
+                stats.evaluation(sign * f64::INFINITY);
+                stats.custom_int_entry("mate_in", line.len().div_ceil(2) as i64);
+                line[0]
+            }
+            // without a mate, play the move that looks best
+            _ => {
+                stats.evaluation(sign * board.heuristic());
+                let score = |m| {
+                    let child = board.transition(m);
+                    sign * match child.is_terminal() {
+                        Some(winner) => terminal_score(winner, draw_score),
+                        None => child.heuristic(),
+                    }
+                };
+                let moves = board.all_moves();
+                let best = moves.iter()
+                    .map(|&m| score(m))
+                    .fold(f64::NEG_INFINITY, f64::max);
+
+                moves.into_iter()
+                    .filter(|&m| score(m) == best)
+                    .choose(&mut thread_rng())?
+            }
+        };
+
+        Some(board.transition(m))
+    }
+
+    fn set_time_control(&mut self, control: TimeControl) {
+        self.config.time_control = control;
+    }
+
+    fn init_stats(&self, stats_folder: String) -> Stats {
+        Stats::new("Proof-number search", None, None, stats_folder, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::color::Color;
+    use crate::game_engine::fen::parse_fen;
+    use crate::game_engine::lan::to_lan;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::proof_number::{MateResult, ProofNumberConfig, ProofNumberSearch};
+    use crate::stats::StatsEntry;
+    use std::time::Duration;
+
+    fn search(solver: &ProofNumberSearch, board: &BasicBoard, limits: &SearchLimits) -> MateResult {
+        solver.search(board, Color::White, limits, &mut StatsEntry::new(None))
+    }
+
+    /// The mating line of `fen` in long algebraic notation, after checking that it ends in mate.
+    fn mate(fen: &str) -> Vec<String> {
+        let board = parse_fen(fen, Duration::from_secs(600)).unwrap();
+        let line = match search(&ProofNumberSearch::new(), &board, &SearchLimits::default()) {
+            MateResult::Mate(line) => line,
+            result => panic!("no mate in {}: {:?}", fen, result),
+        };
+
+        let end = line.iter().fold(board, |board, &m| board.transition(m));
+        assert_eq!(end.is_terminal(), Some(Color::White));
+        line.into_iter().map(to_lan).collect()
+    }
+
+    #[test]
+    fn test_mate() {
+        assert_eq!(mate("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1"), ["a1a6", "b7a6", "b6b7"]);
+        assert_eq!(mate("7k/8/8/4K3/8/8/8/R7 w - - 0 1").len(), 5);
+    }
+
+    #[test]
+    fn test_no_mate() {
+        let board = parse_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Duration::from_secs(600)).unwrap();
+        let solver = ProofNumberSearch::with_config(ProofNumberConfig {
+            max_moves: Some(2),
+            ..ProofNumberConfig::default()
+        });
+        assert_eq!(search(&solver, &board, &SearchLimits::default()), MateResult::NoMate);
+
+        // the mate in 3 isn't a mate in 2
+        let board = parse_fen("7k/8/8/4K3/8/8/8/R7 w - - 0 1", Duration::from_secs(600)).unwrap();
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..SearchLimits::default()
+        };
+        assert_eq!(search(&ProofNumberSearch::new(), &board, &limits), MateResult::NoMate);
+    }
+
+    #[test]
+    fn test_limits() {
+        let board = parse_fen("7k/8/8/4K3/8/8/8/R7 w - - 0 1", Duration::from_secs(600)).unwrap();
+
+        let limits = SearchLimits {
+            nodes: Some(10),
+            ..SearchLimits::default()
+        };
+        assert_eq!(search(&ProofNumberSearch::new(), &board, &limits), MateResult::Unknown);
+
+        let solver = ProofNumberSearch::with_config(ProofNumberConfig {
+            node_limit: 10,
+            ..ProofNumberConfig::default()
+        });
+        assert_eq!(search(&solver, &board, &SearchLimits::default()), MateResult::Unknown);
+
+        let solver = ProofNumberSearch::with_config(ProofNumberConfig {
+            memory_limit: 0,
+            ..ProofNumberConfig::default()
+        });
+        assert_eq!(search(&solver, &board, &SearchLimits::default()), MateResult::Unknown);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::{Move, Extra};
+use crate::game_engine::color::Color::White;
+use crate::game_engine::piece::Piece;
+use crate::game_engine::see::{static_exchange_evaluation, piece_value};
+use crate::solver::config::SearchConfig;
+use crate::stats::StatsEntry;
+
+/// The most material move `m` can win: the captured piece, plus what a promotion adds.
+fn material_gain(board: &impl Board, m: Move) -> i32 {
+    let captured = if m.extra == Extra::EnPassantCaptures {
+        piece_value(Piece::WhitePawn)
+    } else {
+        piece_value(board.piece_at(m.to))
+    };
+
+    let promotion = m.extra
+        .promotion_of_color(board.current_player())
+        .map_or(0, |p| piece_value(p) - piece_value(Piece::WhitePawn));
+
+    captured + promotion
+}
+
+/// Whether the quiescence search can skip move `m`, when not in check.
+///
+/// Delta pruning skips captures that can't bring the score back into the window, even when
+/// the captured piece is won for free. SEE pruning skips captures that lose material.
+/// `stand_pat` is the static evaluation of `board`, and `bound` is the side to move's own bound:
+/// alpha for white and beta for black.
+pub fn prune_capture(config: &SearchConfig, board: &impl Board, m: Move, stand_pat: f64, bound: f64, stats: &mut StatsEntry) -> bool {
+    if !m.extra.is_capturing() {
+        return false;
+    }
+
+    if config.delta_pruning && !bound.is_infinite() {
+        let best_case = (material_gain(board, m) as f64) + config.delta_margin;
+
+        let hopeless = if board.current_player() == White {
+            stand_pat + best_case <= bound
+        } else {
+            stand_pat - best_case >= bound
+        };
+
+        if hopeless {
+            stats.custom_int_entry_add("delta_pruned");
+            return true;
+        }
+    }
+
+    // promotions are worth searching even when the pawn is lost right after
+    if config.see_pruning && !m.extra.is_promotion() && static_exchange_evaluation(board, m) < 0 {
+        stats.custom_int_entry_add("see_pruned");
+        return true;
+    }
+
+    false
+}
@@ -2,20 +2,39 @@ use crate::game_engine::board::Board;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use crate::game_engine::color::Color::{White, Black};
-use crate::game_engine::chess_move::Move;
-use crate::solver::Solver;
+use crate::solver::{tablebase_move, Solver};
+use crate::solver::book::BookConfig;
 use crate::solver::move_order::order_moves;
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::solver::limits::SearchLimits;
+use crate::solver::config::SearchConfig;
+use crate::solver::contempt::terminal_score;
+use crate::solver::qsearch_pruning::prune_capture;
+use crate::solver::extensions::{Extensions, quiescence_moves};
+use crate::game_engine::king_check::king_check;
+use std::time::SystemTime;
 
 pub struct Quiescence {
     search_depth: u64,
+    config: SearchConfig,
+    extensions: Extensions,
+    /// The score of a draw in the current search, from the contempt at its root
+    draw_score: f64,
 }
 
 impl Quiescence {
     pub fn new(search_depth: u64) -> Self {
+        Self::with_config(search_depth, SearchConfig::default())
+    }
+
+    /// Only the extension, quiescence and contempt settings of `config` are used.
+    pub fn with_config(search_depth: u64, config: SearchConfig) -> Self {
         Self {
             search_depth,
+            config,
+            extensions: Extensions::new(),
+            draw_score: 0.0,
         }
     }
 
@@ -25,53 +44,61 @@ impl Quiescence {
         if depth == 0 {
             // return board.get_material_score();
             stats.custom_int_entry_sub("deep_nodes");
-            return Self::quiescense(self, board, a, b, stats);
+            return Self::quiescense(self, board, a, b, self.config.qsearch_checks, stats);
         }
 
-        if board.is_terminal().is_some() {
-            let terminal = board.is_terminal();
-            if terminal.is_some() {
-                return if terminal == Some(Black) {
-                    f64::NEG_INFINITY
-                } else if terminal == Some(White) {
-                    f64::INFINITY
-                } else {
-                    0.0
-                };
-            }
-            return board.heuristic();
+        if let Some(winner) = board.is_terminal() {
+            return terminal_score(winner, self.draw_score);
         }
 
-        if board.current_player() == White {
+        let moves = board.all_moves();
+        let in_check = king_check(board, board.current_player());
+        let extension = self.extensions.node(&self.config, in_check, moves.len(), stats);
+        let depth = depth + extension;
+
+        let value = if board.current_player() == White {
             let mut value = f64::NEG_INFINITY;
-            for move_res in order_moves(board.all_moves(), board){
+            for move_res in order_moves(moves, board){
                 // println!("{}",depth);
-                value = value.max(Self::mini_max_ab(self, &move_res.board, depth - 1, a, b, stats));
+                let push = self.extensions.pawn_push(&self.config, board, move_res.mv, stats);
+                value = value.max(Self::mini_max_ab(self, &move_res.board, depth - 1 + push, a, b, stats));
+                self.extensions.release(push);
                 a = a.max(value);
                 if a >= b {
                     break;
                 }
             }
-            return value;
+            value
         } else {
             let mut value = f64::INFINITY;
-            for move_res in order_moves(board.all_moves(), board) {
-                value = value.min(Self::mini_max_ab(self, &move_res.board, depth - 1, a, b, stats));
+            for move_res in order_moves(moves, board) {
+                let push = self.extensions.pawn_push(&self.config, board, move_res.mv, stats);
+                value = value.min(Self::mini_max_ab(self, &move_res.board, depth - 1 + push, a, b, stats));
+                self.extensions.release(push);
                 b = b.min(value);
                 if b <= a {
                     break;
                 }
             }
-            return value;
-        }
+            value
+        };
+
+        self.extensions.release(extension);
+        value
     }
 
-    pub fn quiescense(&mut self, board: &impl Board, mut a: f64, mut b: f64, stats: &mut StatsEntry) -> f64 {
+    /// Searches captures until the position is quiet. When in check all evasions are searched,
+    /// and with `checks` the quiet moves that give check are searched too.
+    pub fn quiescense(&mut self, board: &impl Board, mut a: f64, mut b: f64, checks: bool, stats: &mut StatsEntry) -> f64 {
         stats.custom_int_entry_add("deep_nodes");
 
+        let in_check = self.config.qsearch_check_evasions && king_check(board, board.current_player());
         let cur_score = board.heuristic();
 
-        if board.current_player() == White {
+        // when in check, standing pat isn't an option
+        if in_check {
+            stats.custom_int_entry_add("qsearch_evasions");
+        } else if board.current_player() == White {
             if cur_score >= b {
                 return b;
             }
@@ -87,29 +114,22 @@ impl Quiescence {
             }
         }
 
-        if board.is_terminal().is_some() {
-            let terminal = board.is_terminal();
-            if terminal.is_some() {
-                return if terminal == Some(Black) {
-                    f64::NEG_INFINITY
-                } else if terminal == Some(White) {
-                    f64::INFINITY
-                } else {
-                    0.0
-                };
-            }
-            return board.heuristic();
+        if let Some(winner) = board.is_terminal() {
+            return terminal_score(winner, self.draw_score);
         }
 
 
         if board.current_player() == White {
             let mut value = f64::NEG_INFINITY;
-            let moves: Vec<Move> = board.all_moves().into_iter().filter(|a| a.extra.is_capturing()).collect();
+            let moves = quiescence_moves(board, in_check, checks);
             if moves.len()==0{
                 return cur_score;
             }
             for move_res in order_moves(moves, board) {
-                value = value.max(Self::quiescense(self, &move_res.board, a, b, stats));
+                if !in_check && prune_capture(&self.config, board, move_res.mv, cur_score, a, stats) {
+                    continue;
+                }
+                value = value.max(Self::quiescense(self, &move_res.board, a, b, false, stats));
                 a = a.max(value);
                 if a >= b {
                     break;
@@ -118,12 +138,15 @@ impl Quiescence {
             return a;
         } else {
             let mut value = f64::INFINITY;
-            let moves: Vec<Move> = board.all_moves().into_iter().filter(|a| a.extra.is_capturing()).collect();
+            let moves = quiescence_moves(board, in_check, checks);
             if moves.len()==0{
                 return cur_score;
             }
             for move_res in order_moves(moves, board) {
-                value = value.min(Self::quiescense(self, &move_res.board, a, b, stats));
+                if !in_check && prune_capture(&self.config, board, move_res.mv, cur_score, b, stats) {
+                    continue;
+                }
+                value = value.min(Self::quiescense(self, &move_res.board, a, b, false, stats));
                 b = b.min(value);
                 if b <= a {
                     break;
@@ -135,8 +158,17 @@ impl Quiescence {
 }
 
 impl Solver for Quiescence {
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mut rng = thread_rng();
+        let start = SystemTime::now();
+        let search_depth = limits.fixed_depth(self.search_depth);
+        self.draw_score = self.config.contempt.draw_score(&board, board.current_player());
+        if self.config.tablebases {
+            if let Some((m, score)) = tablebase_move(&board, self.draw_score, stats) {
+                stats.evaluation(score);
+                return Some(board.transition(m));
+            }
+        }
 
         let mut best_moves = Vec::new();
 
@@ -145,7 +177,11 @@ impl Solver for Quiescence {
         if board.current_player() == White {
             best = f64::NEG_INFINITY;
             for move_res in order_moves(board.all_moves(), &board) {
-                let score = Self::mini_max_ab(self, &move_res.board, self.search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
+                let score = Self::mini_max_ab(self, &move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
                 if score > best {
                     best = score;
                     best_moves = Vec::new();
@@ -159,7 +195,11 @@ impl Solver for Quiescence {
         if board.current_player() == Black {
             best = f64::INFINITY;
             for move_res in order_moves(board.all_moves(), &board) {
-                let score = Self::mini_max_ab(self, &move_res.board, self.search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
+                if !best_moves.is_empty() && limits.exceeded(start, stats.states_seen()) {
+                    break;
+                }
+
+                let score = Self::mini_max_ab(self, &move_res.board, search_depth, f64::NEG_INFINITY, f64::INFINITY, stats);
                 if score < best {
                     best = score;
                     best_moves = Vec::new();
@@ -179,6 +219,10 @@ impl Solver for Quiescence {
         Some(new_state)
     }
 
+    fn book(&self) -> Option<&BookConfig> {
+        self.config.book.as_ref()
+    }
+
     fn init_stats(&self, stats_folder: String) -> Stats {
         Stats::new("Quiescence search", Some(self.search_depth), None, stats_folder, true)
     }
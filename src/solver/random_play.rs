@@ -4,6 +4,7 @@ use rand::thread_rng;
 use crate::solver::Solver;
 use crate::stats::{StatsEntry, Stats};
 use crate::game_engine::board::display::DisplayableBoard;
+use crate::solver::limits::SearchLimits;
 
 pub struct RandomPlay {
 }
@@ -15,7 +16,7 @@ impl RandomPlay {
 }
 
 impl Solver for RandomPlay {
-    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
+    fn make_move_impl<B: Board>(&mut self, board: DisplayableBoard<B>, _limits: &SearchLimits, stats: &mut StatsEntry) -> Option<DisplayableBoard<B>> {
         let mut rng = thread_rng();
 
         let m = board.all_moves().into_iter().choose(&mut rng)?;
@@ -0,0 +1,182 @@
+use crate::game_engine::chess_move::Move;
+use crate::game_engine::color::Color;
+use crate::game_engine::color::Color::White;
+use crate::solver::control::Analysis;
+use crate::solver::limits::SearchLimits;
+use crate::stats::StatsEntry;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub const MAX_LEVEL: u8 = 20;
+
+/// Weakens a solver in ways that look like human mistakes: it searches less deep,
+/// sometimes misjudges positions and doesn't always play the move it thinks is best.
+#[derive(Clone, Debug)]
+pub struct Skill {
+    /// Never search deeper than this
+    pub max_depth: Option<u64>,
+    /// Never search more positions than this for a move
+    pub max_nodes: Option<u64>,
+    /// The number of best moves to choose from
+    pub multi_pv: usize,
+    /// How much worse a move may be and still be played, in centipawns. Moves are chosen with
+    /// a chance proportional to `e^(-loss / temperature)`, so 0 always plays the best move.
+    pub temperature: f64,
+    /// The chance that the scores of the moves are misjudged before choosing
+    pub noise_chance: f64,
+    /// The most a misjudged score is off by, in centipawns
+    pub noise: f64,
+    /// With the same seed, the same positions get the same moves, as long as the search
+    /// is limited by depth or nodes rather than by time.
+    pub seed: Option<u64>,
+}
+
+impl Skill {
+    /// Level 0 is the weakest, `MAX_LEVEL` is full strength.
+    pub fn level(level: u8) -> Self {
+        let level = level.min(MAX_LEVEL);
+        if level == MAX_LEVEL {
+            return Self::full();
+        }
+
+        let weakness = (MAX_LEVEL - level) as f64;
+        Self {
+            max_depth: Some(1 + level as u64 / 2),
+            max_nodes: Some((200.0 * 1.5f64.powi(level as i32)) as u64),
+            multi_pv: 4,
+            temperature: 10.0 * weakness,
+            noise_chance: weakness / 40.0,
+            noise: 10.0 * weakness,
+            seed: None,
+        }
+    }
+
+    /// No weakening at all.
+    pub fn full() -> Self {
+        Self {
+            max_depth: None,
+            max_nodes: None,
+            multi_pv: 1,
+            temperature: 0.0,
+            noise_chance: 0.0,
+            noise: 0.0,
+            seed: None,
+        }
+    }
+
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// `limits`, with the caps of this skill level added.
+    pub fn limit(&self, limits: &SearchLimits) -> SearchLimits {
+        let min = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        SearchLimits {
+            depth: min(limits.depth, self.max_depth),
+            nodes: min(limits.nodes, self.max_nodes),
+            ..limits.clone()
+        }
+    }
+
+    /// Picks one of the moves of `analysis` for `player`, or `None` when it has no moves.
+    pub fn choose(&self, analysis: &Analysis, player: Color, rng: &mut StdRng, stats: &mut StatsEntry) -> Option<Move> {
+        let sign = if player == White { 1.0 } else { -1.0 };
+
+        let misjudged = self.noise > 0.0 && rng.gen_bool(self.noise_chance.clamp(0.0, 1.0));
+        let scores: Vec<f64> = analysis.lines.iter()
+            .map(|line| {
+                let noise = if misjudged { rng.gen_range(-self.noise..=self.noise) } else { 0.0 };
+                sign * line.score + noise
+            })
+            .collect();
+
+        let best = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        // mates and a temperature of zero leave no choice
+        let weights: Vec<f64> = scores.iter()
+            .map(|&score| {
+                if score == best {
+                    1.0
+                } else if self.temperature <= 0.0 || best.is_infinite() {
+                    0.0
+                } else {
+                    ((score - best) / self.temperature).exp()
+                }
+            })
+            .collect();
+
+        let mut pick = rng.gen_range(0.0..weights.iter().sum::<f64>().max(f64::MIN_POSITIVE));
+        let rank = weights.iter()
+            .position(|&w| {
+                pick -= w;
+                pick < 0.0
+            })
+            .unwrap_or(0);
+
+        if misjudged {
+            stats.custom_int_entry("skill_misjudged", 1);
+        }
+        stats.custom_int_entry("skill_rank", rank as i64 + 1);
+
+        analysis.lines.get(rank).and_then(|line| line.pv.first().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::display::DisplayableBoard;
+    use crate::game_engine::board::BasicBoard;
+    use crate::game_engine::chess_move::Move;
+    use crate::solver::Solver;
+    use crate::solver::config::SearchConfig;
+    use crate::solver::id_quiescence::IDQuiescence;
+    use crate::solver::limits::SearchLimits;
+    use crate::solver::skill::Skill;
+    use crate::stats::StatsEntry;
+    use std::time::Duration;
+
+    /// The moves of a game of a weakened solver against itself.
+    fn self_play(seed: u64, limits: &SearchLimits) -> Vec<Move> {
+        let mut solver = IDQuiescence::with_config(SearchConfig {
+            skill: Some(Skill {
+                seed: Some(seed),
+                ..Skill::level(4)
+            }),
+            tablebases: false,
+            ..SearchConfig::default()
+        });
+
+        let mut board = DisplayableBoard::new(BasicBoard::default_board(Duration::from_secs(600)));
+        let mut moves = Vec::new();
+        for _ in 0..12 {
+            board = match solver.make_move_impl(board, limits, &mut StatsEntry::new(None)) {
+                Some(board) => board,
+                None => break,
+            };
+            moves.push(board.last_move().unwrap());
+        }
+        moves
+    }
+
+    #[test]
+    fn test_seeded() {
+        let depth = SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        };
+        assert_eq!(self_play(3, &depth), self_play(3, &depth));
+
+        let nodes = SearchLimits {
+            nodes: Some(2000),
+            ..SearchLimits::default()
+        };
+        assert_eq!(self_play(5, &nodes), self_play(5, &nodes));
+    }
+}
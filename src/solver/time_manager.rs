@@ -0,0 +1,160 @@
+use crate::game_engine::chess_move::Move;
+use std::time::{Duration, SystemTime};
+use std::ops::Add;
+
+/// When the number of moves until the next time control is unknown, plan for this many.
+const DEFAULT_MOVES_TO_GO: u64 = 25;
+
+/// The hard limit is at most this many times the soft limit.
+const HARD_LIMIT_FACTOR: u32 = 3;
+
+/// Every time the best move changes between iterations the soft limit grows by this factor,
+/// up to `MAX_INSTABILITY`. Every iteration that keeps the best move shrinks it again.
+const INSTABILITY_GROWTH: f64 = 1.5;
+const INSTABILITY_DECAY: f64 = 0.9;
+const MAX_INSTABILITY: f64 = 2.5;
+
+/// How the clock of the game works, besides the time that is left.
+#[derive(Clone, Debug)]
+pub struct TimeControl {
+    /// Time added to the clock after every move
+    pub increment: Duration,
+    /// The number of moves until the next time control, if there is one
+    pub moves_to_go: Option<u64>,
+    /// Time lost on every move outside of the search, for example by communication
+    pub move_overhead: Duration,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            increment: Duration::from_millis(0),
+            moves_to_go: None,
+            move_overhead: Duration::from_millis(20),
+        }
+    }
+}
+
+/// Decides how long to think about a move. After the soft limit no new iteration is started,
+/// at the hard limit the search is aborted. Iterations that were aborted must be thrown away.
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    start: SystemTime,
+    soft: Duration,
+    hard: Duration,
+
+    instability: f64,
+    previous_best: Option<Move>,
+}
+
+impl TimeManager {
+    pub fn new(remaining: Duration, control: &TimeControl) -> Self {
+        let available = remaining.checked_sub(control.move_overhead).unwrap_or_default();
+        let moves_to_go = control.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+        // never use more than half of the clock on one move, unless it's the last one before the time control
+        let max = if moves_to_go == 1 { available } else { available / 2 };
+
+        let soft = available / moves_to_go as u32 + control.increment * 3 / 4;
+        let hard = (soft * HARD_LIMIT_FACTOR).min(max);
+
+        Self {
+            start: SystemTime::now(),
+            soft: soft.min(hard),
+            hard,
+
+            instability: 1.0,
+            previous_best: None,
+        }
+    }
+
+    /// A time manager that only stops when the hard limit is reached.
+    pub fn fixed(time: Duration) -> Self {
+        Self {
+            start: SystemTime::now(),
+            soft: time,
+            hard: time,
+
+            instability: 1.0,
+            previous_best: None,
+        }
+    }
+
+    /// A time manager for searches that are only stopped by other limits, or from the outside.
+    pub fn unlimited() -> Self {
+        Self::fixed(Duration::from_secs(365 * 24 * 60 * 60))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        SystemTime::now().duration_since(self.start).unwrap_or_default()
+    }
+
+    /// The soft limit, extended while the best move keeps changing.
+    pub fn soft_limit(&self) -> Duration {
+        self.soft.mul_f64(self.instability).min(self.hard)
+    }
+
+    /// The moment the search has to be aborted.
+    pub fn deadline(&self) -> SystemTime {
+        self.start.add(self.hard)
+    }
+
+    pub fn should_start_iteration(&self) -> bool {
+        self.elapsed() < self.soft_limit()
+    }
+
+    /// Should be called after every completed iteration with the best move it found.
+    pub fn iteration_finished(&mut self, best: Option<Move>) {
+        if self.previous_best.is_some() && best != self.previous_best {
+            self.instability = (self.instability * INSTABILITY_GROWTH).min(MAX_INSTABILITY);
+        } else {
+            self.instability = (self.instability * INSTABILITY_DECAY).max(1.0);
+        }
+        self.previous_best = best;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::time_manager::{TimeManager, TimeControl};
+    use std::time::Duration;
+
+    #[test]
+    fn test_allocation() {
+        let control = TimeControl::default();
+        let tm = TimeManager::new(Duration::from_secs(60), &control);
+        assert!(tm.soft_limit() < tm.hard);
+        assert!(tm.hard <= Duration::from_secs(30));
+
+        // an increment means we can spend more
+        let with_increment = TimeManager::new(Duration::from_secs(60), &TimeControl { increment: Duration::from_secs(2), ..control.clone() });
+        assert!(with_increment.soft_limit() > tm.soft_limit());
+
+        // the last move before the time control may use nearly everything
+        let last_move = TimeManager::new(Duration::from_secs(60), &TimeControl { moves_to_go: Some(1), ..control.clone() });
+        assert!(last_move.hard > Duration::from_secs(50));
+        assert!(last_move.hard < Duration::from_secs(60));
+
+        // the overhead is never spent
+        let almost_flagged = TimeManager::new(Duration::from_millis(10), &control);
+        assert_eq!(almost_flagged.hard, Duration::from_millis(0));
+    }
+
+    #[test]
+    fn test_instability() {
+        let mut tm = TimeManager::new(Duration::from_secs(60), &TimeControl::default());
+        let soft = tm.soft_limit();
+
+        tm.iteration_finished(Some(((0, 6), (0, 4)).into()));
+        assert_eq!(tm.soft_limit(), soft);
+
+        tm.iteration_finished(Some(((1, 6), (1, 4)).into()));
+        assert!(tm.soft_limit() > soft);
+        assert!(tm.soft_limit() <= tm.hard);
+
+        for _ in 0..20 {
+            tm.iteration_finished(Some(((1, 6), (1, 4)).into()));
+        }
+        assert_eq!(tm.soft_limit(), soft);
+    }
+}
@@ -14,6 +14,8 @@ pub struct TranspositionTableStats {
     collisions: u64,
     colisionless_insertions: u64,
     hits: u64,
+    // Permille of the table used by the current search, at the end of the search
+    hashfull: u64,
 }
 
 impl TranspositionTableStats {
@@ -21,7 +23,8 @@ impl TranspositionTableStats {
         Self {
             colisionless_insertions: 0,
             collisions: 0,
-            hits: 0
+            hits: 0,
+            hashfull: 0,
         }
     }
 
@@ -36,6 +39,10 @@ impl TranspositionTableStats {
     pub fn colisionless_insert(&mut self) {
         self.colisionless_insertions += 1;
     }
+
+    pub fn hashfull(&mut self, permille: u64) {
+        self.hashfull = permille;
+    }
 }
 
 #[derive(Clone, Serialize, Debug)]
@@ -46,6 +53,10 @@ pub struct StatsEntry {
     num_states_seen: u64,
     evaluation: f64,
 
+    // Nodes searched by each thread, when searching with more than one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    thread_nodes: Vec<u64>,
+
     // None if variable/not applicable
     search_depth: Option<u64>,
 
@@ -67,6 +78,7 @@ impl StatsEntry {
 
             num_states_seen: 0,
             evaluation: 0.0,
+            thread_nodes: Vec::new(),
 
             search_depth,
             transposition: None,
@@ -92,6 +104,10 @@ impl StatsEntry {
         *self.int_entries.entry(name).or_insert(0) -= 1;
     }
 
+    pub fn get_custom_int_entry(&self, name: &'static str) -> i64 {
+        self.int_entries.get(name).copied().unwrap_or(0)
+    }
+
 
     pub fn custom_float_entry(&mut self, name: &'static str, value: f64) {
         self.float_entries.insert(name, value);
@@ -105,6 +121,17 @@ impl StatsEntry {
         self.num_states_seen += 1;
     }
 
+    pub fn states_seen(&self) -> u64 {
+        self.num_states_seen
+    }
+
+    /// Records the nodes searched by every thread of a parallel search.
+    /// The total becomes the number of states seen.
+    pub fn thread_nodes(&mut self, nodes: Vec<u64>) {
+        self.num_states_seen = nodes.iter().sum();
+        self.thread_nodes = nodes;
+    }
+
     pub fn end(&mut self) {
         self.time_finished = Some(SystemTime::now());
     }
@@ -188,6 +215,10 @@ impl Stats {
         }
     }
 
+    pub fn algorithm_name(&self) -> &'static str {
+        self.metadata.algorithm_name
+    }
+
     pub fn last_entry(&self) -> Option<StatsEntry> {
         self.last_entry.lock().clone()
     }
@@ -0,0 +1,525 @@
+use crate::game_engine::color::Color;
+use crate::game_engine::piece::{Piece, queen_of_color, rook_of_color, bishop_of_color, knight_of_color};
+use crate::tablebase::position::{Material, Position, Pieces, Square, MAX_MEN, rank, file};
+use crate::tablebase::{Table, Tablebases, Probe, Wdl, DRAW, ILLEGAL};
+use std::collections::HashMap;
+
+/// A position after a move, and whether the move captured or promoted so that it is in another table.
+struct Child {
+    pieces: Pieces,
+    leaves_table: bool,
+    /// The pawn that moved two squares, when the other side can take it en passant
+    en_passant: Option<Square>,
+}
+
+fn forward(piece: Piece) -> i8 {
+    if piece.color() == Color::White { 8 } else { -8 }
+}
+
+fn promotions(color: Color) -> [Piece; 4] {
+    [queen_of_color(color), rook_of_color(color), bishop_of_color(color), knight_of_color(color)]
+}
+
+fn moves_two_squares((piece, from): (Piece, Square), to: Square) -> bool {
+    piece.is_pawn() && (rank(to) - rank(from)).abs() == 2
+}
+
+/// The positions after the side to move takes the pawn on `pawn`, which just moved two squares, en passant.
+fn en_passant_captures(position: &Pieces, pawn: Square) -> Vec<Pieces> {
+    let (pushed, _) = position.pieces[position.at(pawn).expect("the pawn that moved")];
+    let passed = (pawn as i8 - forward(pushed)) as Square;
+
+    position.pieces.iter().enumerate()
+        .filter(|&(_, &(piece, sq))| {
+            piece.is_pawn() && piece.color() != pushed.color() && rank(sq) == rank(pawn) && (file(sq) - file(pawn)).abs() == 1
+        })
+        .filter_map(|(i, _)| {
+            let mut pieces = position.pieces.clone();
+            pieces[i].1 = passed;
+            pieces.retain(|&(_, sq)| sq != pawn);
+
+            let child = Pieces {
+                pieces,
+                white_to_move: !position.white_to_move,
+            };
+            (!child.is_illegal()).then_some(child)
+        })
+        .collect()
+}
+
+/// All legal moves of the side to move.
+fn children(position: &Pieces) -> Vec<Child> {
+    let mut children = Vec::new();
+
+    let mut add = |i: usize, to: Square, promotion: Option<Piece>| {
+        let mut pieces = position.pieces.clone();
+        let captured = position.at(to);
+
+        pieces[i].1 = to;
+        if let Some(promotion) = promotion {
+            pieces[i].0 = promotion;
+        }
+        if let Some(captured) = captured {
+            pieces.remove(captured);
+        }
+
+        let child = Pieces {
+            pieces,
+            white_to_move: !position.white_to_move,
+        };
+        // the king of the side that moved can't be left in check
+        if !child.is_illegal() {
+            let double_push = moves_two_squares(position.pieces[i], to);
+            let en_passant = (double_push && !en_passant_captures(&child, to).is_empty()).then_some(to);
+
+            children.push(Child {
+                pieces: child,
+                leaves_table: captured.is_some() || promotion.is_some(),
+                en_passant,
+            });
+        }
+    };
+
+    for (i, &(piece, from)) in position.pieces.iter().enumerate() {
+        if (piece.color() == Color::White) != position.white_to_move {
+            continue;
+        }
+
+        let captures = position.targets(piece, from).into_iter().filter(|&to| match position.at(to) {
+            Some(j) => position.pieces[j].0.color() != piece.color() && !position.pieces[j].0.is_king(),
+            None => !piece.is_pawn(),
+        });
+
+        if !piece.is_pawn() {
+            for to in captures {
+                add(i, to, None);
+            }
+            continue;
+        }
+
+        let mut targets: Vec<Square> = captures.collect();
+        let one = (from as i8 + forward(piece)) as Square;
+        if position.at(one).is_none() {
+            targets.push(one);
+
+            let start = if piece.color() == Color::White { 1 } else { 6 };
+            let two = (one as i8 + forward(piece)) as Square;
+            if rank(from) == start && position.at(two).is_none() {
+                targets.push(two);
+            }
+        }
+
+        for to in targets {
+            if rank(to) == 0 || rank(to) == 7 {
+                for &promotion in &promotions(piece.color()) {
+                    add(i, to, Some(promotion));
+                }
+            } else {
+                add(i, to, None);
+            }
+        }
+    }
+
+    children
+}
+
+/// All positions that lead to `position` with a move that doesn't capture or promote.
+fn parents(position: &Pieces) -> Vec<Pieces> {
+    let mut parents = Vec::new();
+
+    for (i, &(piece, from)) in position.pieces.iter().enumerate() {
+        // the piece of the side that just moved
+        if (piece.color() == Color::White) == position.white_to_move {
+            continue;
+        }
+
+        let origins: Vec<Square> = if piece.is_pawn() {
+            let mut origins = Vec::new();
+            let one = from as i8 - forward(piece);
+            let start = if piece.color() == Color::White { 1 } else { 6 };
+
+            if position.at(one as Square).is_none() {
+                origins.push(one as Square);
+
+                // after a push that can be taken en passant the position is another one, see `Generator`
+                let two = one - forward(piece);
+                if rank(two as Square) == start && position.at(two as Square).is_none() && en_passant_captures(position, from).is_empty() {
+                    origins.push(two as Square);
+                }
+            }
+            origins.retain(|&sq| rank(sq) != 0 && rank(sq) != 7);
+            origins
+        } else {
+            position.targets(piece, from).into_iter().filter(|&sq| position.at(sq).is_none()).collect()
+        };
+
+        for origin in origins {
+            let mut pieces = position.pieces.clone();
+            pieces[i].1 = origin;
+
+            let parent = Pieces {
+                pieces,
+                white_to_move: !position.white_to_move,
+            };
+            if !parent.is_illegal() {
+                parents.push(parent);
+            }
+        }
+    }
+
+    parents
+}
+
+fn to_position(pieces: &Pieces) -> Position {
+    let mut squares = [0; MAX_MEN];
+    for (square, &(_, sq)) in squares.iter_mut().zip(pieces.pieces.iter()) {
+        *square = sq;
+    }
+
+    Position {
+        squares,
+        white_to_move: pieces.white_to_move,
+    }
+}
+
+/// The tables that positions of `material` can reach in one move, by a capture, a promotion or both.
+pub fn dependencies(material: &Material) -> Vec<Material> {
+    let pieces = material.pieces();
+    let mut dependencies: Vec<Material> = Vec::new();
+
+    let mut add = |pieces: Vec<Piece>| {
+        let material = Material::of(&pieces).0;
+        if !material.is_trivial_draw() && !dependencies.contains(&material) {
+            dependencies.push(material);
+        }
+    };
+
+    for (i, piece) in pieces.iter().enumerate() {
+        if piece.is_king() {
+            continue;
+        }
+
+        let mut captured = pieces.to_vec();
+        captured.remove(i);
+        add(captured);
+
+        if piece.is_pawn() {
+            for &promotion in &promotions(piece.color()) {
+                let mut promoted = pieces.to_vec();
+                promoted[i] = promotion;
+                add(promoted.clone());
+
+                for (j, other) in pieces.iter().enumerate() {
+                    if !other.is_king() && other.color() != piece.color() {
+                        let mut captured = promoted.clone();
+                        captured.remove(j);
+                        add(captured);
+                    }
+                }
+            }
+        }
+    }
+
+    dependencies
+}
+
+#[derive(Copy, Clone)]
+struct Node {
+    value: u8,
+    resolved: bool,
+    /// The number of moves in this table whose result isn't known yet
+    unknown_moves: u8,
+    /// The longest loss of the moves that are known to lose
+    longest: u32,
+    can_draw: bool,
+    can_win: bool,
+}
+
+impl Node {
+    const IMPOSSIBLE: Node = Node {
+        value: ILLEGAL,
+        resolved: true,
+        unknown_moves: 0,
+        longest: 0,
+        can_draw: false,
+        can_win: false,
+    };
+}
+
+/// Resolves the positions of one table.
+///
+/// Positions right after a pawn moved two squares, where it can be taken en passant, have another
+/// move than the same position without that right. They get their own nodes after the positions
+/// of the table, which aren't saved: each of them is linked to the single position it came from.
+struct Generator<'a> {
+    material: &'a Material,
+    tablebases: &'a Tablebases,
+    nodes: Vec<Node>,
+    /// The nodes with an en passant right, by the position without it
+    en_passant: HashMap<usize, Vec<usize>>,
+    /// The position before the push of every node with an en passant right, in the same order
+    pushed_from: Vec<usize>,
+
+    /// The nodes that are resolved as wins or losses in a number of plies
+    wins: Vec<Vec<usize>>,
+    losses: Vec<Vec<usize>>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(material: &'a Material, tablebases: &'a Tablebases) -> Self {
+        Self {
+            material,
+            tablebases,
+            nodes: vec![Node::IMPOSSIBLE; material.size()],
+            en_passant: HashMap::new(),
+            pushed_from: Vec::new(),
+            wins: Vec::new(),
+            losses: Vec::new(),
+        }
+    }
+
+    fn push(layers: &mut Vec<Vec<usize>>, plies: u32, node: usize) {
+        let plies = plies as usize;
+        if layers.len() <= plies {
+            layers.resize(plies + 1, Vec::new());
+        }
+        layers[plies].push(node);
+    }
+
+    /// Finds the mates, stalemates and moves to other tables of `node`, which is `pieces`,
+    /// and counts its moves in this table. `captures` are the en passant captures it has.
+    fn add_moves(&mut self, node: usize, pieces: &Pieces, captures: Vec<Pieces>) {
+        self.nodes[node] = Node {
+            value: DRAW,
+            resolved: false,
+            ..Node::IMPOSSIBLE
+        };
+
+        let children = children(pieces);
+        if children.is_empty() && captures.is_empty() {
+            if pieces.in_check() {
+                Self::push(&mut self.losses, 0, node);
+            } else {
+                self.nodes[node].resolved = true;
+            }
+            return;
+        }
+
+        // moves to symmetrical positions count once, like they are found once from the other side
+        let mut moves_in_table = Vec::new();
+
+        for child in children {
+            if child.leaves_table {
+                self.leave_table(node, &child.pieces);
+                continue;
+            }
+
+            let child = match child.en_passant {
+                Some(pawn) => self.add_en_passant(node, &child.pieces, pawn),
+                None => self.material.index(&to_position(&child.pieces)),
+            };
+            if !moves_in_table.contains(&child) {
+                moves_in_table.push(child);
+            }
+        }
+        for capture in captures {
+            self.leave_table(node, &capture);
+        }
+
+        let state = &mut self.nodes[node];
+        state.unknown_moves = moves_in_table.len() as u8;
+        if state.unknown_moves == 0 && !state.can_win {
+            if state.can_draw {
+                state.resolved = true;
+            } else {
+                Self::push(&mut self.losses, state.longest, node);
+            }
+        }
+    }
+
+    /// Adds the result of a move of `node` that captures or promotes, from another table.
+    fn leave_table(&mut self, node: usize, child: &Pieces) {
+        let probe = self.tablebases.probe_pieces(&child.pieces, child.white_to_move)
+            .unwrap_or_else(|| panic!("{} needs the tables it captures or promotes into", self.material.name()))
+            .before();
+
+        let state = &mut self.nodes[node];
+        match probe.wdl {
+            Wdl::Win => {
+                state.can_win = true;
+                Self::push(&mut self.wins, probe.plies, node);
+            }
+            Wdl::Draw => state.can_draw = true,
+            Wdl::Loss => state.longest = state.longest.max(probe.plies),
+        }
+    }
+
+    /// Adds a node for `pieces` with the right to take the pawn on `pawn` en passant, which `parent` just pushed.
+    fn add_en_passant(&mut self, parent: usize, pieces: &Pieces, pawn: Square) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(Node::IMPOSSIBLE);
+        self.pushed_from.push(parent);
+
+        let index = self.material.index(&to_position(pieces));
+        self.en_passant.entry(index).or_default().push(node);
+
+        self.add_moves(node, pieces, en_passant_captures(pieces, pawn));
+        node
+    }
+
+    /// Tells `parent` that one of its moves leads to a position that is `before` for it.
+    fn resolve_move(&mut self, parent: usize, before: Probe) {
+        let state = &mut self.nodes[parent];
+        if state.resolved {
+            return;
+        }
+
+        match before.wdl {
+            Wdl::Win => Self::push(&mut self.wins, before.plies, parent),
+            _ => {
+                state.unknown_moves -= 1;
+                state.longest = state.longest.max(before.plies);
+
+                if state.unknown_moves == 0 && !state.can_win && !state.can_draw {
+                    Self::push(&mut self.losses, state.longest, parent);
+                }
+            }
+        }
+    }
+
+    fn generate(mut self) -> Table {
+        let size = self.material.size();
+
+        for index in 0..size {
+            let pieces = Pieces::new(self.material, &self.material.position(index));
+            if pieces.is_valid() && !pieces.is_illegal() && self.material.is_canonical(index) {
+                self.add_moves(index, &pieces, Vec::new());
+            }
+        }
+
+        let mut plies = 0;
+        while plies < self.wins.len().max(self.losses.len()) {
+            let mut layer = Vec::new();
+
+            for (layers, probe) in [(&mut self.wins, Probe::win(plies as u32)), (&mut self.losses, Probe::loss(plies as u32))] {
+                if let Some(nodes) = layers.get_mut(plies) {
+                    for node in std::mem::take(nodes) {
+                        let state = &mut self.nodes[node];
+                        if !state.resolved {
+                            state.resolved = true;
+                            state.value = probe.value();
+                            layer.push((node, probe));
+                        }
+                    }
+                }
+            }
+
+            for (node, probe) in layer {
+                let before = probe.before();
+
+                if node >= size {
+                    self.resolve_move(self.pushed_from[node - size], before);
+                    continue;
+                }
+
+                let mut found = Vec::new();
+                for parent in parents(&Pieces::new(self.material, &self.material.position(node))) {
+                    let parent = self.material.index(&to_position(&parent));
+                    if found.contains(&parent) {
+                        continue;
+                    }
+                    found.push(parent);
+
+                    self.resolve_move(parent, before);
+                    // the same position with an en passant right has the same moves, and one more
+                    for other in self.en_passant.get(&parent).cloned().unwrap_or_default() {
+                        self.resolve_move(other, before);
+                    }
+                }
+            }
+
+            plies += 1;
+        }
+
+        Table {
+            material: self.material.clone(),
+            values: self.nodes[..size].iter().map(|node| node.value).collect(),
+        }
+    }
+}
+
+/// Makes the table of `material` by retrograde analysis. The tables it depends on have to be in `tablebases`.
+///
+/// First every position gets the results of its mates, stalemates, and moves to other tables.
+/// Then the positions are resolved in order of the number of plies to mate: a position is won in
+/// n + 1 plies when it has a move to a position lost in n plies, and lost in n + 1 plies when all its
+/// moves lead to positions that are won, the longest in n plies. Positions that are never resolved are draws.
+pub fn generate(material: &Material, tablebases: &Tablebases) -> Table {
+    Generator::new(material, tablebases).generate()
+}
+
+/// Makes the tables of `material` and all tables it depends on that aren't in `tablebases` yet,
+/// and adds them. `made` is called with every new table.
+pub fn generate_all(material: &Material, tablebases: &mut Tablebases, made: &mut impl FnMut(&Table)) {
+    if material.is_trivial_draw() || tablebases.contains(material) {
+        return;
+    }
+
+    for dependency in dependencies(material) {
+        generate_all(&dependency, tablebases, made);
+    }
+
+    let table = generate(material, tablebases);
+    made(&table);
+    tablebases.add(table);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_engine::board::{BasicBoard, Board};
+    use crate::game_engine::chess_move::Location;
+    use crate::game_engine::color::Color;
+    use crate::game_engine::piece::Piece;
+    use crate::tablebase::generator::generate_all;
+    use crate::tablebase::position::Material;
+    use crate::tablebase::{Tablebases, Probe, DRAW, LOSS};
+    use std::time::Duration;
+
+    fn board(pieces: &[(Piece, &str)], current: Color) -> BasicBoard {
+        let mut board = BasicBoard::new(Duration::from_secs(60));
+        for &(piece, name) in pieces {
+            *board.piece_at_mut(Location::from_name(name).unwrap()) = piece;
+        }
+        board.current = current;
+        board
+    }
+
+    #[test]
+    fn test_krk() {
+        let mut tablebases = Tablebases::new();
+        generate_all(&Material::parse("KRvK").unwrap(), &mut tablebases, &mut |_| {});
+
+        // mate in one
+        let b = board(&[(Piece::WhiteKing, "a6"), (Piece::WhiteRook, "h1"), (Piece::BlackKing, "a8")], Color::White);
+        assert_eq!(tablebases.probe(&b), Some(Probe::win(1)));
+
+        // mated
+        let b = board(&[(Piece::WhiteKing, "a6"), (Piece::WhiteRook, "h8"), (Piece::BlackKing, "a8")], Color::Black);
+        assert_eq!(tablebases.probe(&b), Some(Probe::loss(0)));
+
+        // the rook is lost
+        let b = board(&[(Piece::WhiteKing, "h1"), (Piece::WhiteRook, "b7"), (Piece::BlackKing, "a8")], Color::Black);
+        assert_eq!(tablebases.probe(&b), Some(Probe::DRAW));
+        // which scores like any other draw
+        assert_eq!(Probe::DRAW.score(Color::Black, -20.0), -20.0);
+
+        // the same with the colors flipped
+        let b = board(&[(Piece::BlackKing, "a3"), (Piece::BlackRook, "h8"), (Piece::WhiteKing, "a1")], Color::Black);
+        assert_eq!(tablebases.probe(&b), Some(Probe::win(1)));
+
+        // the longest win in KRK is mate in 16
+        let table = &tablebases.tables[&Material::parse("KRvK").unwrap()];
+        let longest = table.values.iter().filter(|&&v| v != DRAW && v < LOSS).max();
+        assert_eq!(longest, Some(&31));
+    }
+}
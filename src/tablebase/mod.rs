@@ -0,0 +1,340 @@
+//! Endgame tablebases: for every position with a few pieces, whether the side to move wins, draws
+//! or loses, and in how many plies the game ends in mate with perfect play. They are made by
+//! retrograde analysis with the `generator`, and saved as one file per set of pieces.
+//!
+//! The tables don't know about castling, en passant or the fifty-move rule, so positions where
+//! castling or an en passant capture is possible aren't probed, and wins may take more than 100 plies.
+
+use crate::game_engine::board::Board;
+use crate::game_engine::board::polyglot::en_passant_possible;
+use crate::game_engine::chess_move::{Move, Location};
+use crate::game_engine::color::Color;
+use crate::game_engine::piece::Piece;
+use crate::tablebase::position::{Material, Position, Square, MAX_MEN, flip_color};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+pub mod position;
+pub mod generator;
+pub mod syzygy;
+
+const MAGIC: &[u8] = b"VTB1";
+const EXTENSION: &str = "vtb";
+
+/// The values of a table, from the side to move's view.
+const DRAW: u8 = 0;
+/// Wins in 1 to 127 plies are stored as the number of plies,
+/// losses in 0 to 126 plies as the number of plies plus this.
+const LOSS: u8 = 128;
+/// The score of a position that is won in the tables, see `Probe::score`.
+pub const WIN_SCORE: f64 = 100_000.0;
+
+/// Positions that can't happen, or that are stored under a symmetrical position.
+const ILLEGAL: u8 = 255;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Wdl {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// The result of a position with perfect play, for the side to move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Probe {
+    pub wdl: Wdl,
+    /// The number of plies until mate, 0 for draws
+    pub plies: u32,
+}
+
+impl Probe {
+    pub const DRAW: Probe = Probe { wdl: Wdl::Draw, plies: 0 };
+
+    pub fn win(plies: u32) -> Self {
+        Self { wdl: Wdl::Win, plies }
+    }
+
+    pub fn loss(plies: u32) -> Self {
+        Self { wdl: Wdl::Loss, plies }
+    }
+
+    fn from_value(value: u8) -> Option<Self> {
+        match value {
+            ILLEGAL => None,
+            DRAW => Some(Self::DRAW),
+            v if v < LOSS => Some(Self::win(v as u32)),
+            v => Some(Self::loss((v - LOSS) as u32)),
+        }
+    }
+
+    fn value(&self) -> u8 {
+        match self.wdl {
+            Wdl::Draw => DRAW,
+            Wdl::Win => {
+                assert!(self.plies > 0 && self.plies < LOSS as u32, "wins in more than 127 plies don't fit in a table");
+                self.plies as u8
+            }
+            Wdl::Loss => {
+                assert!(self.plies < (ILLEGAL - LOSS) as u32, "losses in more than 126 plies don't fit in a table");
+                LOSS + self.plies as u8
+            }
+        }
+    }
+
+    /// The result of the position before a move that leads to this one.
+    pub fn before(&self) -> Self {
+        match self.wdl {
+            Wdl::Win => Self::loss(self.plies + 1),
+            Wdl::Draw => Self::DRAW,
+            Wdl::Loss => Self::win(self.plies + 1),
+        }
+    }
+
+    /// The score of the position, positive when white wins. Wins score less than mates that
+    /// were found by searching, and more than any evaluation, and faster wins score higher.
+    /// Draws score `draw_score`, which is from white's perspective like the other scores.
+    pub fn score(&self, current: Color, draw_score: f64) -> f64 {
+        let score = match self.wdl {
+            Wdl::Win => WIN_SCORE - self.plies as f64,
+            Wdl::Draw => return draw_score,
+            Wdl::Loss => self.plies as f64 - WIN_SCORE,
+        };
+
+        if current == Color::White { score } else { -score }
+    }
+
+    /// Orders results from the worst to the best for the side to move:
+    /// the longest losses, then draws, then the shortest wins.
+    pub fn goodness(&self) -> i64 {
+        match self.wdl {
+            Wdl::Loss => self.plies as i64 - 1000,
+            Wdl::Draw => 0,
+            Wdl::Win => 1000 - self.plies as i64,
+        }
+    }
+}
+
+/// The table of one set of pieces.
+pub struct Table {
+    material: Material,
+    values: Vec<u8>,
+}
+
+impl Table {
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn probe(&self, position: &Position) -> Option<Probe> {
+        Probe::from_value(self.values[self.material.index(position)])
+    }
+
+    /// Reads a table written by `save`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let rest = bytes.strip_prefix(MAGIC).ok_or_else(|| invalid("not a tablebase file"))?;
+        let end = rest.iter().position(|&b| b == b'\n').ok_or_else(|| invalid("the material is missing"))?;
+        let name = std::str::from_utf8(&rest[..end]).map_err(|_| invalid("the material is not utf-8"))?;
+        let material = Material::parse(name).ok_or_else(|| invalid("unknown material"))?;
+
+        let values = rest[end + 1..].to_vec();
+        if values.len() != material.size() {
+            return Err(invalid("the table has the wrong size"));
+        }
+
+        Ok(Self { material, values })
+    }
+
+    /// Writes the table to `<directory>/<material>.vtb`, like `KQvK.vtb`.
+    pub fn save(&self, directory: impl AsRef<Path>) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(self.values.len() + 16);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(self.material.name().as_bytes());
+        bytes.push(b'\n');
+        bytes.extend_from_slice(&self.values);
+
+        fs::write(directory.as_ref().join(format!("{}.{}", self.material.name(), EXTENSION)), bytes)
+    }
+}
+
+#[derive(Default)]
+pub struct Tablebases {
+    tables: HashMap<Material, Arc<Table>>,
+    max_men: usize,
+}
+
+impl Tablebases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens all tables in `directory`.
+    pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let mut tablebases = Self::new();
+
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == EXTENSION) {
+                tablebases.add(Table::open(path)?);
+            }
+        }
+
+        Ok(tablebases)
+    }
+
+    pub fn add(&mut self, table: Table) {
+        self.max_men = self.max_men.max(table.material.pieces().len());
+        self.tables.insert(table.material.clone(), Arc::new(table));
+    }
+
+    pub fn contains(&self, material: &Material) -> bool {
+        self.tables.contains_key(material)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Looks up the position with these pieces. Positions where neither side can mate are
+    /// draws without a table.
+    pub fn probe_pieces(&self, pieces: &[(Piece, Square)], white_to_move: bool) -> Option<Probe> {
+        let kinds: Vec<Piece> = pieces.iter().map(|&(piece, _)| piece).collect();
+        let (material, flipped) = Material::of(&kinds);
+        if material.is_trivial_draw() {
+            return Some(Probe::DRAW);
+        }
+        let table = self.tables.get(&material)?;
+
+        // put the squares in the order of the material, with black as white when the colors are flipped
+        let mut position = Position {
+            squares: [0; MAX_MEN],
+            white_to_move: white_to_move != flipped,
+        };
+        let mut used = [false; MAX_MEN];
+        for &(piece, sq) in pieces {
+            let (piece, sq) = if flipped { (flip_color(piece), sq ^ 56) } else { (piece, sq) };
+            let i = (0..material.pieces().len())
+                .find(|&i| !used[i] && material.pieces()[i] == piece)
+                .expect("the material has all pieces");
+            used[i] = true;
+            position.squares[i] = sq;
+        }
+
+        table.probe(&position)
+    }
+
+    /// Looks up `board`, if its pieces have a table and castling and en passant aren't possible.
+    pub fn probe(&self, board: &impl Board) -> Option<Probe> {
+        if self.max_men == 0 || castling_possible(board) || en_passant_possible(board) {
+            return None;
+        }
+
+        let mut pieces = Vec::with_capacity(MAX_MEN);
+        for x in 0..8 {
+            for y in 0..8 {
+                let piece = board.piece_at((x, y));
+                if !piece.is_empty() {
+                    if pieces.len() == self.max_men {
+                        return None;
+                    }
+                    // tables count ranks from white's side
+                    pieces.push((piece, (8 * (7 - y) + x) as Square));
+                }
+            }
+        }
+
+        self.probe_pieces(&pieces, board.current_player() == Color::White)
+    }
+
+    /// Like `probe`, but positions where an en passant capture is possible are looked up by trying all moves.
+    pub fn probe_any(&self, board: &impl Board) -> Option<Probe> {
+        if let Some(probe) = self.probe(board) {
+            return Some(probe);
+        }
+        if !en_passant_possible(board) {
+            return None;
+        }
+
+        // none of the moves leave an en passant capture for the other side
+        board.all_moves().into_iter()
+            .map(|m| self.probe(&board.transition(m)).map(|probe| probe.before()))
+            .collect::<Option<Vec<Probe>>>()?
+            .into_iter()
+            .max_by_key(Probe::goodness)
+    }
+
+    /// The move that keeps the best result: the fastest mate when winning, a move that keeps
+    /// the draw, or the longest defence when losing. `None` if `board` isn't in the tables.
+    pub fn best_move(&self, board: &impl Board) -> Option<(Move, Probe)> {
+        self.probe(board)?;
+
+        board.all_moves().into_iter()
+            .filter_map(|m| Some((m, self.probe_any(&board.transition(m))?.before())))
+            .max_by_key(|(_, probe)| probe.goodness())
+    }
+}
+
+/// Whether a castling right is left with the king and rook still on their squares.
+fn castling_possible(board: &impl Board) -> bool {
+    const CORNERS: [(Piece, &str, Piece, &str); 4] = [
+        (Piece::BlackKing, "e8", Piece::BlackRook, "a8"),
+        (Piece::BlackKing, "e8", Piece::BlackRook, "h8"),
+        (Piece::WhiteKing, "e1", Piece::WhiteRook, "a1"),
+        (Piece::WhiteKing, "e1", Piece::WhiteRook, "h1"),
+    ];
+
+    board.get_castling_rights().iter().zip(CORNERS.iter()).any(|(&right, &(king, king_square, rook, rook_square))| {
+        right
+            && board.piece_at(Location::from_name(king_square).unwrap()) == king
+            && board.piece_at(Location::from_name(rook_square).unwrap()) == rook
+    })
+}
+
+lazy_static!(static ref TABLEBASES: RwLock<Arc<Tablebases>> = RwLock::new(Arc::new(Tablebases::new())););
+
+/// Makes `tablebases` the tables that `Board::probe_tablebase` looks in.
+pub fn install(tablebases: Tablebases) {
+    *TABLEBASES.write() = Arc::new(tablebases);
+}
+
+// tests run in parallel, so a test that installs tables only installs them for its own thread
+#[cfg(test)]
+thread_local!(static TEST_TABLEBASES: std::cell::RefCell<Option<Arc<Tablebases>>> = const { std::cell::RefCell::new(None) });
+
+/// Makes `tablebases` the tables of the current thread only, instead of the installed ones.
+/// `None` goes back to the installed tables.
+#[cfg(test)]
+pub fn install_for_test(tablebases: Option<Tablebases>) {
+    TEST_TABLEBASES.with(|tables| *tables.borrow_mut() = tablebases.map(Arc::new));
+}
+
+/// Calls `f` with the installed tables.
+fn with_installed<R>(f: impl FnOnce(&Tablebases) -> R) -> R {
+    #[cfg(test)]
+    if let Some(tables) = TEST_TABLEBASES.with(|tables| tables.borrow().clone()) {
+        return f(&tables);
+    }
+
+    f(&TABLEBASES.read())
+}
+
+/// Looks up `board` in the installed tables.
+pub fn probe(board: &impl Board) -> Option<Probe> {
+    with_installed(|tables| tables.probe(board))
+}
+
+/// The move that keeps the best result in the installed tables, see `Tablebases::best_move`.
+pub fn best_move(board: &impl Board) -> Option<(Move, Probe)> {
+    with_installed(|tables| tables.best_move(board))
+}
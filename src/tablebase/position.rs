@@ -0,0 +1,382 @@
+use crate::game_engine::piece::Piece;
+use crate::game_engine::color::Color;
+
+/// Tables can be made for at most this many pieces, kings included.
+pub const MAX_MEN: usize = 4;
+
+/// Squares are numbered rank by rank from white's side, from a1 = 0 to h8 = 63.
+pub type Square = u8;
+
+pub fn file(sq: Square) -> i8 {
+    (sq & 7) as i8
+}
+
+pub fn rank(sq: Square) -> i8 {
+    (sq >> 3) as i8
+}
+
+fn square(file: i8, rank: i8) -> Option<Square> {
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some((rank * 8 + file) as Square)
+    } else {
+        None
+    }
+}
+
+/// Pieces of the same kind are sorted by this, most valuable first.
+fn order(piece: Piece) -> u8 {
+    if piece.is_king() {
+        0
+    } else if piece.is_queen() {
+        1
+    } else if piece.is_rook() {
+        2
+    } else if piece.is_bishop() {
+        3
+    } else if piece.is_knight() {
+        4
+    } else {
+        5
+    }
+}
+
+pub fn letter(piece: Piece) -> char {
+    ['K', 'Q', 'R', 'B', 'N', 'P'][order(piece) as usize]
+}
+
+pub fn flip_color(piece: Piece) -> Piece {
+    match piece {
+        Piece::Empty => Piece::Empty,
+        Piece::BlackPawn => Piece::WhitePawn,
+        Piece::WhitePawn => Piece::BlackPawn,
+        Piece::BlackBishop => Piece::WhiteBishop,
+        Piece::WhiteBishop => Piece::BlackBishop,
+        Piece::BlackKnight => Piece::WhiteKnight,
+        Piece::WhiteKnight => Piece::BlackKnight,
+        Piece::BlackRook => Piece::WhiteRook,
+        Piece::WhiteRook => Piece::BlackRook,
+        Piece::BlackKing => Piece::WhiteKing,
+        Piece::WhiteKing => Piece::BlackKing,
+        Piece::BlackQueen => Piece::WhiteQueen,
+        Piece::WhiteQueen => Piece::BlackQueen,
+    }
+}
+
+fn piece_of(letter: char, color: Color) -> Option<Piece> {
+    let white = color == Color::White;
+    Some(match letter {
+        'K' => if white { Piece::WhiteKing } else { Piece::BlackKing },
+        'Q' => if white { Piece::WhiteQueen } else { Piece::BlackQueen },
+        'R' => if white { Piece::WhiteRook } else { Piece::BlackRook },
+        'B' => if white { Piece::WhiteBishop } else { Piece::BlackBishop },
+        'N' => if white { Piece::WhiteKnight } else { Piece::BlackKnight },
+        'P' => if white { Piece::WhitePawn } else { Piece::BlackPawn },
+        _ => return None,
+    })
+}
+
+/// The pieces of a table, like KQ against KR. White's pieces come first, the most valuable first,
+/// and white has the stronger pieces: positions where black has them are looked up with the colors flipped.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Material {
+    pieces: Vec<Piece>,
+}
+
+impl Material {
+    /// The material of `pieces`, and whether the colors had to be flipped to get it.
+    pub fn of(pieces: &[Piece]) -> (Self, bool) {
+        let mut white: Vec<Piece> = pieces.iter().copied().filter(|p| p.color() == Color::White).collect();
+        let mut black: Vec<Piece> = pieces.iter().copied().filter(|p| p.color() == Color::Black).collect();
+        white.sort_by_key(|&p| order(p));
+        black.sort_by_key(|&p| order(p));
+
+        let strength = |side: &[Piece]| {
+            (side.len(), side.iter().map(|p| p.material_worth().abs()).sum::<i32>())
+        };
+
+        let flipped = strength(&black) > strength(&white);
+        let (strong, weak) = if flipped { (black, white) } else { (white, black) };
+
+        let pieces = strong.into_iter().map(|p| if flipped { flip_color(p) } else { p })
+            .chain(weak.into_iter().map(|p| if flipped { flip_color(p) } else { p }))
+            .collect();
+
+        (Self { pieces }, flipped)
+    }
+
+    /// Parses names like "KQvKR".
+    pub fn parse(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        if !white.starts_with('K') || !black.starts_with('K') || white.len() + black.len() > MAX_MEN {
+            return None;
+        }
+
+        let pieces: Option<Vec<Piece>> = white.chars().map(|c| piece_of(c, Color::White))
+            .chain(black.chars().map(|c| piece_of(c, Color::Black)))
+            .collect();
+        let pieces = pieces?;
+        if pieces.iter().filter(|p| p.is_king()).count() != 2 {
+            return None;
+        }
+
+        Some(Self::of(&pieces).0)
+    }
+
+    pub fn name(&self) -> String {
+        let white: String = self.pieces.iter().filter(|p| p.color() == Color::White).map(|&p| letter(p)).collect();
+        let black: String = self.pieces.iter().filter(|p| p.color() == Color::Black).map(|&p| letter(p)).collect();
+        format!("{}v{}", white, black)
+    }
+
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces
+    }
+
+    pub fn has_pawns(&self) -> bool {
+        self.pieces.iter().any(|p| p.is_pawn())
+    }
+
+    /// Neither side can ever mate: only kings, or a king and a single minor piece against a king.
+    pub fn is_trivial_draw(&self) -> bool {
+        let others: Vec<&Piece> = self.pieces.iter().filter(|p| !p.is_king()).collect();
+        match others.as_slice() {
+            [] => true,
+            [p] => p.is_bishop() || p.is_knight(),
+            _ => false,
+        }
+    }
+
+    /// The number of positions in the table, including impossible ones.
+    pub fn size(&self) -> usize {
+        2 * self.king_squares() * 64usize.pow(self.pieces.len() as u32 - 1)
+    }
+
+    /// The number of squares the white king can be on after using the symmetry of the board.
+    fn king_squares(&self) -> usize {
+        if self.has_pawns() { 32 } else { 10 }
+    }
+
+    /// The symmetries of the board: pawns can only be mirrored left to right,
+    /// without them the board can also be mirrored top to bottom and along the diagonal.
+    fn transforms(&self) -> &'static [fn(Square) -> Square] {
+        const ALL: [fn(Square) -> Square; 8] = [
+            |sq| sq,
+            |sq| sq ^ 7,
+            |sq| sq ^ 56,
+            |sq| sq ^ 63,
+            |sq| ((sq & 7) << 3) | (sq >> 3),
+            |sq| (((sq & 7) << 3) | (sq >> 3)) ^ 7,
+            |sq| (((sq & 7) << 3) | (sq >> 3)) ^ 56,
+            |sq| (((sq & 7) << 3) | (sq >> 3)) ^ 63,
+        ];
+
+        if self.has_pawns() { &ALL[..2] } else { &ALL }
+    }
+
+    /// The index of the white king in the reduced set of squares, if it's in there.
+    fn king_index(&self, sq: Square) -> Option<usize> {
+        let (f, r) = (file(sq), rank(sq));
+        if self.has_pawns() {
+            // files a to d
+            (f < 4).then(|| (r * 4 + f) as usize)
+        } else {
+            // the triangle a1, d1, d4
+            (f < 4 && r <= f).then(|| (f * (f + 1) / 2 + r) as usize)
+        }
+    }
+
+    fn king_square(&self, index: usize) -> Square {
+        if self.has_pawns() {
+            ((index / 4) * 8 + index % 4) as Square
+        } else {
+            let f = (0..4).rev().find(|f| f * (f + 1) / 2 <= index).expect("king index < 10");
+            (((index - f * (f + 1) / 2) * 8) + f) as Square
+        }
+    }
+
+    /// The index of `position` in the table. Symmetrical positions get the same index.
+    pub fn index(&self, position: &Position) -> usize {
+        let n = self.pieces.len();
+
+        self.transforms().iter()
+            .filter_map(|transform| {
+                let mut squares = position.squares;
+                for sq in &mut squares[..n] {
+                    *sq = transform(*sq);
+                }
+                let king = self.king_index(squares[0])?;
+
+                // identical pieces can be swapped
+                let mut i = 1;
+                while i < n {
+                    let j = (i..n).take_while(|&j| self.pieces[j] == self.pieces[i]).count() + i;
+                    squares[i..j].sort_unstable();
+                    i = j;
+                }
+
+                let index = squares[1..n].iter().fold(
+                    (!position.white_to_move as usize) * self.king_squares() + king,
+                    |index, &sq| index * 64 + sq as usize,
+                );
+                Some(index)
+            })
+            .min()
+            .expect("the king can always be moved to the reduced squares")
+    }
+
+    /// The position with this index. Only positions with the smallest index of all their
+    /// symmetrical versions are used, `is_canonical` tells if this is one.
+    pub fn position(&self, mut index: usize) -> Position {
+        let n = self.pieces.len();
+        let mut squares = [0; MAX_MEN];
+
+        for i in (1..n).rev() {
+            squares[i] = (index % 64) as Square;
+            index /= 64;
+        }
+        squares[0] = self.king_square(index % self.king_squares());
+
+        Position {
+            squares,
+            white_to_move: index < self.king_squares(),
+        }
+    }
+
+    pub fn is_canonical(&self, index: usize) -> bool {
+        self.index(&self.position(index)) == index
+    }
+}
+
+/// A position of a table: the squares of the pieces of its `Material`, in the same order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Position {
+    pub squares: [Square; MAX_MEN],
+    pub white_to_move: bool,
+}
+
+/// The pieces on the board during generation: pieces of the material that were captured are gone.
+#[derive(Clone, Debug)]
+pub struct Pieces {
+    pub pieces: Vec<(Piece, Square)>,
+    pub white_to_move: bool,
+}
+
+impl Pieces {
+    pub fn new(material: &Material, position: &Position) -> Self {
+        Self {
+            pieces: material.pieces().iter().copied().zip(position.squares.iter().copied()).collect(),
+            white_to_move: position.white_to_move,
+        }
+    }
+
+    pub fn at(&self, sq: Square) -> Option<usize> {
+        self.pieces.iter().position(|&(_, s)| s == sq)
+    }
+
+    /// Whether no two pieces are on the same square and no pawn is on the first or last rank.
+    pub fn is_valid(&self) -> bool {
+        self.pieces.iter().enumerate().all(|(i, &(piece, sq))| {
+            !(piece.is_pawn() && (rank(sq) == 0 || rank(sq) == 7))
+                && self.pieces[i + 1..].iter().all(|&(_, other)| other != sq)
+        })
+    }
+
+    /// Whether the side to move could capture the other king, which makes the position impossible.
+    pub fn is_illegal(&self) -> bool {
+        let king = self.king(!self.white_to_move);
+        self.attacked(king, self.white_to_move)
+    }
+
+    pub fn in_check(&self) -> bool {
+        let king = self.king(self.white_to_move);
+        self.attacked(king, !self.white_to_move)
+    }
+
+    fn king(&self, white: bool) -> Square {
+        self.pieces.iter()
+            .find(|(p, _)| p.is_king() && (p.color() == Color::White) == white)
+            .expect("both kings are always on the board")
+            .1
+    }
+
+    /// Whether a piece of `by_white` attacks `target`.
+    pub fn attacked(&self, target: Square, by_white: bool) -> bool {
+        self.pieces.iter()
+            .filter(|(p, _)| (p.color() == Color::White) == by_white)
+            .any(|&(piece, from)| self.attacks(piece, from, target))
+    }
+
+    fn attacks(&self, piece: Piece, from: Square, to: Square) -> bool {
+        let (df, dr) = (file(to) - file(from), rank(to) - rank(from));
+
+        if piece.is_king() {
+            from != to && df.abs() <= 1 && dr.abs() <= 1
+        } else if piece.is_knight() {
+            df.abs() * dr.abs() == 2
+        } else if piece.is_pawn() {
+            let forward = if piece.color() == Color::White { 1 } else { -1 };
+            dr == forward && df.abs() == 1
+        } else {
+            let straight = df == 0 || dr == 0;
+            let diagonal = df.abs() == dr.abs();
+            let moves_like = (piece.is_rook() && straight) || (piece.is_bishop() && diagonal) || (piece.is_queen() && (straight || diagonal));
+            if from == to || !moves_like {
+                return false;
+            }
+
+            // nothing in between
+            let (sf, sr) = (df.signum(), dr.signum());
+            let (mut f, mut r) = (file(from) + sf, rank(from) + sr);
+            while (f, r) != (file(to), rank(to)) {
+                if self.at((r * 8 + f) as Square).is_some() {
+                    return false;
+                }
+                f += sf;
+                r += sr;
+            }
+            true
+        }
+    }
+
+    /// The squares `piece` on `from` can move to when they're empty, or capture on when there's
+    /// an enemy. Pawns only get their captures here, their pushes are separate.
+    pub fn targets(&self, piece: Piece, from: Square) -> Vec<Square> {
+        const KING: [(i8, i8); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+        const KNIGHT: [(i8, i8); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+        const STRAIGHT: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+        const DIAGONAL: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+        let step = |&(df, dr): &(i8, i8)| square(file(from) + df, rank(from) + dr);
+
+        if piece.is_king() {
+            KING.iter().filter_map(step).collect()
+        } else if piece.is_knight() {
+            KNIGHT.iter().filter_map(step).collect()
+        } else if piece.is_pawn() {
+            let forward = if piece.color() == Color::White { 1 } else { -1 };
+            [(-1, forward), (1, forward)].iter().filter_map(step).collect()
+        } else {
+            let directions: Vec<&(i8, i8)> = if piece.is_rook() {
+                STRAIGHT.iter().collect()
+            } else if piece.is_bishop() {
+                DIAGONAL.iter().collect()
+            } else {
+                STRAIGHT.iter().chain(DIAGONAL.iter()).collect()
+            };
+
+            let mut targets = Vec::new();
+            for &(df, dr) in directions {
+                let (mut f, mut r) = (file(from) + df, rank(from) + dr);
+                while let Some(sq) = square(f, r) {
+                    targets.push(sq);
+                    if self.at(sq).is_some() {
+                        break;
+                    }
+                    f += df;
+                    r += dr;
+                }
+            }
+            targets
+        }
+    }
+}
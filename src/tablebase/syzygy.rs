@@ -0,0 +1,1385 @@
+//! Probing of Syzygy tablebases: the standard `.rtbw` (win/draw/loss) and `.rtbz` (distance to
+//! zeroing) files, read from a local directory.
+//!
+//! Unlike the tables of the `generator`, Syzygy tables know about the fifty-move rule. A win
+//! that takes more than 100 plies without a capture or pawn move is a *cursed* win and a loss
+//! that is saved by the rule a *blessed* loss, and both are draws. The win/draw/loss values are
+//! right just after a capture or pawn move, so the search only probes those, and the distance
+//! to zeroing is used at the root to choose moves that keep the result with the clock as it is.
+
+use crate::game_engine::board::Board;
+use crate::game_engine::chess_move::Move;
+use crate::game_engine::color::Color;
+use crate::game_engine::piece::Piece;
+use crate::tablebase::castling_possible;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+const WDL_EXTENSION: &str = "rtbw";
+const DTZ_EXTENSION: &str = "rtbz";
+
+/// The most pieces a Syzygy table has.
+const TBPIECES: usize = 7;
+
+/// The score of a position that is won in the tables, below the wins of the tables of the
+/// `generator`, which also know how fast the win is.
+pub const WIN_SCORE: f64 = super::WIN_SCORE - 1000.0;
+
+/// The result of a position for the side to move, with the fifty-move rule.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Wdl {
+    Loss = -2,
+    /// Lost, but saved by the fifty-move rule
+    BlessedLoss = -1,
+    Draw = 0,
+    /// Won, but not before the fifty-move rule draws the game
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// The score of the position, positive when white wins. Cursed wins and blessed losses
+    /// are draws, but score a little better or worse than `draw_score`.
+    pub fn score(&self, current: Color, draw_score: f64) -> f64 {
+        let score = match self {
+            Wdl::Loss => -WIN_SCORE,
+            Wdl::BlessedLoss => -1.0,
+            Wdl::Draw => 0.0,
+            Wdl::CursedWin => 1.0,
+            Wdl::Win => WIN_SCORE,
+        };
+
+        draw_score + if current == Color::White { score } else { -score }
+    }
+}
+
+/// The piece letters in the order of the table names.
+const PCHR: [char; 6] = ['K', 'Q', 'R', 'B', 'N', 'P'];
+
+/// The index of the first king among the ten squares a1-d1-d4 it is mirrored to.
+#[rustfmt::skip]
+const TRIANGLE: [usize; 64] = [
+    6, 0, 1, 2, 2, 1, 0, 6,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    6, 0, 1, 2, 2, 1, 0, 6,
+];
+
+const INVTRIANGLE: [usize; 10] = [1, 2, 3, 10, 11, 19, 0, 9, 18, 27];
+
+#[rustfmt::skip]
+const LOWER: [usize; 64] = [
+    28,  0,  1,  2,  3,  4,  5,  6,
+     0, 29,  7,  8,  9, 10, 11, 12,
+     1,  7, 30, 13, 14, 15, 16, 17,
+     2,  8, 13, 31, 18, 19, 20, 21,
+     3,  9, 14, 18, 32, 22, 23, 24,
+     4, 10, 15, 19, 22, 33, 25, 26,
+     5, 11, 16, 20, 23, 25, 34, 27,
+     6, 12, 17, 21, 24, 26, 27, 35,
+];
+
+#[rustfmt::skip]
+const DIAG: [usize; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  8,
+     0,  1,  0,  0,  0,  0,  9,  0,
+     0,  0,  2,  0,  0, 10,  0,  0,
+     0,  0,  0,  3, 11,  0,  0,  0,
+     0,  0,  0, 12,  4,  0,  0,  0,
+     0,  0, 13,  0,  0,  5,  0,  0,
+     0, 14,  0,  0,  0,  0,  6,  0,
+    15,  0,  0,  0,  0,  0,  0,  7,
+];
+
+#[rustfmt::skip]
+const FLAP: [usize; 64] = [
+    0,  0,  0,  0,  0,  0,  0, 0,
+    0,  6, 12, 18, 18, 12,  6, 0,
+    1,  7, 13, 19, 19, 13,  7, 1,
+    2,  8, 14, 20, 20, 14,  8, 2,
+    3,  9, 15, 21, 21, 15,  9, 3,
+    4, 10, 16, 22, 22, 16, 10, 4,
+    5, 11, 17, 23, 23, 17, 11, 5,
+    0,  0,  0,  0,  0,  0,  0, 0,
+];
+
+#[rustfmt::skip]
+const PTWIST: [usize; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    47, 35, 23, 11, 10, 22, 34, 46,
+    45, 33, 21,  9,  8, 20, 32, 44,
+    43, 31, 19,  7,  6, 18, 30, 42,
+    41, 29, 17,  5,  4, 16, 28, 40,
+    39, 27, 15,  3,  2, 14, 26, 38,
+    37, 25, 13,  1,  0, 12, 24, 36,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const INVFLAP: [usize; 24] = [
+     8, 16, 24, 32, 40, 48,
+     9, 17, 25, 33, 41, 49,
+    10, 18, 26, 34, 42, 50,
+    11, 19, 27, 35, 43, 51,
+];
+
+const FILE_TO_FILE: [usize; 8] = [0, 1, 2, 3, 3, 2, 1, 0];
+
+/// The number of placements of the leading pieces, by encoding type.
+const PIVFAC: [u64; 3] = [31332, 28056, 462];
+
+/// Which of the four maps of a DTZ table a result uses, and which flags say it is stored in plies.
+const WDL_TO_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+const PA_FLAGS: [u8; 5] = [8, 0, 0, 0, 4];
+
+/// Larger than any distance to zeroing, to rank moves at the root.
+const MAX_DTZ: i32 = 1 << 18;
+
+struct Indices {
+    /// `binomial[k][n]` is `n` choose `k + 1`
+    binomial: [[u64; 64]; TBPIECES - 1],
+    pawn_index: [[u64; 24]; TBPIECES - 1],
+    pawn_factor: [[u64; 4]; TBPIECES - 1],
+    /// The index of the two kings, for the first king on the triangle and the second anywhere
+    kk_index: [[i32; 64]; 10],
+}
+
+impl Indices {
+    fn new() -> Self {
+        let mut binomial = [[0; 64]; TBPIECES - 1];
+        for (k, row) in binomial.iter_mut().enumerate() {
+            for (n, value) in row.iter_mut().enumerate() {
+                *value = subfactor(k + 1, n as u64);
+            }
+        }
+
+        let mut pawn_index = [[0; 24]; TBPIECES - 1];
+        let mut pawn_factor = [[0; 4]; TBPIECES - 1];
+        for i in 0..TBPIECES - 1 {
+            for (file, factor) in pawn_factor[i].iter_mut().enumerate() {
+                let mut s = 0;
+                for j in 6 * file..6 * file + 6 {
+                    pawn_index[i][j] = s;
+                    s += if i == 0 { 1 } else { binomial[i - 1][PTWIST[INVFLAP[j]]] };
+                }
+                *factor = s;
+            }
+        }
+
+        Self { binomial, pawn_index, pawn_factor, kk_index: kk_index() }
+    }
+}
+
+/// The second king goes anywhere when the first one is off the diagonal, else below the
+/// diagonal first and on it last.
+fn kk_index() -> [[i32; 64]; 10] {
+    let touching = |a: usize, b: usize| {
+        (file(a) - file(b)).abs() <= 1 && (rank(a) - rank(b)).abs() <= 1
+    };
+
+    let mut index = [[-1; 64]; 10];
+    let mut next = 0;
+    for (t, row) in index.iter_mut().enumerate().take(6) {
+        for (sq, value) in row.iter_mut().enumerate() {
+            if !touching(INVTRIANGLE[t], sq) {
+                *value = next;
+                next += 1;
+            }
+        }
+    }
+    for below in [true, false] {
+        for (t, row) in index.iter_mut().enumerate().skip(6) {
+            for (sq, value) in row.iter_mut().enumerate() {
+                let fits = if below { offdiag(sq) < 0 } else { offdiag(sq) == 0 };
+                if fits && !touching(INVTRIANGLE[t], sq) {
+                    *value = next;
+                    next += 1;
+                }
+            }
+        }
+    }
+
+    index
+}
+
+lazy_static!(static ref INDICES: Indices = Indices::new(););
+
+fn file(sq: usize) -> i32 {
+    (sq & 7) as i32
+}
+
+fn rank(sq: usize) -> i32 {
+    (sq >> 3) as i32
+}
+
+fn offdiag(sq: usize) -> i32 {
+    rank(sq) - file(sq)
+}
+
+fn flipdiag(sq: usize) -> usize {
+    ((sq >> 3) | (sq << 3)) & 63
+}
+
+/// `n` choose `k`.
+fn subfactor(k: usize, n: u64) -> u64 {
+    let mut f = 1;
+    let mut l = 1;
+    for i in 0..k as u64 {
+        f *= n.saturating_sub(i);
+        l *= i + 1;
+    }
+    f / l
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u32_be_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_be_at(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// The pieces of a table name in the order of `PCHR`, with the stronger side first
+/// (or the other way around when `mirror`).
+fn normalize(name: &str, mirror: bool) -> Option<String> {
+    let (white, black) = name.split_once('v')?;
+    let order = |side: &str| -> Option<Vec<usize>> {
+        let mut order = side.chars().map(|c| PCHR.iter().position(|&p| p == c)).collect::<Option<Vec<_>>>()?;
+        order.sort_unstable();
+        Some(order)
+    };
+    let (white, black) = (order(white)?, order(black)?);
+    let letters = |side: &[usize]| side.iter().map(|&i| PCHR[i]).collect::<String>();
+
+    if mirror ^ ((white.len(), &black) < (black.len(), &white)) {
+        Some(format!("{}v{}", letters(&black), letters(&white)))
+    } else {
+        Some(format!("{}v{}", letters(&white), letters(&black)))
+    }
+}
+
+/// The pieces of a position as in the tables: the type from 1 (pawn) to 6 (king), plus 8 for black.
+fn piece_code(piece: Piece) -> u8 {
+    let kind = if piece.is_pawn() {
+        1
+    } else if piece.is_knight() {
+        2
+    } else if piece.is_bishop() {
+        3
+    } else if piece.is_rook() {
+        4
+    } else if piece.is_queen() {
+        5
+    } else {
+        6
+    };
+
+    if piece.color() == Color::Black { kind | 8 } else { kind }
+}
+
+/// The pieces of a board, by square from a1 to h8.
+struct Pieces {
+    pieces: Vec<(u8, usize)>,
+    white_to_move: bool,
+}
+
+impl Pieces {
+    fn of(board: &impl Board) -> Self {
+        let mut pieces = Vec::new();
+        for sq in 0..64 {
+            // boards count ranks from black's side
+            let piece = board.piece_at(((sq & 7) as i8, (7 - (sq >> 3)) as i8));
+            if !piece.is_empty() {
+                pieces.push((piece_code(piece), sq));
+            }
+        }
+
+        Self { pieces, white_to_move: board.current_player() == Color::White }
+    }
+
+    /// The name of the table of the pieces, white first (black first when `mirror`).
+    fn key(&self, mirror: bool) -> String {
+        let side = |black: bool| {
+            let mut letters = String::new();
+            for (letter, kind) in PCHR.iter().zip([6, 5, 4, 3, 2, 1]) {
+                let code = if black { kind | 8 } else { kind };
+                for _ in self.pieces.iter().filter(|&&(c, _)| c == code) {
+                    letters.push(*letter);
+                }
+            }
+            letters
+        };
+
+        format!("{}v{}", side(mirror), side(!mirror))
+    }
+
+    /// Puts the squares of `piece` (with the colours flipped by `cmirror`) at `p[i..]`.
+    fn fill(&self, piece: u8, cmirror: u8, mirror: usize, p: &mut [usize; TBPIECES], mut i: usize) -> usize {
+        for &(code, sq) in &self.pieces {
+            if code == piece ^ cmirror && i < TBPIECES {
+                p[i] = sq ^ mirror;
+                i += 1;
+            }
+        }
+        i
+    }
+}
+
+/// What the name of a table says about it.
+struct Header {
+    key: String,
+    symmetric: bool,
+    num: usize,
+    has_pawns: bool,
+    /// The number of pawns of the leading side, which has the fewest pawns, and of the other side
+    pawns: [usize; 2],
+    enc_type: usize,
+}
+
+impl Header {
+    fn parse(name: &str) -> Option<Self> {
+        let key = normalize(name, false)?;
+        let (white, black) = name.split_once('v')?;
+        let num = name.len() - 1;
+        if num > TBPIECES || !white.starts_with('K') || !black.starts_with('K') {
+            return None;
+        }
+
+        let count = |side: &str, c: char| side.chars().filter(|&p| p == c).count();
+        let mut pawns = [count(white, 'P'), count(black, 'P')];
+        if pawns[1] > 0 && (pawns[0] == 0 || pawns[1] < pawns[0]) {
+            pawns.swap(0, 1);
+        }
+
+        let unique = PCHR.iter()
+            .map(|&c| (count(white, c) == 1) as usize + (count(black, c) == 1) as usize)
+            .sum::<usize>();
+
+        Some(Self {
+            symmetric: key == normalize(name, true)?,
+            key,
+            num,
+            has_pawns: pawns[0] > 0,
+            pawns,
+            enc_type: if unique >= 3 { 0 } else { 2 },
+        })
+    }
+
+    /// The colour flip, the rank flip and the side of the table to look up a position in.
+    fn orientation(&self, key: &str, white_to_move: bool) -> (u8, usize, usize) {
+        let flip = if self.symmetric { !white_to_move } else { key != self.key };
+        let side = (flip == white_to_move) as usize;
+
+        if flip { (8, 0x38, side) } else { (0, 0, side) }
+    }
+}
+
+/// How the positions of a table (for one side, and one file of the leading pawn) are indexed.
+#[derive(Default)]
+struct Encoding {
+    pieces: Vec<u8>,
+    norm: Vec<usize>,
+    factor: [u64; TBPIECES],
+    size: u64,
+}
+
+impl Encoding {
+    fn pieces(header: &Header, data: &[u8], at: usize, high: bool) -> Option<Self> {
+        let nibble = |b: u8| if high { b >> 4 } else { b & 0x0f };
+        let order = nibble(*data.get(at)?) as usize;
+        let pieces = (0..header.num).map(|i| data.get(at + i + 1).map(|&b| nibble(b))).collect::<Option<Vec<_>>>()?;
+
+        let mut norm = vec![0; header.num];
+        let leading = if header.enc_type == 0 { 3 } else { 2 };
+        norm[0] = leading;
+        Self::group(&mut norm, &pieces, leading);
+
+        let mut encoding = Self { pieces, norm, ..Self::default() };
+        let mut n = 64 - encoding.norm[0] as u64;
+        let mut f = 1;
+        let mut i = encoding.norm[0];
+        let mut k = 0;
+        while i < header.num || k == order {
+            if k == order {
+                encoding.factor[0] = f;
+                f *= PIVFAC[header.enc_type];
+            } else {
+                encoding.factor[i] = f;
+                f *= subfactor(encoding.norm[i], n);
+                n -= encoding.norm[i] as u64;
+                i += encoding.norm[i];
+            }
+            k += 1;
+        }
+        encoding.size = f;
+
+        Some(encoding)
+    }
+
+    fn pawns(header: &Header, data: &[u8], at: usize, file: usize, high: bool) -> Option<Self> {
+        let nibble = |b: u8| if high { b >> 4 } else { b & 0x0f };
+        let skip = 1 + (header.pawns[1] > 0) as usize;
+        let order = nibble(*data.get(at)?) as usize;
+        let order2 = if header.pawns[1] > 0 { nibble(*data.get(at + 1)?) as usize } else { 0x0f };
+        let pieces = (0..header.num).map(|i| data.get(at + i + skip).map(|&b| nibble(b))).collect::<Option<Vec<_>>>()?;
+
+        let mut norm = vec![0; header.num];
+        norm[0] = header.pawns[0];
+        if header.pawns[1] > 0 {
+            norm[header.pawns[0]] = header.pawns[1];
+        }
+        Self::group(&mut norm, &pieces, header.pawns[0] + header.pawns[1]);
+
+        let mut encoding = Self { pieces, norm, ..Self::default() };
+        let mut i = encoding.norm[0];
+        if order2 < 0x0f {
+            i += encoding.norm[i];
+        }
+        let mut n = 64 - i as u64;
+        let mut f = 1;
+        let mut k = 0;
+        while i < header.num || k == order || k == order2 {
+            if k == order {
+                encoding.factor[0] = f;
+                f *= INDICES.pawn_factor[encoding.norm[0] - 1][file];
+            } else if k == order2 {
+                let leading = encoding.norm[0];
+                encoding.factor[leading] = f;
+                f *= subfactor(encoding.norm[leading], 48 - leading as u64);
+            } else {
+                encoding.factor[i] = f;
+                f *= subfactor(encoding.norm[i], n);
+                n -= encoding.norm[i] as u64;
+                i += encoding.norm[i];
+            }
+            k += 1;
+        }
+        encoding.size = f;
+
+        Some(encoding)
+    }
+
+    /// Counts the runs of identical pieces from `i` on.
+    fn group(norm: &mut [usize], pieces: &[u8], mut i: usize) {
+        while i < pieces.len() {
+            let mut j = i;
+            while j < pieces.len() && pieces[j] == pieces[i] {
+                norm[i] += 1;
+                j += 1;
+            }
+            i += norm[i];
+        }
+    }
+
+    /// The index of the pieces from `i` on, in groups of identical pieces.
+    fn encode_rest(&self, pos: &mut [usize; TBPIECES], mut i: usize, num: usize) -> u64 {
+        let mut idx = 0;
+        while i < num {
+            let t = self.norm[i];
+            pos[i..i + t].sort_unstable();
+
+            let mut s = 0;
+            for m in i..i + t {
+                let p = pos[m];
+                let below = pos[..i].iter().filter(|&&q| p > q).count();
+                s += INDICES.binomial[m - i][p - below];
+            }
+            idx += s * self.factor[i];
+            i += t;
+        }
+        idx
+    }
+
+    fn encode_piece(&self, header: &Header, pos: &mut [usize; TBPIECES]) -> u64 {
+        let n = header.num;
+        if pos[0] & 0x04 != 0 {
+            pos[..n].iter_mut().for_each(|p| *p ^= 0x07);
+        }
+        if pos[0] & 0x20 != 0 {
+            pos[..n].iter_mut().for_each(|p| *p ^= 0x38);
+        }
+        let first = (0..n).find(|&i| offdiag(pos[i]) != 0).unwrap_or(n);
+        if first < (if header.enc_type == 0 { 3 } else { 2 }) && offdiag(pos[first]) > 0 {
+            pos[..n].iter_mut().for_each(|p| *p = flipdiag(*p));
+        }
+
+        let (idx, i) = if header.enc_type == 0 {
+            let i = (pos[1] > pos[0]) as usize;
+            let j = (pos[2] > pos[0]) as usize + (pos[2] > pos[1]) as usize;
+            let idx = if offdiag(pos[0]) != 0 {
+                TRIANGLE[pos[0]] * 63 * 62 + (pos[1] - i) * 62 + (pos[2] - j)
+            } else if offdiag(pos[1]) != 0 {
+                6 * 63 * 62 + DIAG[pos[0]] * 28 * 62 + LOWER[pos[1]] * 62 + pos[2] - j
+            } else if offdiag(pos[2]) != 0 {
+                6 * 63 * 62 + 4 * 28 * 62 + DIAG[pos[0]] * 7 * 28 + (DIAG[pos[1]] - i) * 28 + LOWER[pos[2]]
+            } else {
+                6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + DIAG[pos[0]] * 7 * 6 + (DIAG[pos[1]] - i) * 6 + (DIAG[pos[2]] - j)
+            };
+            (idx as u64, 3)
+        } else {
+            (INDICES.kk_index[TRIANGLE[pos[0]]][pos[1]].max(0) as u64, 2)
+        };
+
+        idx * self.factor[0] + self.encode_rest(pos, i, n)
+    }
+
+    fn encode_pawn(&self, header: &Header, pos: &mut [usize; TBPIECES]) -> u64 {
+        let n = header.num;
+        if pos[0] & 0x04 != 0 {
+            pos[..n].iter_mut().for_each(|p| *p ^= 0x07);
+        }
+
+        let leading = header.pawns[0];
+        pos[1..leading].sort_unstable_by_key(|&p| std::cmp::Reverse(PTWIST[p]));
+
+        let t = leading - 1;
+        let mut idx = INDICES.pawn_index[t][FLAP[pos[0]]];
+        for i in (1..=t).rev() {
+            idx += INDICES.binomial[t - i][PTWIST[pos[i]]];
+        }
+        idx *= self.factor[0];
+
+        // the pawns of the other side, which can't be on the first and last rank
+        let mut i = leading;
+        let end = i + header.pawns[1];
+        if end > i {
+            pos[i..end].sort_unstable();
+            let mut s = 0;
+            for m in i..end {
+                let p = pos[m];
+                let below = pos[..i].iter().filter(|&&q| p > q).count();
+                s += INDICES.binomial[m - i][p - below - 8];
+            }
+            idx += s * self.factor[i];
+            i = end;
+        }
+
+        idx + self.encode_rest(pos, i, n)
+    }
+}
+
+/// Puts the leading pawn with the lowest `FLAP` first, and returns its file mirrored to a to d.
+fn pawn_file(header: &Header, pos: &mut [usize; TBPIECES]) -> usize {
+    for i in 1..header.pawns[0] {
+        if FLAP[pos[0]] > FLAP[pos[i]] {
+            pos.swap(0, i);
+        }
+    }
+    FILE_TO_FILE[pos[0] & 0x07]
+}
+
+/// The compressed values of one side and file of a table: blocks of symbols with canonical
+/// Huffman codes, where each symbol stands for a pair of symbols or a value.
+#[derive(Default)]
+struct PairsData {
+    flags: u8,
+    idxbits: u32,
+    blocksize: u32,
+    min_len: u32,
+    /// Where the first symbol of each code length is stored
+    offset: usize,
+    sympat: usize,
+    symlen: Vec<u32>,
+    base: Vec<u64>,
+    indextable: usize,
+    sizetable: usize,
+    data: usize,
+    /// The sizes of the index table, the size table and the blocks
+    sizes: [u64; 3],
+}
+
+impl PairsData {
+    /// Reads the header at `at`, and returns it with where the next one starts.
+    fn setup(data: &[u8], at: usize, tb_size: u64, wdl: bool) -> Option<(Self, usize)> {
+        let flags = *data.get(at)?;
+        if flags & 0x80 != 0 {
+            // all positions have the same value
+            let min_len = if wdl { *data.get(at + 1)? as u32 } else { 0 };
+            return Some((Self { flags, min_len, ..Self::default() }, at + 2));
+        }
+
+        let blocksize = *data.get(at + 1)? as u32;
+        let idxbits = *data.get(at + 2)? as u32;
+        let real_num_blocks = u32_at(data, at + 4)? as u64;
+        let num_blocks = real_num_blocks + *data.get(at + 3)? as u64;
+        let max_len = *data.get(at + 8)? as usize;
+        let min_len = *data.get(at + 9)? as usize;
+        if max_len < min_len || min_len == 0 || idxbits == 0 || idxbits >= 64 {
+            return None;
+        }
+        let h = max_len - min_len + 1;
+        let num_syms = u16_at(data, at + 10 + 2 * h)? as usize;
+
+        let mut pairs = Self {
+            flags,
+            idxbits,
+            blocksize,
+            min_len: min_len as u32,
+            offset: at + 10,
+            sympat: at + 12 + 2 * h,
+            symlen: vec![0; num_syms],
+            ..Self::default()
+        };
+        let next = at + 12 + 2 * h + 3 * num_syms + (num_syms & 1);
+
+        let num_indices = (tb_size + (1 << idxbits) - 1) >> idxbits;
+        pairs.sizes = [6 * num_indices, 2 * num_blocks, (1 << blocksize) * real_num_blocks];
+
+        let mut done = vec![false; num_syms];
+        for s in 0..num_syms {
+            if !done[s] {
+                pairs.symbol_length(data, s, &mut done)?;
+            }
+        }
+
+        pairs.base = vec![0; h];
+        for i in (0..h - 1).rev() {
+            let step = u16_at(data, pairs.offset + 2 * i)? as u64 - u16_at(data, pairs.offset + 2 * i + 2)? as u64;
+            pairs.base[i] = (pairs.base[i + 1].wrapping_add(step)) / 2;
+        }
+        for (i, base) in pairs.base.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - (min_len + i) as u32).unwrap_or(0);
+        }
+
+        Some((pairs, next))
+    }
+
+    /// The number of values a symbol stands for, minus one.
+    fn symbol_length(&mut self, data: &[u8], s: usize, done: &mut [bool]) -> Option<()> {
+        let w = self.sympat + 3 * s;
+        let s2 = ((*data.get(w + 2)? as usize) << 4) | (*data.get(w + 1)? as usize >> 4);
+        if s2 == 0x0fff {
+            self.symlen[s] = 0;
+        } else {
+            let s1 = ((*data.get(w + 1)? as usize & 0x0f) << 8) | *data.get(w)? as usize;
+            if s1 >= done.len() || s2 >= done.len() {
+                return None;
+            }
+            // mark it before descending, so broken files can't recurse forever
+            done[s] = true;
+            if !done[s1] {
+                self.symbol_length(data, s1, done)?;
+            }
+            if !done[s2] {
+                self.symbol_length(data, s2, done)?;
+            }
+            self.symlen[s] = self.symlen[s1] + self.symlen[s2] + 1;
+        }
+        done[s] = true;
+        Some(())
+    }
+
+    fn offset_at(&self, data: &[u8], len: u32) -> Option<u64> {
+        Some(u16_at(data, self.offset + 2 * (len - self.min_len) as usize)? as u64)
+    }
+
+    /// The value of the position with index `idx`.
+    fn decompress(&self, data: &[u8], idx: u64) -> Option<u32> {
+        if self.idxbits == 0 {
+            return Some(self.min_len);
+        }
+
+        let mainidx = (idx >> self.idxbits) as usize;
+        let mut litidx = (idx & ((1 << self.idxbits) - 1)) as i64 - (1 << (self.idxbits - 1));
+        let mut block = u32_at(data, self.indextable + 6 * mainidx)? as usize;
+        litidx += u16_at(data, self.indextable + 6 * mainidx + 4)? as i64;
+
+        let block_size = |block: usize| u16_at(data, self.sizetable + 2 * block).map(|s| s as i64);
+        if litidx < 0 {
+            while litidx < 0 {
+                block = block.checked_sub(1)?;
+                litidx += block_size(block)? + 1;
+            }
+        } else {
+            while litidx > block_size(block)? {
+                litidx -= block_size(block)? + 1;
+                block += 1;
+            }
+        }
+
+        let mut ptr = self.data + (block << self.blocksize);
+        let mut code = u64_be_at(data, ptr)?;
+        ptr += 8;
+        // the number of bits at the end of `code` that still have to be read
+        let mut bitcnt = 0;
+        let mut sym;
+        loop {
+            let mut len = self.min_len;
+            while code < *self.base.get((len - self.min_len) as usize)? {
+                len += 1;
+            }
+            sym = (self.offset_at(data, len)? + ((code - self.base[(len - self.min_len) as usize]) >> (64 - len))) as usize;
+            let length = *self.symlen.get(sym)? as i64;
+            if litidx < length + 1 {
+                break;
+            }
+            litidx -= length + 1;
+            code <<= len;
+            bitcnt += len;
+            if bitcnt >= 32 {
+                bitcnt -= 32;
+                code |= (u32_be_at(data, ptr)? as u64) << bitcnt;
+                ptr += 4;
+            }
+        }
+
+        while self.symlen[sym] != 0 {
+            let w = self.sympat + 3 * sym;
+            let s1 = ((*data.get(w + 1)? as usize & 0x0f) << 8) | *data.get(w)? as usize;
+            let length = *self.symlen.get(s1)? as i64;
+            if litidx < length + 1 {
+                sym = s1;
+            } else {
+                litidx -= length + 1;
+                sym = ((*data.get(w + 2)? as usize) << 4) | (*data.get(w + 1)? as usize >> 4);
+            }
+        }
+
+        Some(*data.get(self.sympat + 3 * sym)? as u32)
+    }
+}
+
+/// Moves the pointer to the next multiple of 64.
+fn align(at: usize) -> usize {
+    (at + 0x3f) & !0x3f
+}
+
+/// Reads where the index tables, size tables and blocks of all `pairs` are, which follow each other in this order.
+fn place(pairs: &mut [&mut PairsData], mut at: usize) -> usize {
+    for p in pairs.iter_mut() {
+        p.indextable = at;
+        at += p.sizes[0] as usize;
+    }
+    for p in pairs.iter_mut() {
+        p.sizetable = at;
+        at += p.sizes[1] as usize;
+    }
+    for p in pairs.iter_mut() {
+        at = align(at);
+        p.data = at;
+        at += p.sizes[2] as usize;
+    }
+    at
+}
+
+struct Part {
+    encoding: Encoding,
+    pairs: PairsData,
+}
+
+impl Part {
+    fn value(&self, header: &Header, data: &[u8], pos: &mut [usize; TBPIECES]) -> Option<u32> {
+        let idx = if header.has_pawns {
+            self.encoding.encode_pawn(header, pos)
+        } else {
+            self.encoding.encode_piece(header, pos)
+        };
+        self.pairs.decompress(data, idx)
+    }
+}
+
+fn check_magic(data: &[u8], magic: &[u8; 4]) -> Option<()> {
+    (data.get(..4)? == magic).then_some(())
+}
+
+/// The encodings of both sides for every file of the leading pawn (only one "file" without pawns).
+fn encodings(header: &Header, data: &[u8], mut at: usize) -> Option<(Vec<[Encoding; 2]>, usize)> {
+    let mut encodings = Vec::new();
+    if header.has_pawns {
+        for file in 0..4 {
+            encodings.push([
+                Encoding::pawns(header, data, at, file, false)?,
+                Encoding::pawns(header, data, at, file, true)?,
+            ]);
+            at += header.num + 1 + (header.pawns[1] > 0) as usize;
+        }
+    } else {
+        encodings.push([Encoding::pieces(header, data, at, false)?, Encoding::pieces(header, data, at, true)?]);
+        at += header.num + 1;
+    }
+
+    Some((encodings, at + (at & 1)))
+}
+
+struct WdlTable {
+    data: Vec<u8>,
+    /// By file of the leading pawn and by side to move
+    parts: Vec<[Option<Part>; 2]>,
+}
+
+impl WdlTable {
+    fn read(header: &Header, data: Vec<u8>) -> Option<Self> {
+        check_magic(&data, &WDL_MAGIC)?;
+        let flags = *data.get(4)?;
+        let split = flags & 0x01 != 0;
+        let files = if header.has_pawns && flags & 0x02 != 0 { 4 } else { 1 };
+
+        let (encodings, mut at) = encodings(header, &data, 5)?;
+        let mut parts = Vec::new();
+        for [white, black] in encodings.into_iter().take(files) {
+            let (pairs, next) = PairsData::setup(&data, at, white.size, true)?;
+            at = next;
+            let white = Part { encoding: white, pairs };
+            let black = if split {
+                let (pairs, next) = PairsData::setup(&data, at, black.size, true)?;
+                at = next;
+                Some(Part { encoding: black, pairs })
+            } else {
+                None
+            };
+            parts.push([Some(white), black]);
+        }
+
+        let mut pairs = parts.iter_mut()
+            .flat_map(|sides| sides.iter_mut().flatten().map(|part| &mut part.pairs))
+            .collect::<Vec<_>>();
+        place(&mut pairs, at);
+
+        Some(Self { data, parts })
+    }
+
+    fn probe(&self, header: &Header, pieces: &Pieces, key: &str) -> Option<i32> {
+        let (cmirror, mirror, side) = header.orientation(key, pieces.white_to_move);
+        let mut p = [0; TBPIECES];
+
+        let part = if header.has_pawns {
+            let lead = self.parts[0][0].as_ref()?.encoding.pieces[0];
+            let mut i = pieces.fill(lead, cmirror, mirror, &mut p, 0);
+            let part = self.parts.get(pawn_file(header, &mut p))?[side].as_ref()?;
+            while i < header.num {
+                i = pieces.fill(part.encoding.pieces[i], cmirror, mirror, &mut p, i);
+            }
+            part
+        } else {
+            let part = self.parts[0][side].as_ref()?;
+            let mut i = 0;
+            while i < header.num {
+                i = pieces.fill(part.encoding.pieces[i], cmirror, mirror, &mut p, i);
+            }
+            part
+        };
+
+        Some(part.value(header, &self.data, &mut p)? as i32 - 2)
+    }
+}
+
+struct DtzTable {
+    data: Vec<u8>,
+    /// By file of the leading pawn, for the one side to move the table has
+    parts: Vec<Part>,
+    map: usize,
+    map_idx: Vec<[usize; 4]>,
+}
+
+/// What a DTZ table says about a position.
+enum Dtz {
+    Plies(i32),
+    /// The table only has the positions with the other side to move.
+    OtherSide,
+}
+
+impl DtzTable {
+    fn read(header: &Header, data: Vec<u8>) -> Option<Self> {
+        check_magic(&data, &DTZ_MAGIC)?;
+        let files = if header.has_pawns && *data.get(4)? & 0x02 != 0 { 4 } else { 1 };
+
+        let (encodings, mut at) = encodings(header, &data, 5)?;
+        let mut parts = Vec::new();
+        for [encoding, _] in encodings.into_iter().take(files) {
+            let (pairs, next) = PairsData::setup(&data, at, encoding.size, false)?;
+            at = next;
+            parts.push(Part { encoding, pairs });
+        }
+
+        let map = at;
+        let mut map_idx = Vec::new();
+        for part in &parts {
+            let mut idx = [0; 4];
+            if part.pairs.flags & 2 != 0 {
+                if part.pairs.flags & 16 == 0 {
+                    for i in &mut idx {
+                        *i = at + 1 - map;
+                        at += 1 + *data.get(at)? as usize;
+                    }
+                } else {
+                    at += at & 1;
+                    for i in &mut idx {
+                        *i = (at + 2 - map) / 2;
+                        at += 2 + 2 * u16_at(&data, at)? as usize;
+                    }
+                }
+            }
+            map_idx.push(idx);
+        }
+        at += at & 1;
+
+        let mut pairs = parts.iter_mut().map(|part| &mut part.pairs).collect::<Vec<_>>();
+        place(&mut pairs, at);
+
+        Some(Self { data, parts, map, map_idx })
+    }
+
+    fn probe(&self, header: &Header, pieces: &Pieces, key: &str, wdl: i32) -> Option<Dtz> {
+        let (cmirror, mirror, side) = header.orientation(key, pieces.white_to_move);
+        let mut p = [0; TBPIECES];
+
+        let (part, file) = if header.has_pawns {
+            let mut i = pieces.fill(self.parts[0].encoding.pieces[0], cmirror, mirror, &mut p, 0);
+            let file = pawn_file(header, &mut p);
+            let part = self.parts.get(file)?;
+            if (part.pairs.flags & 1) as usize != side {
+                return Some(Dtz::OtherSide);
+            }
+            while i < header.num {
+                i = pieces.fill(part.encoding.pieces[i], cmirror, mirror, &mut p, i);
+            }
+            (part, file)
+        } else {
+            let part = &self.parts[0];
+            if (part.pairs.flags & 1) as usize != side && !header.symmetric {
+                return Some(Dtz::OtherSide);
+            }
+            let mut i = 0;
+            while i < header.num {
+                i = pieces.fill(part.encoding.pieces[i], cmirror, mirror, &mut p, i);
+            }
+            (part, 0)
+        };
+
+        let mut value = part.value(header, &self.data, &mut p)? as usize;
+        let flags = part.pairs.flags;
+        if flags & 2 != 0 {
+            let idx = self.map_idx[file][WDL_TO_MAP[(wdl + 2) as usize]];
+            value = if flags & 16 == 0 {
+                *self.data.get(self.map + idx + value)? as usize
+            } else {
+                u16_at(&self.data, self.map + 2 * (idx + value))? as usize
+            };
+        }
+
+        // the tables store moves instead of plies where that doesn't lose information
+        let mut plies = value as i32;
+        if flags & PA_FLAGS[(wdl + 2) as usize] == 0 || wdl % 2 != 0 {
+            plies *= 2;
+        }
+
+        Some(Dtz::Plies(plies + 1))
+    }
+}
+
+/// The files of one set of pieces, read when they are first probed.
+struct TableFiles {
+    header: Header,
+    wdl_path: Option<PathBuf>,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<WdlTable>>,
+    dtz: OnceLock<Option<DtzTable>>,
+}
+
+impl TableFiles {
+    fn wdl(&self) -> Option<&WdlTable> {
+        self.wdl.get_or_init(|| WdlTable::read(&self.header, fs::read(self.wdl_path.as_ref()?).ok()?)).as_ref()
+    }
+
+    fn dtz(&self) -> Option<&DtzTable> {
+        self.dtz.get_or_init(|| DtzTable::read(&self.header, fs::read(self.dtz_path.as_ref()?).ok()?)).as_ref()
+    }
+}
+
+/// The sign of `x`: -1, 0 or 1.
+fn sign(x: i32) -> i32 {
+    x.signum()
+}
+
+/// The distance to zeroing of a position where the best move captures or moves a pawn.
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    sign(wdl) * if wdl.abs() == 2 { 1 } else { 101 }
+}
+
+fn is_zeroing(board: &impl Board, m: &Move) -> bool {
+    m.extra.is_capturing() || board.piece_at(m.from).is_pawn()
+}
+
+#[derive(Default)]
+pub struct Syzygy {
+    /// By the names of the tables with both colours, like `KQvKR` and `KRvKQ`
+    tables: HashMap<String, Arc<TableFiles>>,
+    max_pieces: usize,
+}
+
+impl Syzygy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the tables in `directory`. They are only read when they are probed.
+    pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let mut paths: HashMap<String, (Option<PathBuf>, Option<PathBuf>)> = HashMap::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let (name, extension) = match (path.file_stem().and_then(|s| s.to_str()), path.extension()) {
+                (Some(name), Some(extension)) => (name.to_string(), extension),
+                _ => continue,
+            };
+            if extension == WDL_EXTENSION {
+                paths.entry(name).or_default().0 = Some(path);
+            } else if extension == DTZ_EXTENSION {
+                paths.entry(name).or_default().1 = Some(path);
+            }
+        }
+
+        let mut syzygy = Self::new();
+        for (name, (wdl_path, dtz_path)) in paths {
+            let header = match Header::parse(&name) {
+                Some(header) => header,
+                None => continue,
+            };
+            let mirrored = normalize(&name, true).expect("the name was parsed");
+            syzygy.max_pieces = syzygy.max_pieces.max(header.num);
+
+            let files = Arc::new(TableFiles {
+                header,
+                wdl_path,
+                dtz_path,
+                wdl: OnceLock::new(),
+                dtz: OnceLock::new(),
+            });
+            syzygy.tables.insert(mirrored, files.clone());
+            syzygy.tables.insert(files.header.key.clone(), files);
+        }
+
+        Ok(syzygy)
+    }
+
+    /// The number of sets of pieces with tables.
+    pub fn len(&self) -> usize {
+        self.tables.values().map(|files| &files.header.key).collect::<HashSet<_>>().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// The most pieces of the tables.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn probe_wdl_table(&self, board: &impl Board) -> Option<i32> {
+        let pieces = Pieces::of(board);
+        if pieces.pieces.len() == 2 {
+            return Some(0);
+        }
+        let key = pieces.key(false);
+        let files = self.tables.get(&key)?;
+
+        files.wdl()?.probe(&files.header, &pieces, &key)
+    }
+
+    fn probe_dtz_table(&self, board: &impl Board, wdl: i32) -> Option<Dtz> {
+        let pieces = Pieces::of(board);
+        let key = pieces.key(false);
+        let files = self.tables.get(&key)?;
+
+        files.dtz()?.probe(&files.header, &pieces, &key, wdl)
+    }
+
+    /// The value of `board` with the captures (and pawn moves when `zeroing`) searched first,
+    /// since the tables may store anything where one of those is the best move. The flag says
+    /// whether the best move is one of them.
+    fn search(&self, board: &impl Board, zeroing: bool) -> Option<(i32, bool)> {
+        let moves = board.all_moves();
+        if moves.is_empty() {
+            let mated = board.is_terminal() == Some(board.current_player().other());
+            return Some((if mated { -2 } else { 0 }, true));
+        }
+
+        let mut best = -2;
+        let mut searched = 0;
+        for m in &moves {
+            if !(m.extra.is_capturing() || zeroing && board.piece_at(m.from).is_pawn()) {
+                continue;
+            }
+            searched += 1;
+
+            let value = -self.search(&board.transition(*m), false)?.0;
+            if value > best {
+                best = value;
+                if value == 2 {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // when all moves were searched, the table (which ignores en passant) isn't needed
+        let all_searched = searched == moves.len();
+        let value = if all_searched { best } else { self.probe_wdl_table(board)? };
+
+        if best >= value {
+            Some((best, best > 0 || all_searched))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    fn fits(&self, board: &impl Board, limit: usize) -> bool {
+        let mut count = 0;
+        for x in 0..8 {
+            for y in 0..8 {
+                if !board.piece_at((x, y)).is_empty() {
+                    count += 1;
+                }
+            }
+        }
+
+        count <= limit.min(self.max_pieces) && !castling_possible(board)
+    }
+
+    /// The result of `board` as if the last move was a capture or pawn move, if it has at most
+    /// `limit` pieces and no castling rights.
+    pub fn probe_wdl(&self, board: &impl Board, limit: usize) -> Option<Wdl> {
+        if !self.fits(board, limit) {
+            return None;
+        }
+
+        self.search(board, false).map(|(wdl, _)| Wdl::from_value(wdl))
+    }
+
+    /// The number of plies to the next capture or pawn move with best play, negative when the
+    /// side to move loses, and past 100 for cursed wins and blessed losses. 0 for draws.
+    fn dtz(&self, board: &impl Board) -> Option<i32> {
+        let (wdl, zeroing) = self.search(board, true)?;
+        if wdl == 0 {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        if let Dtz::Plies(plies) = self.probe_dtz_table(board, wdl)? {
+            return Some((plies + if wdl.abs() == 1 { 100 } else { 0 }) * sign(wdl));
+        }
+
+        // the table has the other side to move, so look one move ahead
+        let mut best = 0xffff;
+        for m in board.all_moves() {
+            let zeroing = is_zeroing(board, &m);
+            let child = board.transition(m);
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&child, false)?.0)
+            } else {
+                -self.dtz(&child)?
+            };
+
+            if dtz == 1 && child.all_moves().is_empty() {
+                best = 1;
+            }
+            if !zeroing {
+                dtz += sign(dtz);
+            }
+            if dtz < best && sign(dtz) == sign(wdl) {
+                best = dtz;
+            }
+        }
+
+        Some(if best == 0xffff { -1 } else { best })
+    }
+
+    /// The move that keeps the best result with the fifty-move rule counted from the halfmove
+    /// clock of `board`: the fastest capture or pawn move when winning, a move that keeps the
+    /// draw, or the longest defence when losing. Returns the move with the result.
+    pub fn best_move(&self, board: &impl Board) -> Option<(Move, Wdl)> {
+        if !self.fits(board, TBPIECES) {
+            return None;
+        }
+
+        let clock = board.halfmove_clock() as i32;
+        let mut best: Option<((i32, i32), Move)> = None;
+        for m in board.all_moves() {
+            let child = board.transition(m);
+            let mut dtz = if child.halfmove_clock() == 0 {
+                dtz_before_zeroing(-self.search(&child, false)?.0)
+            } else {
+                let dtz = -self.dtz(&child)?;
+                dtz + sign(dtz)
+            };
+            if dtz == 2 && child.all_moves().is_empty() && child.is_terminal() == Some(board.current_player()) {
+                dtz = 1;
+            }
+
+            // wins within the fifty-move rule are all as good, and so are losses that can't be saved by it
+            let rank = if dtz > 0 {
+                if dtz + clock <= 99 { MAX_DTZ } else { MAX_DTZ - (dtz + clock) }
+            } else if dtz < 0 {
+                if -dtz * 2 + clock < 100 { -MAX_DTZ } else { -MAX_DTZ + (-dtz + clock) }
+            } else {
+                0
+            };
+
+            // then make progress when winning and hold out when losing
+            let key = (rank, -dtz);
+            if best.as_ref().is_none_or(|(best_key, _)| key > *best_key) {
+                best = Some((key, m));
+            }
+        }
+
+        let ((rank, _), m) = best?;
+        let wdl = match rank {
+            r if r == MAX_DTZ => Wdl::Win,
+            r if r > 0 => Wdl::CursedWin,
+            0 => Wdl::Draw,
+            r if r == -MAX_DTZ => Wdl::Loss,
+            _ => Wdl::BlessedLoss,
+        };
+        Some((m, wdl))
+    }
+}
+
+lazy_static!(static ref SYZYGY: RwLock<Arc<Syzygy>> = RwLock::new(Arc::new(Syzygy::new())););
+
+/// Makes `syzygy` the tables that the solvers probe.
+pub fn install(syzygy: Syzygy) {
+    *SYZYGY.write() = Arc::new(syzygy);
+}
+
+/// Looks up `board` in the installed tables, see `Syzygy::probe_wdl`.
+pub fn probe_wdl(board: &impl Board, limit: usize) -> Option<Wdl> {
+    let syzygy = SYZYGY.read().clone();
+    if syzygy.is_empty() {
+        return None;
+    }
+    syzygy.probe_wdl(board, limit)
+}
+
+/// The move that keeps the best result in the installed tables, see `Syzygy::best_move`.
+pub fn best_move(board: &impl Board) -> Option<(Move, Wdl)> {
+    let syzygy = SYZYGY.read().clone();
+    if syzygy.is_empty() {
+        return None;
+    }
+    syzygy.best_move(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(normalize("KvKQ", false).as_deref(), Some("KQvK"));
+        assert_eq!(normalize("KQvK", true).as_deref(), Some("KvKQ"));
+        assert_eq!(normalize("KRvKQ", false).as_deref(), Some("KQvKR"));
+        assert_eq!(normalize("KNPvKB", false).as_deref(), Some("KNPvKB"));
+
+        let header = Header::parse("KRvKR").unwrap();
+        assert!(header.symmetric);
+        assert_eq!(header.enc_type, 0);
+        assert_eq!(Header::parse("KQQvK").unwrap().enc_type, 2);
+
+        let header = Header::parse("KPvKPP").unwrap();
+        assert_eq!(header.pawns, [1, 2]);
+        assert_eq!(header.num, 5);
+    }
+
+    #[test]
+    fn test_indices() {
+        let kk = &INDICES.kk_index;
+        assert_eq!(kk.iter().flatten().filter(|&&i| i >= 0).count(), 462);
+        assert_eq!(kk.iter().flatten().max(), Some(&461));
+        assert_eq!(6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + 4 * 7 * 6, PIVFAC[0]);
+    }
+
+    /// Every position of a table gets an index below the size of the table.
+    fn check_encoding(name: &str) {
+        let header = Header::parse(name).unwrap();
+        // the order byte and the pieces, as the table would store them for white to move
+        let mut bytes = vec![0, 0];
+        for c in name.chars().filter(|&c| c != 'v') {
+            bytes.push(match c { 'P' => 1, 'N' => 2, 'B' => 3, 'R' => 4, 'Q' => 5, _ => 6 });
+        }
+        let black = name.find('v').unwrap();
+        for (i, byte) in bytes[2..].iter_mut().enumerate() {
+            if i >= black {
+                *byte |= 8;
+            }
+        }
+        // leading pawns first, then the other pawns, then the other pieces by how often they appear
+        let mut pieces = bytes[2..].to_vec();
+        if header.has_pawns {
+            pieces.sort_by_key(|&p| (p & 7 != 1, p));
+        } else {
+            let counts = pieces.clone();
+            pieces.sort_by_key(|&p| (counts.iter().filter(|&&q| q == p).count(), p));
+        }
+        bytes.truncate(2);
+        bytes.extend(pieces);
+
+        for file in 0..if header.has_pawns { 4 } else { 1 } {
+            let encoding = if header.has_pawns {
+                Encoding::pawns(&header, &bytes, if header.pawns[1] > 0 { 0 } else { 1 }, file, false).unwrap()
+            } else {
+                Encoding::pieces(&header, &bytes, 1, false).unwrap()
+            };
+
+            let mut p = [0; TBPIECES];
+            for a in 0..64 {
+                for b in 0..64 {
+                    for c in 0..64 {
+                        let squares = [a, b, c];
+                        if a == b || b == c || a == c {
+                            continue;
+                        }
+                        if header.has_pawns && (encoding.pieces.iter().zip(squares).any(|(&piece, sq)| piece & 7 == 1 && !(8..56).contains(&sq))
+                            || FILE_TO_FILE[a & 7] != file) {
+                            continue;
+                        }
+                        p[..3].copy_from_slice(&squares);
+                        let idx = if header.has_pawns {
+                            pawn_file(&header, &mut p);
+                            encoding.encode_pawn(&header, &mut p)
+                        } else {
+                            encoding.encode_piece(&header, &mut p)
+                        };
+                        assert!(idx < encoding.size, "{} {:?} has index {} of {}", name, squares, idx, encoding.size);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_encoding() {
+        check_encoding("KQvK");
+        check_encoding("KPvK");
+    }
+}
@@ -1,54 +1,390 @@
 use crate::stats::TranspositionTableStats;
+use crate::game_engine::chess_move::{Move, Extra};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
-pub struct TranspositionTable<T> {
-    data: Vec<Option<(T, u64)>>,
-    pub collisions: u64,
-    pub used: u64,
+/// How the stored value relates to the real value of the position.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Bound {
+    Exact,
+    /// The real value is at least the stored value (the search failed high)
+    Lower,
+    /// The real value is at most the stored value (the search failed low)
+    Upper,
 }
 
+impl Bound {
+    /// The bound a search result represents, when it was searched with the window (a, b)
+    pub fn from_window(value: f64, a: f64, b: f64) -> Self {
+        if value <= a {
+            Bound::Upper
+        } else if value >= b {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        }
+    }
+}
 
-impl<T> TranspositionTable<T> {
-    pub fn new(size: u64) -> Self<> {
-        let mut data = Vec::new();
-        data.reserve_exact(size as usize);
-        for _ in 0..size {
-            data.push(None)
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub depth: u64,
+    pub value: f64,
+    pub bound: Bound,
+    /// The best move found, searched first when we get to this position again
+    pub best_move: Option<Move>,
+}
+
+/// A decoded slot of the table.
+#[derive(Clone, Copy)]
+struct Slot {
+    hash: u64,
+    entry: Entry,
+    generation: u8,
+}
+
+const DEPTH_MASK: u64 = 0xffff;
+const BOUND_SHIFT: u64 = 16;
+const GENERATION_SHIFT: u64 = 18;
+const MOVE_SHIFT: u64 = 26;
+const HAS_MOVE: u64 = 1 << 42;
+const OCCUPIED: u64 = 1 << 43;
+
+fn pack_move(m: Move) -> u64 {
+    (m.from.x as u64)
+        | (m.from.y as u64) << 3
+        | (m.to.x as u64) << 6
+        | (m.to.y as u64) << 9
+        | (m.extra as u64) << 12
+}
+
+fn unpack_move(bits: u64) -> Option<Move> {
+    let location = |shift: u64| ((bits >> shift & 7) as i8, (bits >> (shift + 3) & 7) as i8);
+    let extra = Extra::from_number((bits >> 12 & 0xf) as u8)?;
+
+    Some((location(0), location(6), extra).into())
+}
+
+/// A slot that can be read and written by several threads at once without locking.
+/// The key is stored xor-ed with the data, so an entry that was torn by two threads
+/// writing at the same time no longer matches the hash of either of them, and is ignored.
+#[derive(Default)]
+struct AtomicSlot {
+    key: AtomicU64,
+    value: AtomicU64,
+    data: AtomicU64,
+}
+
+impl AtomicSlot {
+    fn load(&self) -> Option<Slot> {
+        let key = self.key.load(Ordering::Relaxed);
+        let value = self.value.load(Ordering::Relaxed);
+        let data = self.data.load(Ordering::Relaxed);
+
+        if data & OCCUPIED == 0 {
+            return None;
         }
+
+        let bound = match data >> BOUND_SHIFT & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+
+        let best_move = if data & HAS_MOVE != 0 {
+            unpack_move(data >> MOVE_SHIFT)
+        } else {
+            None
+        };
+
+        Some(Slot {
+            hash: key ^ value ^ data,
+            entry: Entry {
+                depth: data & DEPTH_MASK,
+                value: f64::from_bits(value),
+                bound,
+                best_move,
+            },
+            generation: (data >> GENERATION_SHIFT) as u8,
+        })
+    }
+
+    fn store(&self, slot: Slot) {
+        let bound = match slot.entry.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+
+        let mut data = slot.entry.depth.min(DEPTH_MASK)
+            | bound << BOUND_SHIFT
+            | (slot.generation as u64) << GENERATION_SHIFT
+            | OCCUPIED;
+
+        if let Some(m) = slot.entry.best_move {
+            data |= pack_move(m) << MOVE_SHIFT | HAS_MOVE;
+        }
+
+        let value = slot.entry.value.to_bits();
+
+        self.key.store(slot.hash ^ value ^ data, Ordering::Relaxed);
+        self.value.store(value, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// The number of entries that share an index. The last one is always replaced,
+/// the others only by deeper searches or when they are from an older search.
+const BUCKET_SIZE: usize = 4;
+
+type Bucket = [AtomicSlot; BUCKET_SIZE];
+
+/// A transposition table that can be shared between search threads.
+/// Entries written by other threads may be missed or overwritten, but never read corrupted.
+pub struct TranspositionTable {
+    data: Vec<Bucket>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Creates a table of at most `size_mb` megabytes. The number of buckets
+    /// is always a power of two, so they can be indexed with a mask.
+    pub fn new(size_mb: u64) -> Self {
+        let max_buckets = ((size_mb * 1024 * 1024) as usize / size_of::<Bucket>()).max(1);
+
+        let mut buckets = 1;
+        while buckets * 2 <= max_buckets {
+            buckets *= 2;
+        }
+
         Self {
-            data,
-            collisions: 0,
-            used: 0,
+            data: (0..buckets).map(|_| Default::default()).collect(),
+            generation: AtomicU8::new(0),
         }
     }
 
+    /// The number of entries the table can hold.
     pub fn len(&self) -> u64 {
-        self.data.len() as u64
+        (self.data.len() * BUCKET_SIZE) as u64
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash & (self.data.len() as u64 - 1)) as usize
+    }
+
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
     }
 
-    pub fn insert(&mut self, hash: u64, value: T, stats: &mut TranspositionTableStats) {
-        let len = self.data.len();
-        let a = &mut self.data[(hash % len as u64) as usize];
+    /// Should be called before every new search. Entries from earlier searches are still used,
+    /// but get replaced before anything from the current search.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn insert(&self, hash: u64, mut value: Entry, stats: &mut TranspositionTableStats) {
+        let generation = self.generation();
+        let bucket = &self.data[self.index(hash)];
+        let slots: Vec<Option<Slot>> = bucket.iter().map(AtomicSlot::load).collect();
+
+        // the same position is always overwritten, but don't forget its best move
+        if let Some(i) = slots.iter().position(|s| s.is_some_and(|s| s.hash == hash)) {
+            if value.best_move.is_none() {
+                value.best_move = slots[i].unwrap().entry.best_move;
+            }
+            bucket[i].store(Slot { hash, entry: value, generation });
+            stats.colisionless_insert();
+            return;
+        }
 
-        if a.is_some() {
+        // otherwise use an empty slot, or replace the least valuable of the depth preferred slots:
+        // ones from earlier searches first, then the shallowest. When even that one is more
+        // valuable than the new entry, the always replace slot is used instead.
+        let replace = if let Some(empty) = slots.iter().position(|s| s.is_none()) {
+            empty
+        } else {
+            let (replace, least_valuable) = slots[..BUCKET_SIZE - 1].iter()
+                .flatten()
+                .enumerate()
+                .min_by_key(|(_, s)| (s.generation == generation, s.entry.depth))
+                .unwrap();
+
+            if least_valuable.generation == generation && least_valuable.entry.depth > value.depth {
+                BUCKET_SIZE - 1
+            } else {
+                replace
+            }
+        };
+
+        if slots[replace].is_some() {
             stats.collision()
         } else {
             stats.colisionless_insert()
         }
-        *a = Some((value, hash));
+
+        bucket[replace].store(Slot {
+            hash,
+            entry: value,
+            generation,
+        });
+    }
+
+    pub fn get(&self, hash: u64) -> Option<Entry> {
+        self.data[self.index(hash)].iter()
+            .filter_map(AtomicSlot::load)
+            .find(|s| s.hash == hash)
+            .map(|s| s.entry)
     }
 
-    pub fn get(&self, hash: u64) -> Option<&T> {
-        let len = self.data.len();
-        let res = self.data[(hash % len as u64) as usize].as_ref();
+    /// The occupancy of the table in permille, estimated from the first thousand entries.
+    /// Only entries written during the current search count.
+    pub fn hashfull(&self) -> u64 {
+        let generation = self.generation();
+        let mut used = 0;
+        let mut total = 0;
 
-        if let Some((i, found_hash)) = res {
-            if *found_hash != hash {
-                return None
-            } else {
-                return Some(i)
+        for slot in self.data.iter().flatten().take(1000) {
+            total += 1;
+            if let Some(s) = slot.load() {
+                if s.generation == generation {
+                    used += 1;
+                }
             }
-        } else {
-            None
+        }
+
+        used * 1000 / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transposition_table::{TranspositionTable, Entry, Bound, BUCKET_SIZE};
+    use crate::stats::TranspositionTableStats;
+    use crate::game_engine::chess_move::Extra;
+
+    fn entry(depth: u64) -> Entry {
+        Entry {
+            depth,
+            value: depth as f64,
+            bound: Bound::Exact,
+            best_move: Some(((0, 0), (0, depth as i8 % 8)).into()),
+        }
+    }
+
+    #[test]
+    fn test_size_power_of_two() {
+        let table = TranspositionTable::new(1);
+        assert!(table.data.len().is_power_of_two());
+        assert!(table.data.len() * std::mem::size_of::<super::Bucket>() <= 1024 * 1024);
+        assert!(table.data.len() * 2 * std::mem::size_of::<super::Bucket>() > 1024 * 1024);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let table = TranspositionTable::new(1);
+        let mut stats = TranspositionTableStats::new();
+        let buckets = table.data.len() as u64;
+
+        // all of these hashes end up in the same bucket
+        let hash = |i: u64| 7 + i * buckets;
+
+        for i in 0..BUCKET_SIZE as u64 {
+            table.insert(hash(i), entry(5 + i), &mut stats);
+        }
+        for i in 0..BUCKET_SIZE as u64 {
+            assert_eq!(table.get(hash(i)).unwrap().depth, 5 + i);
+        }
+
+        // a shallow entry doesn't replace any of the deeper ones, only the always replace slot
+        table.insert(hash(10), entry(1), &mut stats);
+        assert!(table.get(hash(BUCKET_SIZE as u64 - 1)).is_none());
+        assert!(table.get(hash(10)).is_some());
+        for i in 0..BUCKET_SIZE as u64 - 1 {
+            assert!(table.get(hash(i)).is_some());
+        }
+
+        // a deep one replaces the shallowest of the depth preferred slots
+        table.insert(hash(11), entry(20), &mut stats);
+        assert!(table.get(hash(0)).is_none());
+        assert_eq!(table.get(hash(11)).unwrap().depth, 20);
+
+        // after a new search, old entries are replaced first, regardless of depth
+        table.new_search();
+        table.insert(hash(12), entry(1), &mut stats);
+        assert_eq!(table.get(hash(12)).unwrap().depth, 1);
+        assert!(table.get(hash(11)).is_some());
+    }
+
+    #[test]
+    fn test_keeps_best_move() {
+        let table = TranspositionTable::new(1);
+        let mut stats = TranspositionTableStats::new();
+
+        table.insert(42, entry(3), &mut stats);
+        table.insert(42, Entry { best_move: None, ..entry(4) }, &mut stats);
+
+        let found = table.get(42).unwrap();
+        assert_eq!(found.depth, 4);
+        assert_eq!(found.best_move, entry(3).best_move);
+    }
+
+    #[test]
+    fn test_hashfull() {
+        let table = TranspositionTable::new(1);
+        let mut stats = TranspositionTableStats::new();
+        assert_eq!(table.hashfull(), 0);
+
+        // fills exactly the first 500 entries
+        for i in 0..500 / BUCKET_SIZE as u64 {
+            for j in 0..BUCKET_SIZE as u64 {
+                table.insert(i + j * table.data.len() as u64, entry(1), &mut stats);
+            }
+        }
+        assert_eq!(table.hashfull(), 500);
+
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_move_packing() {
+        let moves: Vec<crate::game_engine::chess_move::Move> = vec![
+            ((0, 0), (7, 7)).into(),
+            ((4, 7), (6, 7), Extra::KingCastle).into(),
+            ((3, 1), (2, 0), Extra::QueenPromotionCapture).into(),
+            ((4, 3), (5, 2), Extra::EnPassantCaptures).into(),
+        ];
+
+        for m in moves {
+            assert_eq!(super::unpack_move(super::pack_move(m)), Some(m));
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_concurrent_access() {
+        let table = TranspositionTable::new(1);
+        let buckets = table.data.len() as u64;
+
+        // many threads writing different entries to the same few buckets. Every entry that
+        // is found has to be exactly the one that was written for that hash.
+        std::thread::scope(|scope| {
+            for thread in 0..4u64 {
+                let table = &table;
+                scope.spawn(move || {
+                    let mut stats = TranspositionTableStats::new();
+                    for i in 0..20_000u64 {
+                        let hash = (i % 16) * buckets + (i % 3) + thread * 7919 * buckets;
+                        table.insert(hash, entry(hash % 100), &mut stats);
+
+                        if let Some(found) = table.get(hash ^ buckets) {
+                            let expected = entry((hash ^ buckets) % 100);
+                            assert_eq!(found.depth, expected.depth);
+                            assert_eq!(found.value, expected.value);
+                            assert_eq!(found.best_move, expected.best_move);
+                        }
+                    }
+                });
+            }
+        });
+    }
+}